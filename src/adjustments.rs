@@ -0,0 +1,715 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Image adjustments of the versions.
+
+use std::collections::BTreeMap;
+
+use exempi2::Xmp;
+
+use crate::audit::{
+    audit_get_array_value, audit_get_bool_value, audit_get_dict_value, Report, SkipReason,
+};
+use crate::plutils::Value;
+use crate::plutils::{get_bool_value, get_data_value, get_int_value, get_str_value, unarchive};
+use crate::rect::Rect;
+use crate::xmp::ns::NS_CAMERA_RAW;
+use crate::xmp::{ToXmp, XmpProperty};
+
+/// A typed adjustment operation. The parameters are the `inputKeys`
+/// of the operation. Missing parameters are `None`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// RAW Fine Tuning. `RKRawDecodeOperation`.
+    RawDecode {
+        /// `inputMethodVersion`. The RAW decoder version.
+        method_version: Option<String>,
+        boost: Option<f64>,
+        boost_preserve_hue: Option<f64>,
+    },
+    /// Exposure. `RKExposureOperation`. Exposure is in EV.
+    Exposure {
+        exposure: Option<f64>,
+        recovery: Option<f64>,
+        black_point: Option<f64>,
+        brightness: Option<f64>,
+    },
+    /// White balance. `RKWhiteBalanceOperation`. Temperature in K.
+    WhiteBalance {
+        temperature: Option<f64>,
+        tint: Option<f64>,
+    },
+    /// Enhance. `RKEnhanceOperation`.
+    Enhance {
+        contrast: Option<f64>,
+        definition: Option<f64>,
+        saturation: Option<f64>,
+        vibrancy: Option<f64>,
+    },
+    /// Levels. `RKLevelsOperation`. Values from 0 to 1.
+    Levels {
+        black: Option<f64>,
+        gray: Option<f64>,
+        white: Option<f64>,
+    },
+    /// Curves. `RKCurvesOperation`. The points from 0 to 1.
+    Curves { points: Vec<(f64, f64)> },
+    /// Highlights & Shadows. `RKHighlightsAndShadowsOperation`.
+    HighlightsShadows {
+        highlights: Option<f64>,
+        shadows: Option<f64>,
+    },
+    /// Crop. `RKCropOperation`. In pixels of the master, the origin
+    /// is bottom left.
+    Crop {
+        x: Option<f64>,
+        y: Option<f64>,
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    /// Straighten. `RKStraightenCropOperation`. Angle in degrees.
+    Straighten { angle: Option<f64> },
+    /// An operation that isn't typed. See the parameters.
+    Other,
+}
+
+/// An adjustment from `RKImageAdjustments`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Adjustment {
+    pub uuid: Option<String>,
+    pub model_id: Option<i64>,
+    /// Position in the stack. `adjIndex`.
+    pub index: i64,
+    /// Whether the adjustment is applied. `isEnabled`.
+    pub enabled: bool,
+    /// The operation identifier, like `RKExposureOperation`.
+    pub identifier: String,
+    /// The name displayed in the UI.
+    pub display_name: Option<String>,
+    /// The typed operation.
+    pub operation: Operation,
+    /// uuid of the brush mask, for brushed adjustments. The mask
+    /// isn't loaded.
+    pub mask: Option<String>,
+    /// All the parameters, by name.
+    pub parameters: BTreeMap<String, Value>,
+}
+
+/// The adjustments of a version.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Adjustments {
+    /// `adjustmentProperties.RawDecodeVersion`.
+    pub raw_decode_version: Option<String>,
+    /// `hasAdjustments`.
+    pub has_adjustments: Option<bool>,
+    /// `hasEnabledAdjustments`. If any adjustment past RAW decode is
+    /// enabled.
+    pub has_enabled_adjustments: Option<bool>,
+    /// The adjustments, sorted by index.
+    pub stack: Vec<Adjustment>,
+}
+
+fn get_real(parameters: &BTreeMap<String, Value>, key: &str) -> Option<f64> {
+    match parameters.get(key) {
+        Some(Value::Real(f)) => Some(*f),
+        Some(Value::Integer(n)) => n.as_signed().map(|n| n as f64),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parse a point, either an array of two numbers or a string
+/// like `{x, y}`.
+fn parse_point(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Array(array) if array.len() == 2 => {
+            let coord = |value: &Value| match value {
+                Value::Real(f) => Some(*f),
+                Value::Integer(n) => n.as_signed().map(|n| n as f64),
+                _ => None,
+            };
+            Some((coord(&array[0])?, coord(&array[1])?))
+        }
+        Value::String(s) => {
+            let mut coords = s
+                .trim_matches(|c| c == '{' || c == '}')
+                .split(',')
+                .map(|coord| coord.trim().parse::<f64>());
+            match (coords.next(), coords.next(), coords.next()) {
+                (Some(Ok(x)), Some(Ok(y)), None) => Some((x, y)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Operation {
+    /// Create the operation `identifier` from the `parameters`.
+    /// Return `None` if the operation is unknown.
+    fn from(identifier: &str, parameters: &BTreeMap<String, Value>) -> Option<Operation> {
+        let real = |key| get_real(parameters, key);
+        let operation = match identifier {
+            "RKRawDecodeOperation" => Operation::RawDecode {
+                method_version: parameters
+                    .get("inputMethodVersion")
+                    .and_then(Value::as_string)
+                    .map(str::to_owned),
+                boost: real("inputBoostAmount"),
+                boost_preserve_hue: real("inputBoostPreserveHueAmount"),
+            },
+            "RKExposureOperation" => Operation::Exposure {
+                exposure: real("inputExposure"),
+                recovery: real("inputRecovery"),
+                black_point: real("inputBlackPoint"),
+                brightness: real("inputBrightness"),
+            },
+            "RKWhiteBalanceOperation" => Operation::WhiteBalance {
+                temperature: real("inputTemperature"),
+                tint: real("inputTint"),
+            },
+            "RKEnhanceOperation" => Operation::Enhance {
+                contrast: real("inputContrast"),
+                definition: real("inputDefinition"),
+                saturation: real("inputSaturation"),
+                vibrancy: real("inputVibrancy"),
+            },
+            "RKLevelsOperation" => Operation::Levels {
+                black: real("inputBlackLevel"),
+                gray: real("inputGrayLevel"),
+                white: real("inputWhiteLevel"),
+            },
+            "RKCurvesOperation" => Operation::Curves {
+                points: parameters
+                    .get("inputPoints")
+                    .and_then(Value::as_array)
+                    .map(|points| points.iter().filter_map(parse_point).collect())
+                    .unwrap_or_default(),
+            },
+            "RKHighlightsAndShadowsOperation" => Operation::HighlightsShadows {
+                highlights: real("inputHighlights"),
+                shadows: real("inputShadows"),
+            },
+            "RKCropOperation" => Operation::Crop {
+                x: real("inputXOrigin"),
+                y: real("inputYOrigin"),
+                width: real("inputWidth"),
+                height: real("inputHeight"),
+            },
+            "RKStraightenCropOperation" => Operation::Straighten {
+                angle: real("inputAngle"),
+            },
+            // Known, but not typed.
+            "RKBlackAndWhiteOperation"
+            | "RKColorMonochromeOperation"
+            | "RKSepiaOperation"
+            | "RKColorOperation"
+            | "RKSharpenOperation"
+            | "RKEdgeSharpenOperation"
+            | "RKNoiseReductionOperation"
+            | "RKVignetteOperation"
+            | "RKDevignetteOperation"
+            | "RKChromaticAberrationOperation"
+            | "RKRedEyeOperation"
+            | "RKRetouchOperation"
+            | "RKSpotAndPatchOperation"
+            | "RKFlipOperation"
+            | "RKDodgeOperation"
+            | "RKBurnOperation"
+            | "RKPolishOperation" => Operation::Other,
+            _ => return None,
+        };
+        Some(operation)
+    }
+}
+
+impl Adjustment {
+    /// Create the adjustment from the `RKImageAdjustments` item
+    /// `dict`. The operation is archived in `data`. `key` is used
+    /// for the audit.
+    fn from(
+        dict: &plist::Dictionary,
+        key: &str,
+        auditor: &mut Option<&mut Report>,
+    ) -> Option<Adjustment> {
+        let archive = get_data_value(dict, "data").and_then(|data| unarchive(&data));
+        let archive = archive.as_ref().and_then(Value::as_dictionary);
+        let identifier = archive
+            .and_then(|archive| get_str_value(archive, "DGOperationIdentifier"))
+            .or_else(|| get_str_value(dict, "name"));
+        let identifier = match identifier {
+            Some(identifier) => identifier,
+            None => {
+                if let Some(ref mut report) = *auditor {
+                    report.skip(key, SkipReason::InvalidData);
+                }
+                return None;
+            }
+        };
+        let parameters: BTreeMap<String, Value> = archive
+            .and_then(|archive| archive.get("inputKeys"))
+            .and_then(Value::as_dictionary)
+            .map(|input| input.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        let mask = parameters
+            .iter()
+            .find(|(k, _)| k.contains("Mask"))
+            .and_then(|(_, v)| v.as_string())
+            .map(str::to_owned);
+
+        let operation = Operation::from(&identifier, &parameters).unwrap_or_else(|| {
+            if let Some(ref mut report) = *auditor {
+                report.skip(&format!("{}.{}", key, identifier), SkipReason::UnknownProp);
+            }
+            Operation::Other
+        });
+
+        Some(Adjustment {
+            uuid: get_str_value(dict, "uuid"),
+            model_id: get_int_value(dict, "modelId"),
+            index: get_int_value(dict, "adjIndex").unwrap_or(0),
+            enabled: get_bool_value(dict, "isEnabled")
+                .or_else(|| archive.and_then(|archive| get_bool_value(archive, "enabled")))
+                .unwrap_or(true),
+            display_name: archive
+                .and_then(|archive| get_str_value(archive, "DGOperationDisplayName")),
+            identifier,
+            operation,
+            mask,
+            parameters,
+        })
+    }
+}
+
+impl Adjustments {
+    /// Load the adjustments from the version plist `dict`.
+    pub fn from(
+        dict: &plist::Dictionary,
+        auditor: &mut Option<&mut Report>,
+    ) -> Option<Adjustments> {
+        let properties = audit_get_dict_value(dict, "adjustmentProperties", auditor);
+        let array = audit_get_array_value(dict, "RKImageAdjustments", auditor);
+        if properties.is_none() && array.is_none() {
+            return None;
+        }
+
+        let mut stack: Vec<Adjustment> = array
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_dictionary)
+            .filter_map(|item| Adjustment::from(item, "RKImageAdjustments", auditor))
+            .collect();
+        stack.sort_by_key(|adjustment| adjustment.index);
+
+        Some(Adjustments {
+            raw_decode_version: properties
+                .as_ref()
+                .and_then(|properties| get_str_value(properties, "RawDecodeVersion")),
+            has_adjustments: audit_get_bool_value(dict, "hasAdjustments", auditor),
+            has_enabled_adjustments: audit_get_bool_value(dict, "hasEnabledAdjustments", auditor),
+            stack,
+        })
+    }
+
+    /// Return the enabled adjustments.
+    pub fn enabled(&self) -> impl Iterator<Item = &Adjustment> {
+        self.stack.iter().filter(|adjustment| adjustment.enabled)
+    }
+
+    /// Return the enabled crop, normalized to the `master_size` with
+    /// the origin at the top left.
+    pub fn crop_rect(&self, master_size: Option<(i64, i64)>) -> Option<Rect> {
+        let (master_width, master_height) = master_size.filter(|(w, h)| *w > 0 && *h > 0)?;
+        let (master_width, master_height) = (master_width as f64, master_height as f64);
+        self.enabled()
+            .filter_map(|adjustment| match adjustment.operation {
+                Operation::Crop {
+                    x: Some(x),
+                    y: Some(y),
+                    width: Some(width),
+                    height: Some(height),
+                } => Some(Rect {
+                    x: x / master_width,
+                    y: 1.0 - (y + height) / master_height,
+                    width: width / master_width,
+                    height: height / master_height,
+                }),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Translate the enabled adjustments to Camera Raw Settings, as
+    /// closely as possible. `master_size` is the width and height of
+    /// the master, needed for the crop.
+    pub fn camera_raw_settings(&self, master_size: Option<(i64, i64)>) -> CameraRawSettings {
+        let mut settings = CameraRawSettings::default();
+        for adjustment in self.enabled() {
+            settings.translate(adjustment, master_size);
+        }
+        if !settings.properties.is_empty() || !settings.tone_curve.is_empty() {
+            // Process version 2012, for the `*2012` properties.
+            settings.set("ProcessVersion", Some("6.7".to_string()));
+            settings.set("HasSettings", Some("True".to_string()));
+        }
+        settings
+    }
+}
+
+/// The Camera Raw Settings (`crs:`) translated from the adjustments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraRawSettings {
+    /// The simple properties, by name.
+    pub properties: BTreeMap<&'static str, String>,
+    /// The `ToneCurvePV2012` points, from 0 to 255.
+    pub tone_curve: Vec<(i64, i64)>,
+    /// The enabled adjustments that couldn't be translated. The
+    /// operation identifier, or `identifier.parameter`.
+    pub untranslated: Vec<String>,
+    /// The `Highlights2012` value. The Exposure recovery and the
+    /// Highlights & Shadows both add to it.
+    highlights: Option<f64>,
+}
+
+/// Format `value` as a signed integer clamped to +/-100, the range
+/// of most of the `crs:` sliders.
+fn slider(value: f64) -> String {
+    format!("{:+}", value.round().clamp(-100.0, 100.0) as i64)
+}
+
+impl CameraRawSettings {
+    fn set(&mut self, name: &'static str, value: Option<String>) {
+        if let Some(value) = value {
+            self.properties.insert(name, value);
+        }
+    }
+
+    /// Add `value` to `Highlights2012`.
+    fn add_highlights(&mut self, value: Option<f64>) {
+        if let Some(value) = value {
+            let highlights = self.highlights.unwrap_or(0.0) + value;
+            self.highlights = Some(highlights);
+            self.set("Highlights2012", Some(slider(highlights)));
+        }
+    }
+
+    /// Translate `adjustment`. `master_size` is needed for the crop.
+    fn translate(&mut self, adjustment: &Adjustment, master_size: Option<(i64, i64)>) {
+        let identifier = &adjustment.identifier;
+        match adjustment.operation {
+            // The RAW decoder settings are the baseline of the other
+            // converters.
+            Operation::RawDecode { .. } => {}
+            Operation::Exposure {
+                exposure,
+                recovery,
+                black_point,
+                brightness,
+            } => {
+                self.set("Exposure2012", exposure.map(|ev| format!("{:+.2}", ev)));
+                // Recovery goes from 0 to 1.5.
+                self.add_highlights(recovery.map(|recovery| -recovery / 1.5 * 100.0));
+                self.set("Blacks2012", black_point.map(|black| slider(-black)));
+                // Brightness only exists in the older process versions.
+                if brightness.is_some_and(|brightness| brightness != 0.0) {
+                    self.untranslated
+                        .push(format!("{}.inputBrightness", identifier));
+                }
+            }
+            Operation::WhiteBalance { temperature, tint } => {
+                if temperature.is_some() || tint.is_some() {
+                    self.set("WhiteBalance", Some("Custom".to_string()));
+                }
+                self.set(
+                    "Temperature",
+                    temperature.map(|temperature| format!("{}", temperature.round() as i64)),
+                );
+                self.set("Tint", tint.map(slider));
+            }
+            Operation::Enhance {
+                contrast,
+                definition,
+                saturation,
+                vibrancy,
+            } => {
+                self.set(
+                    "Contrast2012",
+                    contrast.map(|contrast| slider(contrast * 100.0)),
+                );
+                self.set(
+                    "Clarity2012",
+                    definition.map(|definition| slider(definition * 100.0)),
+                );
+                // Saturation is neutral at 1.
+                self.set(
+                    "Saturation",
+                    saturation.map(|saturation| slider((saturation - 1.0) * 100.0)),
+                );
+                self.set(
+                    "Vibrance",
+                    vibrancy.map(|vibrancy| slider(vibrancy * 100.0)),
+                );
+            }
+            Operation::HighlightsShadows {
+                highlights,
+                shadows,
+            } => {
+                self.add_highlights(highlights.map(|highlights| -highlights));
+                self.set("Shadows2012", shadows.map(slider));
+            }
+            Operation::Curves { ref points } => {
+                self.tone_curve = points
+                    .iter()
+                    .map(|(x, y)| {
+                        let scale = |v: f64| (v * 255.0).round().clamp(0.0, 255.0) as i64;
+                        (scale(*x), scale(*y))
+                    })
+                    .collect();
+                if !self.tone_curve.is_empty() {
+                    // Otherwise the curve is taken as linear.
+                    self.set("ToneCurveName2012", Some("Custom".to_string()));
+                }
+            }
+            Operation::Crop {
+                x: Some(x),
+                y: Some(y),
+                width: Some(width),
+                height: Some(height),
+            } if master_size.is_some_and(|(w, h)| w > 0 && h > 0) => {
+                let (master_width, master_height) = master_size.unwrap();
+                let (master_width, master_height) = (master_width as f64, master_height as f64);
+                // The origin of the Aperture crop is bottom left, the
+                // origin of `crs:` is top left. Both are unrotated.
+                let crop = [
+                    ("CropLeft", x / master_width),
+                    ("CropTop", 1.0 - (y + height) / master_height),
+                    ("CropRight", (x + width) / master_width),
+                    ("CropBottom", 1.0 - y / master_height),
+                ];
+                for (name, value) in crop {
+                    self.set(name, Some(format!("{:.6}", value.clamp(0.0, 1.0))));
+                }
+                self.set("HasCrop", Some("True".to_string()));
+            }
+            Operation::Straighten { angle: Some(angle) } => {
+                // Aperture rotates counter-clockwise.
+                self.set("CropAngle", Some(format!("{:.2}", -angle)));
+                self.set("HasCrop", Some("True".to_string()));
+            }
+            Operation::Straighten { angle: None } => {}
+            Operation::Crop { .. } | Operation::Levels { .. } | Operation::Other => {
+                self.untranslated.push(identifier.clone())
+            }
+        }
+    }
+}
+
+impl ToXmp for CameraRawSettings {
+    fn to_xmp(&self, xmp: &mut Xmp) -> bool {
+        let mut result = true;
+        for (name, value) in &self.properties {
+            result &= XmpProperty::new(NS_CAMERA_RAW, name).put_into_xmp(value, xmp);
+        }
+        // Replace the curve, the points would be appended otherwise.
+        if !self.tone_curve.is_empty() && xmp.has_property(NS_CAMERA_RAW, "ToneCurvePV2012") {
+            result &= xmp
+                .delete_property(NS_CAMERA_RAW, "ToneCurvePV2012")
+                .is_ok();
+        }
+        for (x, y) in &self.tone_curve {
+            result &= xmp
+                .append_array_item(
+                    NS_CAMERA_RAW,
+                    "ToneCurvePV2012",
+                    exempi2::PropFlags::ARRAY_IS_ORDERED,
+                    &format!("{}, {}", x, y),
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_adjustments_parse() {
+    use crate::plutils::parse_plist;
+    use crate::testutils;
+
+    let plist = parse_plist(testutils::get_test_file_path("Version-0.apversion")).unwrap();
+    let dict = plist.as_dictionary().unwrap();
+    let mut report = Report::new();
+    let adjustments = Adjustments::from(dict, &mut Some(&mut report)).unwrap();
+
+    assert_eq!(adjustments.raw_decode_version.as_deref(), Some("3"));
+    assert_eq!(adjustments.has_adjustments, Some(true));
+    assert_eq!(adjustments.has_enabled_adjustments, Some(false));
+    assert_eq!(adjustments.stack.len(), 1);
+    let raw_decode = &adjustments.stack[0];
+    assert_eq!(raw_decode.identifier, "RKRawDecodeOperation");
+    assert_eq!(raw_decode.display_name.as_deref(), Some("RAW Fine Tuning"));
+    assert_eq!(raw_decode.index, 1_000_000);
+    assert!(raw_decode.enabled);
+    assert_eq!(
+        raw_decode.operation,
+        Operation::RawDecode {
+            method_version: Some("3".to_string()),
+            boost: Some(1.0),
+            boost_preserve_hue: Some(1.0),
+        }
+    );
+    assert!(report.get_skipped().is_empty());
+
+    // Unknown operations are reported.
+    let mut item = plist::Dictionary::new();
+    item.insert("name".to_string(), Value::from("RKFancyOperation"));
+    let adjustment = Adjustment::from(&item, "RKImageAdjustments", &mut Some(&mut report)).unwrap();
+    assert_eq!(adjustment.operation, Operation::Other);
+    assert!(matches!(
+        report
+            .get_skipped()
+            .get("RKImageAdjustments.RKFancyOperation"),
+        Some(SkipReason::UnknownProp)
+    ));
+
+    assert_eq!(parse_point(&Value::from("{0.25, 0.5}")), Some((0.25, 0.5)));
+}
+
+#[cfg(test)]
+#[test]
+fn test_camera_raw_settings() {
+    let adjustment =
+        |index: i64, enabled: bool, identifier: &str, operation: Operation| Adjustment {
+            uuid: None,
+            model_id: None,
+            index,
+            enabled,
+            identifier: identifier.to_string(),
+            display_name: None,
+            operation,
+            mask: None,
+            parameters: BTreeMap::new(),
+        };
+    let adjustments = Adjustments {
+        stack: vec![
+            adjustment(
+                1,
+                true,
+                "RKExposureOperation",
+                Operation::Exposure {
+                    exposure: Some(0.5),
+                    recovery: Some(0.75),
+                    black_point: None,
+                    brightness: Some(0.1),
+                },
+            ),
+            adjustment(
+                2,
+                true,
+                "RKCropOperation",
+                Operation::Crop {
+                    x: Some(100.0),
+                    y: Some(0.0),
+                    width: Some(200.0),
+                    height: Some(50.0),
+                },
+            ),
+            adjustment(3, true, "RKSharpenOperation", Operation::Other),
+            // Disabled adjustments are ignored.
+            adjustment(
+                4,
+                false,
+                "RKWhiteBalanceOperation",
+                Operation::WhiteBalance {
+                    temperature: Some(5000.0),
+                    tint: None,
+                },
+            ),
+            adjustment(
+                5,
+                true,
+                "RKHighlightsAndShadowsOperation",
+                Operation::HighlightsShadows {
+                    highlights: Some(20.0),
+                    shadows: Some(10.0),
+                },
+            ),
+            adjustment(
+                6,
+                true,
+                "RKCurvesOperation",
+                Operation::Curves {
+                    points: vec![(0.0, 0.0), (0.5, 0.6), (1.0, 1.0)],
+                },
+            ),
+        ],
+        ..Default::default()
+    };
+
+    let settings = adjustments.camera_raw_settings(Some((400, 100)));
+    assert_eq!(settings.properties["Exposure2012"], "+0.50");
+    // The recovery and the highlights add up.
+    assert_eq!(settings.properties["Highlights2012"], "-70");
+    assert_eq!(settings.properties["Shadows2012"], "+10");
+    assert_eq!(settings.tone_curve, vec![(0, 0), (128, 153), (255, 255)]);
+    assert_eq!(settings.properties["ToneCurveName2012"], "Custom");
+    assert_eq!(settings.properties["CropLeft"], "0.250000");
+    assert_eq!(settings.properties["CropTop"], "0.500000");
+    assert_eq!(settings.properties["CropRight"], "0.750000");
+    assert_eq!(settings.properties["CropBottom"], "1.000000");
+    assert_eq!(settings.properties["HasCrop"], "True");
+    assert!(!settings.properties.contains_key("Temperature"));
+    assert_eq!(
+        settings.untranslated,
+        vec!["RKExposureOperation.inputBrightness", "RKSharpenOperation"]
+    );
+
+    // Without the master size the crop can't be translated.
+    let settings = adjustments.camera_raw_settings(None);
+    assert!(!settings.properties.contains_key("CropLeft"));
+    assert!(settings
+        .untranslated
+        .contains(&"RKCropOperation".to_string()));
+
+    assert_eq!(
+        adjustments.crop_rect(Some((400, 100))),
+        Some(Rect {
+            x: 0.25,
+            y: 0.5,
+            width: 0.5,
+            height: 0.5
+        })
+    );
+    assert!(adjustments.crop_rect(None).is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_camera_raw_settings_to_xmp() {
+    let settings = CameraRawSettings {
+        tone_curve: vec![(0, 0), (128, 153), (255, 255)],
+        ..Default::default()
+    };
+    let mut xmp = Xmp::new();
+    assert!(settings.to_xmp(&mut xmp));
+    // Writing again doesn't duplicate the curve.
+    assert!(settings.to_xmp(&mut xmp));
+
+    let mut points = vec![];
+    while let Ok(item) = xmp.get_array_item(
+        NS_CAMERA_RAW,
+        "ToneCurvePV2012",
+        points.len() as i32 + 1,
+        &mut exempi2::PropFlags::empty(),
+    ) {
+        points.push(item.to_string());
+    }
+    assert_eq!(points, vec!["0, 0", "128, 153", "255, 255"]);
+}
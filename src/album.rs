@@ -4,6 +4,7 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
+use std::collections::HashMap;
 use std::path::Path;
 
 use chrono::{DateTime, Utc};
@@ -13,17 +14,21 @@ use crate::audit::{
     audit_get_bool_value, audit_get_date_value, audit_get_int_value, audit_get_str_value, Report,
     SkipReason,
 };
-use crate::plutils::{get_array_value, Value};
+use crate::plutils::{get_array_value, get_data_value, Value};
+use crate::query::SmartQuery;
+use crate::sqlutils::get_date_value;
 use crate::store;
 use crate::AplibObject;
 use crate::AplibType;
 use crate::PlistLoadable;
+use crate::{Error, Result, SqliteLoadable};
 
 #[derive(
     Clone, Copy, Debug, Default, num_derive::FromPrimitive, num_derive::ToPrimitive, PartialEq,
 )]
 #[repr(u32)]
 /// Subclass for album
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Subclass {
     #[default]
     /// Invalid.
@@ -38,6 +43,7 @@ pub enum Subclass {
 
 /// Album object.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Album {
     /// uuid
     uuid: Option<String>,
@@ -69,22 +75,28 @@ pub struct Album {
     pub selected_track_path_uuid: Option<String>,
     /// Content list - for `User` subclass
     pub content: Option<Vec<String>>,
+    /// The query of the smart album. `UserQueryInfo`.
+    pub query: Option<SmartQuery>,
+    /// The filter of the album view. `FilterInfo`.
+    pub filter: Option<SmartQuery>,
 }
 
 impl PlistLoadable for Album {
-    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Option<Album>
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Album>
     where
         P: AsRef<Path>,
     {
         use crate::plutils::*;
 
-        let plist = parse_plist(plist_path);
+        let plist = parse_plist(&plist_path)?;
         match plist {
             Value::Dictionary(ref dict) => {
-                let info_dict = get_dict_value(dict, "InfoDictionary")?;
+                let info_dict = get_dict_value(dict, "InfoDictionary").ok_or_else(|| {
+                    Error::MissingProperty(plist_path.as_ref().to_path_buf(), "InfoDictionary")
+                })?;
                 let subclass = audit_get_int_value(&info_dict, "albumSubclass", &mut auditor)
                     .and_then(Subclass::from_i64);
-                let result = Some(Album {
+                let result = Ok(Album {
                     uuid: audit_get_str_value(&info_dict, "uuid", &mut auditor),
                     folder_uuid: audit_get_str_value(&info_dict, "folderUuid", &mut auditor),
                     subclass,
@@ -120,17 +132,67 @@ impl PlistLoadable for Album {
                         &mut auditor,
                     ),
                     content: Album::content_from(dict, &subclass, &mut auditor),
+                    query: Album::query_from(dict, "UserQueryInfo", &mut auditor),
+                    filter: Album::query_from(dict, "FilterInfo", &mut auditor),
                 });
                 if let Some(ref mut auditor) = auditor {
                     auditor.audit_ignored(&info_dict, None);
                 }
                 result
             }
-            _ => None,
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
         }
     }
 }
 
+impl SqliteLoadable for Album {
+    fn tables() -> &'static str {
+        "RKAlbum"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, folderUuid, albumSubclass, albumType, queryFolderUuid, \
+         sortAscending, sortKeyPath, name, createDate, colorLabelIndex, \
+         customSortAvailable, isHidden, isMagic, isFavorite, isInTrash, \
+         selectedTrackPathUuid"
+    }
+
+    /// Load the album from the row. The content is in
+    /// `RKAlbumVersion`, see `Album::content_from_db()`.
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Album {
+            uuid: row.get("uuid")?,
+            folder_uuid: row.get("folderUuid")?,
+            model_id: row.get("modelId")?,
+            subclass: row
+                .get::<_, Option<i64>>("albumSubclass")?
+                .and_then(Subclass::from_i64),
+            album_type: row.get("albumType")?,
+            query_folder_uuid: row.get("queryFolderUuid")?,
+            // Only in the plist.
+            db_version: None,
+            sort_asc: row.get("sortAscending")?,
+            sort_key: row.get("sortKeyPath")?,
+            name: row.get("name")?,
+            custom_sort_available: row.get("customSortAvailable")?,
+            colour_label_index: row.get("colorLabelIndex")?,
+            create_date: get_date_value(row, "createDate")?,
+            is_hidden: row.get("isHidden")?,
+            is_magic: row.get("isMagic")?,
+            is_favourite: row.get("isFavorite")?,
+            is_in_trash: row.get("isInTrash")?,
+            selected_track_path_uuid: row.get("selectedTrackPathUuid")?,
+            content: None,
+            // Only in the plist.
+            query: None,
+            filter: None,
+        })
+    }
+}
+
 impl AplibObject for Album {
     fn obj_type(&self) -> AplibType {
         AplibType::Album
@@ -153,6 +215,52 @@ impl AplibObject for Album {
 }
 
 impl Album {
+    /// Load the content of all the albums from `RKAlbumVersion`
+    /// in the library database `conn`. The key is the album uuid.
+    pub(crate) fn content_from_db(
+        conn: &rusqlite::Connection,
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let mut result: HashMap<String, Vec<String>> = HashMap::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT a.uuid, v.uuid FROM RKAlbumVersion av \
+             JOIN RKAlbum a ON av.albumId = a.modelId \
+             JOIN RKVersion v ON av.versionId = v.modelId",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            result.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+
+        Ok(result)
+    }
+
+    /// Return the modelId of the rows of `RKAlbumVersion` in the
+    /// library database `conn` whose album or version doesn't exist.
+    pub(crate) fn orphan_content_from_db(conn: &rusqlite::Connection) -> Result<Vec<i64>> {
+        let mut stmt = conn.prepare(
+            "SELECT av.modelId FROM RKAlbumVersion av \
+             LEFT JOIN RKAlbum a ON av.albumId = a.modelId \
+             LEFT JOIN RKVersion v ON av.versionId = v.modelId \
+             WHERE a.modelId IS NULL OR v.modelId IS NULL",
+        )?;
+        let result = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+
+        Ok(result)
+    }
+
+    /// Load the query archived in `key`.
+    fn query_from(
+        dict: &plist::Dictionary,
+        key: &str,
+        auditor: &mut Option<&mut Report>,
+    ) -> Option<SmartQuery> {
+        let data = get_data_value(dict, key)?;
+        SmartQuery::from_archive(&data, key, auditor)
+    }
+
     /// Load album content. `dict` should contain the "versionUuids" key.
     /// and the subclass should be `Subclass::User`.
     fn content_from(
@@ -191,7 +299,7 @@ fn test_album_parse() {
         testutils::get_test_file_path("gOnttfpzQoOxcwLpFS9DQg.apalbum").as_path(),
         None,
     );
-    assert!(album.is_some());
+    assert!(album.is_ok());
     let album = album.unwrap();
 
     assert_eq!(album.uuid.as_ref().unwrap(), "gOnttfpzQoOxcwLpFS9DQg");
@@ -204,6 +312,8 @@ fn test_album_parse() {
     assert!(album.sort_asc.unwrap());
     assert_eq!(album.sort_key.as_ref().unwrap(), "exifProperties.ImageDate");
     assert!(album.name.is_none());
+    assert!(album.query.is_none());
+    assert!(album.filter.is_some());
 
     //    let report = album.audit();
     // XXX fix when have actual audit.
@@ -219,7 +329,7 @@ fn test_album_content_parse() {
         testutils::get_test_file_path("x6yNun58SB2sImfCarTJHA.apalbum").as_path(),
         None,
     );
-    assert!(album.is_some());
+    assert!(album.is_ok());
     let album = album.unwrap();
 
     assert_eq!(album.uuid.as_ref().unwrap(), "x6yNun58SB2sImfCarTJHA");
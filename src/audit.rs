@@ -10,7 +10,7 @@ use chrono::{DateTime, Utc};
 
 use crate::plutils::{
     get_array_value, get_bool_value, get_data_value, get_date_value, get_dict_value, get_int_value,
-    get_str_value, Value,
+    get_real_value, get_str_value, Value,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -173,6 +173,21 @@ pub fn audit_get_int_value(
     value
 }
 
+pub fn audit_get_real_value(
+    dict: &plist::Dictionary,
+    key: &str,
+    report: &mut Option<&mut Report>,
+) -> Option<f64> {
+    let value = get_real_value(dict, key);
+    if let Some(ref mut report) = *report {
+        match value {
+            Some(_) => report.parsed(key),
+            _ => report.skip(key, SkipReason::NotFound),
+        }
+    }
+    value
+}
+
 pub fn audit_get_bool_value(
     dict: &plist::Dictionary,
     key: &str,
@@ -6,7 +6,7 @@
 
 use std::io::stderr;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use num_traits::ToPrimitive;
 use pbr::ProgressBar;
 
@@ -17,6 +17,10 @@ use aplib::Library;
 use aplib::ModelInfo;
 use aplib::StoreWrapper;
 use aplib::{AlbumSubclass, PROGRESS_NONE};
+use aplib::{
+    CsvColumn, EmbedOptions, ExistingSidecar, ExportStatus, PairMember, PairPolicy, SidecarNaming,
+    SidecarOptions, StackTarget, VersionSelection, XmpOptions, DEFAULT_CSV_COLUMNS,
+};
 
 mod tree;
 
@@ -33,10 +37,149 @@ enum Command {
     Audit(CommandArgs),
     List(CommandArgs),
     Tree(tree::TreeArgs),
+    /// Verify the plists against the library database.
+    VerifyDb(VerifyArgs),
+    /// Write an XMP sidecar for each version.
+    ExportXmp(ExportXmpArgs),
+    /// Write the versions as CSV.
+    Csv(CsvArgs),
+    /// Write the library into a sqlite database with a stable schema.
+    ExportSqlite(ExportSqliteArgs),
+}
+
+#[derive(Clone, Debug, Parser)]
+struct VerifyArgs {
+    path: String,
+}
+
+#[derive(Clone, Debug, Parser)]
+struct ExportXmpArgs {
+    /// Name the sidecars IMG.xmp instead of IMG.CR2.xmp.
+    #[arg(long)]
+    replace_extension: bool,
+    /// Merge into the existing sidecars.
+    #[arg(long, conflicts_with = "overwrite")]
+    merge: bool,
+    /// Overwrite the existing sidecars.
+    #[arg(long)]
+    overwrite: bool,
+    /// Only export the original versions.
+    #[arg(long, conflicts_with = "non_originals")]
+    originals: bool,
+    /// Only export the versions that aren't the original.
+    #[arg(long)]
+    non_originals: bool,
+    /// Translate the adjustments to Camera Raw Settings.
+    #[arg(long)]
+    camera_raw: bool,
+    /// Also write the keywords to digiKam:TagsList.
+    #[arg(long)]
+    digikam: bool,
+    /// Also write the face regions to MP:RegionInfo.
+    #[arg(long)]
+    mp_regions: bool,
+    /// Write the stack as a keyword: the prefix followed by the
+    /// stack uuid.
+    #[arg(long, conflicts_with = "stack_custom")]
+    stack_keyword: Option<String>,
+    /// Write the stack to aplib:StackUuid and aplib:StackPick.
+    #[arg(long)]
+    stack_custom: bool,
+    /// Which masters of the RAW+JPEG pairs to export.
+    #[arg(long, value_enum, default_value_t)]
+    pairs: Pairs,
+    /// Copy the masters and embed the XMP in the copies. RAW files
+    /// get a sidecar.
+    #[arg(long)]
+    embed: bool,
+    /// The file mapping the volumes to their mount points, see
+    /// `list --mapping`.
+    #[arg(long)]
+    mapping: Option<String>,
+    path: String,
+    dest: String,
+}
+
+#[derive(Clone, Debug, Parser)]
+struct CsvArgs {
+    /// The comma separated columns: uuid, name, file_name,
+    /// version_number, rating, colour_label, flagged, original, hidden,
+    /// in_trash, image_date, capture_date, time_zone, master.uuid,
+    /// master.name, master.file_name, master.type, master.subtype,
+    /// project.uuid, project.name, path, keywords, exif.<key> and
+    /// iptc.<key>.
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+    /// The file to write to. Default to the standard output.
+    #[arg(long, short)]
+    output: Option<String>,
+    /// The file mapping the volumes to their mount points, see
+    /// `list --mapping`.
+    #[arg(long)]
+    mapping: Option<String>,
+    path: String,
+}
+
+#[derive(Clone, Debug, Parser)]
+struct ExportSqliteArgs {
+    /// Print the schema and exit.
+    #[arg(long)]
+    schema: bool,
+    /// The file mapping the volumes to their mount points, see
+    /// `list --mapping`.
+    #[arg(long)]
+    mapping: Option<String>,
+    #[arg(required_unless_present = "schema")]
+    path: Option<String>,
+    /// The database to create.
+    #[arg(required_unless_present = "schema")]
+    dest: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum Pairs {
+    /// The master the version uses.
+    #[default]
+    Current,
+    /// The RAW master.
+    Raw,
+    /// The JPEG master.
+    Jpeg,
+    /// Both masters.
+    Both,
+}
+
+impl From<Pairs> for PairPolicy {
+    fn from(pairs: Pairs) -> PairPolicy {
+        match pairs {
+            Pairs::Current => PairPolicy::Current,
+            Pairs::Raw => PairPolicy::Raw,
+            Pairs::Jpeg => PairPolicy::Jpeg,
+            Pairs::Both => PairPolicy::Both,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum Format {
+    /// Tables.
+    #[default]
+    Text,
+    /// NDJSON, one object per line. Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    Json,
 }
 
 #[derive(Clone, Debug, Parser)]
 struct CommandArgs {
+    /// The output format of dump.
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+    /// For list, the file mapping the volumes to their mount points:
+    /// `<volume name> = <mount point>` or `uuid:<disk uuid> = <mount point>`
+    /// on each line.
+    #[arg(long)]
+    mapping: Option<String>,
     #[arg(long)]
     all: bool,
     #[arg(long)]
@@ -51,6 +194,15 @@ struct CommandArgs {
     keywords: bool,
     #[arg(long)]
     volumes: bool,
+    #[arg(long)]
+    places: bool,
+    #[arg(long)]
+    faces: bool,
+    #[arg(long)]
+    stacks: bool,
+    /// The RAW+JPEG pairs.
+    #[arg(long)]
+    pairs: bool,
     path: String,
 }
 
@@ -62,34 +214,219 @@ fn main() {
         Command::Audit(_) => process_audit(&args),
         Command::List(_) => process_list(&args),
         Command::Tree(args) => tree::process_tree(&args),
+        Command::VerifyDb(args) => process_verify_db(&args),
+        Command::ExportXmp(args) => process_export_xmp(&args),
+        Command::Csv(args) => process_csv(&args),
+        Command::ExportSqlite(args) => process_export_sqlite(&args),
     };
 }
 
 fn process_list(args: &Args) {
     if let Command::List(args) = &args.command {
         let mut library = Library::new(&args.path);
+        if let Err(err) = library.library_version() {
+            eprintln!("Invalid library: {err}");
+            return;
+        }
+        if let Err(err) = library
+            .load_volumes(PROGRESS_NONE)
+            .and_then(|_| library.load_masters(PROGRESS_NONE))
         {
-            let version = library.library_version();
-            if version.is_err() {
-                println!("Invalid library");
-                return;
-            }
+            eprintln!("Error loading library: {err}");
+            return;
         }
-        library.load_volumes(PROGRESS_NONE);
-        library.load_masters(PROGRESS_NONE);
 
+        if !load_mapping(&mut library, args.mapping.as_deref()) {
+            return;
+        }
         let masters = library.masters();
         for master_uuid in masters {
             if master_uuid.is_empty() {
                 continue;
             }
-            if let Some(master_path) = library.resolve_master_path(master_uuid) {
-                println!("{master_path}");
-            } else {
-                eprintln!("Can't resolve master path for {master_uuid}");
+            match library.resolve_master_file(master_uuid) {
+                Some(resolved) if resolved.exists => println!("{}", resolved.path.display()),
+                Some(resolved) => {
+                    println!("{}", resolved.path.display());
+                    eprintln!("Missing file {}", resolved.path.display());
+                }
+                None => eprintln!("Can't resolve master path for {master_uuid}"),
+            }
+        }
+    }
+}
+
+/// Set the volume `mapping` file, if any, to the path resolver of
+/// `library`. Return false if it is invalid.
+fn load_mapping(library: &mut Library, mapping: Option<&str>) -> bool {
+    let mapping = match mapping {
+        Some(mapping) => mapping,
+        None => return true,
+    };
+    let mut resolver = library.path_resolver();
+    if let Err(err) = resolver.load_mappings(mapping) {
+        eprintln!("Invalid mapping: {err}");
+        return false;
+    }
+    library.set_path_resolver(resolver);
+    true
+}
+
+fn process_verify_db(args: &VerifyArgs) {
+    let mut library = Library::new(&args.path);
+    if let Err(err) = library.library_version() {
+        eprintln!("Invalid library: {err}");
+        return;
+    }
+    let report = match library.verify_database() {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Error verifying library: {err}");
+            return;
+        }
+    };
+
+    println!("Mismatches {}", report.mismatches.len());
+    for mismatch in &report.mismatches {
+        println!(
+            "| {:<8} | {:<26} | {:<24} | {} != {}",
+            format!("{:?}", mismatch.obj_type),
+            mismatch.uuid,
+            mismatch.field,
+            mismatch.plist,
+            mismatch.database
+        );
+    }
+    println!("Missing in database {}", report.missing_in_database.len());
+    for (obj_type, uuid) in &report.missing_in_database {
+        println!("| {:<8} | {}", format!("{:?}", obj_type), uuid);
+    }
+    println!("Missing in plists {}", report.missing_in_plists.len());
+    for (obj_type, uuid) in &report.missing_in_plists {
+        println!("| {:<8} | {}", format!("{:?}", obj_type), uuid);
+    }
+    println!("Orphans {}", report.orphans.len());
+    for orphan in &report.orphans {
+        println!(
+            "| {:<8} | {:<26} | parent {}",
+            format!("{:?}", orphan.obj_type),
+            orphan.uuid,
+            orphan.parent
+        );
+    }
+    println!(
+        "Orphan album content {}",
+        report.orphan_album_versions.len()
+    );
+    for model_id in &report.orphan_album_versions {
+        println!("| RKAlbumVersion {}", model_id);
+    }
+    if report.is_consistent() {
+        println!("Library is consistent.");
+    }
+}
+
+fn process_export_xmp(args: &ExportXmpArgs) {
+    let mut library = Library::new(&args.path);
+    if let Err(err) = library.library_version() {
+        eprintln!("Invalid library: {err}");
+        return;
+    }
+    if let Err(err) = library
+        .load_volumes(PROGRESS_NONE)
+        .and_then(|_| library.load_masters(PROGRESS_NONE))
+        .and_then(|_| library.load_versions(PROGRESS_NONE))
+        .and_then(|_| library.load_places(PROGRESS_NONE))
+        .and_then(|_| library.load_faces(PROGRESS_NONE))
+        .and_then(|_| library.load_stacks())
+    {
+        eprintln!("Error loading library: {err}");
+        return;
+    }
+    if !load_mapping(&mut library, args.mapping.as_deref()) {
+        return;
+    }
+
+    let options = SidecarOptions {
+        naming: if args.replace_extension {
+            SidecarNaming::ReplaceExtension
+        } else {
+            SidecarNaming::AppendExtension
+        },
+        existing: if args.merge {
+            ExistingSidecar::Merge
+        } else if args.overwrite {
+            ExistingSidecar::Overwrite
+        } else {
+            ExistingSidecar::Skip
+        },
+        versions: if args.originals {
+            VersionSelection::Originals
+        } else if args.non_originals {
+            VersionSelection::NonOriginals
+        } else {
+            VersionSelection::All
+        },
+        pairs: args.pairs.into(),
+        xmp: XmpOptions {
+            camera_raw: args.camera_raw,
+            digikam_tags: args.digikam,
+            mp_regions: args.mp_regions,
+            stack: match args.stack_keyword {
+                Some(ref prefix) => StackTarget::Keyword(prefix.clone()),
+                None if args.stack_custom => StackTarget::Custom,
+                None => StackTarget::None,
+            },
+            ..Default::default()
+        },
+    };
+    let entries = if args.embed {
+        let options = EmbedOptions {
+            overwrite: args.overwrite || args.merge,
+            versions: options.versions,
+            pairs: options.pairs,
+            sidecar_naming: options.naming,
+            xmp: options.xmp.clone(),
+        };
+        library.export_masters(&args.dest, &options)
+    } else {
+        library.write_sidecars(&args.dest, &options)
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Error exporting: {err}");
+            return;
+        }
+    };
+
+    let mut failed = 0;
+    for entry in &entries {
+        let destination = entry
+            .destination
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        match entry.status {
+            ExportStatus::Failed(ref err) => {
+                failed += 1;
+                eprintln!(
+                    "| {:<22} | {} failed: {}",
+                    entry.version_uuid, destination, err
+                );
             }
+            ref status => println!(
+                "| {:<22} | {:<16} | {}",
+                entry.version_uuid,
+                format!("{:?}", status),
+                destination
+            ),
+        }
+        for adjustment in &entry.xmp_report.untranslated_adjustments {
+            println!("|   untranslated {}", adjustment);
         }
     }
+    println!("Exported {} versions, {} failed", entries.len(), failed);
 }
 
 fn print_report(report: &Report) {
@@ -115,18 +452,22 @@ fn process_audit(args: &Args) {
         let auditor = Reporter::new();
         library.set_auditor(Some(auditor));
 
+        if let Err(err) = library.library_version() {
+            eprintln!("Invalid library: {err}");
+            return;
+        }
+        if let Err(err) = library
+            .load_volumes(PROGRESS_NONE)
+            .and_then(|_| library.load_folders(PROGRESS_NONE))
+            .and_then(|_| library.load_albums(PROGRESS_NONE))
+            .and_then(|_| library.load_masters(PROGRESS_NONE))
+            .and_then(|_| library.load_versions(PROGRESS_NONE))
+            .and_then(|_| library.load_places(PROGRESS_NONE))
+            .and_then(|_| library.load_faces(PROGRESS_NONE))
         {
-            let version = library.library_version();
-            if version.is_err() {
-                println!("Invalid library");
-                return;
-            }
+            eprintln!("Error loading library: {err}");
+            return;
         }
-        library.load_volumes(PROGRESS_NONE);
-        library.load_folders(PROGRESS_NONE);
-        library.load_albums(PROGRESS_NONE);
-        library.load_masters(PROGRESS_NONE);
-        library.load_versions(PROGRESS_NONE);
 
         println!("Audit:");
         let auditor = library.auditor().unwrap();
@@ -153,7 +494,7 @@ fn process_audit(args: &Args) {
 }
 
 /// print the keywords with indentation for the hierarchy
-fn print_keywords(keywords: &[Keyword], indent: &str) {
+fn print_keywords(library: &Library, keywords: &[&Keyword], indent: &str) {
     for keyword in keywords {
         if !keyword.is_valid() {
             continue;
@@ -162,31 +503,77 @@ fn print_keywords(keywords: &[Keyword], indent: &str) {
         let uuid = keyword.uuid().as_ref().unwrap();
         let parent = keyword.parent().clone().unwrap_or_default();
         println!("| {:<26} | {:<26} | {}{}", uuid, parent, indent, name);
-        if keyword.children.is_some() {
+        let children = library.keyword_children(uuid);
+        if !children.is_empty() {
             let new_indent = if indent.is_empty() {
                 String::from("+- ") + indent
             } else {
                 String::from("\t") + indent
             };
-            print_keywords(keyword.children.as_ref().unwrap(), &new_indent);
+            print_keywords(library, &children, &new_indent);
         }
     }
 }
 
+/// Dump the library as NDJSON.
+#[cfg(feature = "serde")]
+fn dump_json(args: &CommandArgs, library: &mut Library) {
+    let mut result = Ok(());
+    if args.all || args.volumes {
+        result = result.and_then(|_| library.load_volumes(PROGRESS_NONE));
+    }
+    if args.all || args.folders {
+        result = result.and_then(|_| library.load_folders(PROGRESS_NONE));
+    }
+    if args.all || args.albums {
+        result = result.and_then(|_| library.load_albums(PROGRESS_NONE));
+    }
+    if args.all || args.keywords {
+        result = result.and_then(|_| library.load_keywords(PROGRESS_NONE));
+    }
+    if args.all || args.masters {
+        result = result.and_then(|_| library.load_masters(PROGRESS_NONE));
+    }
+    if args.all || args.versions {
+        result = result.and_then(|_| library.load_versions(PROGRESS_NONE));
+    }
+    if args.all || args.places {
+        result = result.and_then(|_| library.load_places(PROGRESS_NONE));
+    }
+    if let Err(err) = result.and_then(|_| library.export_json(std::io::stdout().lock())) {
+        eprintln!("Error dumping library: {err}");
+    }
+}
+
 fn process_dump(args: &Args) {
     if let Command::Dump(args) = &args.command {
         let mut library = Library::new(&args.path);
 
-        {
-            if let Ok(version) = library.library_version() {
-                println!("Version {}", version);
-            } else {
-                println!("Version not found.");
+        #[cfg(feature = "serde")]
+        if let Format::Json = args.format {
+            if let Err(err) = library.library_version() {
+                eprintln!("Invalid library: {err}");
+                return;
+            }
+            dump_json(args, &mut library);
+            return;
+        }
+
+        match library.library_version() {
+            Ok(version) => println!("Version {}", version),
+            Err(err) => {
+                eprintln!("Version not found: {err}");
                 return;
             }
         }
 
-        let model_info = library.get_model_info().unwrap();
+        let model_info = match library.get_model_info() {
+            Ok(model_info) => model_info,
+            Err(err) => {
+                eprintln!("Model info not found: {err}");
+                return;
+            }
+        };
         println!("model info");
         println!("\tDB version: {}", model_info.db_version.unwrap_or(0));
         println!(
@@ -239,20 +626,172 @@ fn process_dump(args: &Args) {
         if args.all || args.versions {
             dump_versions(&model_info, &mut library);
         }
+        if args.all || args.places {
+            dump_places(&mut library);
+        }
+        if args.all || args.faces {
+            dump_faces(&mut library);
+        }
+        if args.all || args.stacks {
+            dump_stacks(&mut library);
+        }
+        if args.all || args.pairs {
+            dump_pairs(&mut library);
+        }
     } else {
         unreachable!()
     }
 }
 
+fn dump_pairs(library: &mut Library) {
+    if let Err(err) = library
+        .load_masters(PROGRESS_NONE)
+        .and_then(|_| library.load_versions(PROGRESS_NONE))
+    {
+        eprintln!("Error loading masters: {err}");
+        return;
+    }
+    let pairs = library.master_pairs();
+    println!("{} RAW+JPEG pairs:", pairs.len());
+    println!("| raw                    | jpeg                   | recip | version                | uses |");
+    println!("+------------------------+------------------------+-------+------------------------+------+");
+    for pair in &pairs {
+        println!(
+            "| {:<22} | {:<22} | {:<5} | {:<22} | {:<4} |",
+            pair.raw, pair.jpeg, pair.reciprocal, "", ""
+        );
+        if pair.conflicting {
+            eprintln!(
+                "Conflicting pair {} {}: a master is in other pairs",
+                pair.raw, pair.jpeg
+            );
+        }
+        for (version, member) in &pair.versions {
+            let uses = match member {
+                PairMember::Raw => "RAW",
+                PairMember::Jpeg => "JPEG",
+            };
+            println!(
+                "| {:<22} | {:<22} | {:<5} | {:<22} | {:<4} |",
+                "", "", "", version, uses
+            );
+        }
+    }
+}
+
+fn dump_stacks(library: &mut Library) {
+    if let Err(err) = library.load_stacks() {
+        eprintln!("Error loading stacks: {err}");
+        return;
+    }
+    let stacks = library.stacks();
+    println!("{} Stacks:", stacks.len());
+    println!("| uuid                   | pick                   | versions |");
+    println!("+------------------------+------------------------+----------+");
+    for stack in stacks {
+        println!(
+            "| {:<22} | {:<22} | {:>8} |",
+            stack.uuid,
+            stack.pick.clone().unwrap_or_default(),
+            stack.versions.len(),
+        );
+    }
+}
+
+fn dump_faces(library: &mut Library) {
+    if let Err(err) = library.load_faces(PROGRESS_NONE) {
+        eprintln!("Error loading faces: {err}");
+        return;
+    }
+    let faces = match library.faces() {
+        Some(faces) => faces,
+        None => return,
+    };
+
+    println!("{} Face names:", faces.names.len());
+    println!("| Name                   | Full name                      | key  | id   |");
+    println!("+------------------------+--------------------------------+------+------+");
+    for (key, name) in &faces.names {
+        println!(
+            "| {:<22} | {:<30} | {:>4} | {:>4} |",
+            name.name.clone().unwrap_or_default(),
+            name.full_name.clone().unwrap_or_default(),
+            key,
+            name.model_id.unwrap_or(0),
+        );
+    }
+
+    println!("{} Detected faces:", faces.detected().len());
+    println!("| master uuid            | state     | key  | rectangle                     |");
+    println!("+------------------------+-----------+------+-------------------------------+");
+    for face in faces.detected() {
+        let rect = face
+            .rect
+            .map(|rect| {
+                format!(
+                    "{:.3},{:.3} {:.3}x{:.3}",
+                    rect.x, rect.y, rect.width, rect.height
+                )
+            })
+            .unwrap_or_default();
+        println!(
+            "| {:<22} | {:<9} | {:>4} | {:<29} |",
+            face.master_uuid.clone().unwrap_or_default(),
+            format!("{:?}", face.state()),
+            face.face_key.unwrap_or(0),
+            rect,
+        );
+    }
+}
+
+fn dump_places(library: &mut Library) {
+    if let Err(err) = library.load_places(PROGRESS_NONE) {
+        eprintln!("Error loading places: {err}");
+        return;
+    }
+
+    let places = library.places();
+    println!("{} Places:", places.len());
+
+    println!("| Name                   | uuid                   | parent                 | type | centroid              | id   |");
+    println!("+------------------------+------------------------+------------------------+------+-----------------------+------+");
+    for uuid in places {
+        match library.get(uuid) {
+            Some(StoreWrapper::Place(place)) => {
+                let centroid = place
+                    .centroid
+                    .map(|(latitude, longitude)| format!("{latitude:.5},{longitude:.5}"))
+                    .unwrap_or_default();
+                println!(
+                    "| {:<22} | {:<22} | {:<22} | {:>4} | {:<21} | {:>4} |",
+                    place.name.clone().unwrap_or_default(),
+                    uuid,
+                    place.parent().clone().unwrap_or_default(),
+                    place.place_type.unwrap_or(0),
+                    centroid,
+                    place.model_id(),
+                )
+            }
+            _ => {
+                println!("Place not found.");
+            }
+        }
+    }
+}
+
 fn dump_volumes(library: &mut Library) {
     let mut pb = ProgressBar::on(stderr(), 1);
     pb.tick_format("|/-\\");
 
-    library.load_volumes(Some(&mut |_: u64| {
+    let result = library.load_volumes(Some(&mut |_: u64| {
         pb.tick();
         true
     }));
     pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading volumes: {err}");
+        return;
+    }
 
     let volumes = library.volumes();
     println!("{} Volumes:", volumes.len());
@@ -285,11 +824,15 @@ fn dump_folders(library: &mut Library) {
     let mut pb = ProgressBar::on(stderr(), 1);
     pb.tick_format("|/-\\");
 
-    library.load_folders(Some(&mut |_: u64| {
+    let result = library.load_folders(Some(&mut |_: u64| {
         pb.tick();
         true
     }));
     pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading folders: {err}");
+        return;
+    }
 
     let folders = library.folders();
     println!("{} Folders:", folders.len());
@@ -333,11 +876,15 @@ fn dump_albums(library: &mut Library) {
     let mut pb = ProgressBar::on(stderr(), 1);
     pb.tick_format("|/-\\");
 
-    library.load_albums(Some(&mut |_: u64| {
+    let result = library.load_albums(Some(&mut |_: u64| {
         pb.tick();
         true
     }));
     pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading albums: {err}");
+        return;
+    }
 
     let albums = library.albums();
     println!("{} Albums:", albums.len());
@@ -377,23 +924,38 @@ fn dump_albums(library: &mut Library) {
 }
 
 fn dump_keywords(library: &mut Library) {
-    if let Some(ref keywords) = library.list_keywords() {
-        println!("{} keywords:", keywords.len());
-        println!("| uuid                       | parent                     | name");
-        println!("+----------------------------+----------------------------+-----------");
-        print_keywords(keywords, "");
+    let mut pb = ProgressBar::on(stderr(), 1);
+    pb.tick_format("|/-\\");
+
+    let result = library.load_keywords(Some(&mut |_: u64| {
+        pb.tick();
+        true
+    }));
+    pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading keywords: {err}");
+        return;
     }
+
+    println!("{} keywords:", library.keywords().len());
+    println!("| uuid                       | parent                     | name");
+    println!("+----------------------------+----------------------------+-----------");
+    print_keywords(library, &library.keyword_roots(), "");
 }
 
 fn dump_masters(model_info: &ModelInfo, library: &mut Library) {
     let count = model_info.master_count.unwrap_or(0) as u64;
     let mut pb = ProgressBar::on(stderr(), count);
 
-    library.load_masters(Some(&mut |inc: u64| {
+    let result = library.load_masters(Some(&mut |inc: u64| {
         pb.add(inc);
         true
     }));
     pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading masters: {err}");
+        return;
+    }
 
     let masters = library.masters();
     println!("{} Masters:", masters.len());
@@ -426,11 +988,15 @@ fn dump_versions(model_info: &ModelInfo, library: &mut Library) {
     let count = model_info.version_count.unwrap_or(0) as u64;
     let mut pb = ProgressBar::on(stderr(), count);
 
-    library.load_versions(Some(&mut |inc: u64| {
+    let result = library.load_versions(Some(&mut |inc: u64| {
         pb.add(inc);
         true
     }));
     pb.finish();
+    if let Err(err) = result {
+        eprintln!("Error loading versions: {err}");
+        return;
+    }
 
     let versions = library.versions();
     println!("{} Versions:", versions.len());
@@ -467,3 +1033,80 @@ fn dump_versions(model_info: &ModelInfo, library: &mut Library) {
         }
     }
 }
+
+fn process_export_sqlite(args: &ExportSqliteArgs) {
+    if args.schema {
+        println!("{}", aplib::SQLITE_EXPORT_SCHEMA.trim());
+        return;
+    }
+    let (Some(path), Some(dest)) = (&args.path, &args.dest) else {
+        return;
+    };
+    let mut library = Library::new(path);
+    if let Err(err) = library.library_version() {
+        eprintln!("Invalid library: {err}");
+        return;
+    }
+    if let Err(err) = library
+        .load_volumes(PROGRESS_NONE)
+        .and_then(|_| library.load_folders(PROGRESS_NONE))
+        .and_then(|_| library.load_albums(PROGRESS_NONE))
+        .and_then(|_| library.load_keywords(PROGRESS_NONE))
+        .and_then(|_| library.load_masters(PROGRESS_NONE))
+        .and_then(|_| library.load_versions(PROGRESS_NONE))
+    {
+        eprintln!("Error loading library: {err}");
+        return;
+    }
+    if !load_mapping(&mut library, args.mapping.as_deref()) {
+        return;
+    }
+    if let Err(err) = library.export_sqlite(dest) {
+        eprintln!("Error exporting: {err}");
+    }
+}
+
+fn process_csv(args: &CsvArgs) {
+    let columns = if args.columns.is_empty() {
+        CsvColumn::parse_list(&DEFAULT_CSV_COLUMNS)
+    } else {
+        CsvColumn::parse_list(&args.columns)
+    };
+    let columns = match columns {
+        Ok(columns) => columns,
+        Err(err) => {
+            eprintln!("Invalid columns: {err}");
+            return;
+        }
+    };
+    let mut library = Library::new(&args.path);
+    if let Err(err) = library.library_version() {
+        eprintln!("Invalid library: {err}");
+        return;
+    }
+    if let Err(err) = library
+        .load_volumes(PROGRESS_NONE)
+        .and_then(|_| library.load_folders(PROGRESS_NONE))
+        .and_then(|_| library.load_masters(PROGRESS_NONE))
+        .and_then(|_| library.load_versions(PROGRESS_NONE))
+    {
+        eprintln!("Error loading library: {err}");
+        return;
+    }
+    if !load_mapping(&mut library, args.mapping.as_deref()) {
+        return;
+    }
+    let result = match args.output {
+        Some(ref output) => match std::fs::File::create(output) {
+            Ok(file) => library.export_csv(std::io::BufWriter::new(file), &columns),
+            Err(err) => {
+                eprintln!("Can't create {output}: {err}");
+                return;
+            }
+        },
+        None => library.export_csv(std::io::stdout().lock(), &columns),
+    };
+    if let Err(err) = result {
+        eprintln!("Error exporting: {err}");
+    }
+}
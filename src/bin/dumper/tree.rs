@@ -118,10 +118,15 @@ fn add_object(uuid: &str, tree: &mut Tree, library: &Library) {
 
 pub(crate) fn process_tree(args: &TreeArgs) {
     let mut library = Library::new(&args.path);
-    library.load_folders(PROGRESS_NONE);
-    library.load_albums(PROGRESS_NONE);
-    library.load_masters(PROGRESS_NONE);
-    library.load_versions(PROGRESS_NONE);
+    if let Err(err) = library
+        .load_folders(PROGRESS_NONE)
+        .and_then(|_| library.load_albums(PROGRESS_NONE))
+        .and_then(|_| library.load_masters(PROGRESS_NONE))
+        .and_then(|_| library.load_versions(PROGRESS_NONE))
+    {
+        eprintln!("Error loading library: {err}");
+        return;
+    }
 
     let mut tree = Tree::new();
     let folders = library.folders();
@@ -0,0 +1,300 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Export the versions as CSV, one row per version.
+
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::exif::ExifValue;
+use crate::folder::Folder;
+use crate::iptc::IptcValue;
+use crate::master::Master;
+use crate::version::Version;
+use crate::{AplibObject, Error, Result};
+
+/// A column of the CSV export.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CsvColumn {
+    Uuid,
+    Name,
+    FileName,
+    VersionNumber,
+    Rating,
+    ColourLabel,
+    Flagged,
+    Original,
+    Hidden,
+    InTrash,
+    /// The image date, in UTC.
+    ImageDate,
+    /// The capture date in the picture time zone.
+    CaptureDate,
+    TimeZone,
+    MasterUuid,
+    MasterName,
+    MasterFileName,
+    MasterType,
+    MasterSubtype,
+    ProjectUuid,
+    ProjectName,
+    /// The path of the master file, resolved with the path resolver
+    /// of the library.
+    Path,
+    /// The keyword paths, `|` separated, joined by `; `.
+    Keywords,
+    /// A property of the EXIF bag.
+    Exif(String),
+    /// A property of the IPTC bag.
+    Iptc(String),
+}
+
+/// The columns when none are chosen.
+pub const DEFAULT_CSV_COLUMNS: [&str; 8] = [
+    "uuid",
+    "name",
+    "version_number",
+    "rating",
+    "capture_date",
+    "project.name",
+    "path",
+    "keywords",
+];
+
+impl FromStr for CsvColumn {
+    type Err = Error;
+
+    /// Parse the column `name`, like `master.file_name` or
+    /// `exif.FocalLength`.
+    fn from_str(name: &str) -> Result<CsvColumn> {
+        if let Some(key) = name.strip_prefix("exif.") {
+            return Ok(CsvColumn::Exif(key.to_string()));
+        }
+        if let Some(key) = name.strip_prefix("iptc.") {
+            return Ok(CsvColumn::Iptc(key.to_string()));
+        }
+        Ok(match name {
+            "uuid" => CsvColumn::Uuid,
+            "name" => CsvColumn::Name,
+            "file_name" => CsvColumn::FileName,
+            "version_number" => CsvColumn::VersionNumber,
+            "rating" => CsvColumn::Rating,
+            "colour_label" => CsvColumn::ColourLabel,
+            "flagged" => CsvColumn::Flagged,
+            "original" => CsvColumn::Original,
+            "hidden" => CsvColumn::Hidden,
+            "in_trash" => CsvColumn::InTrash,
+            "image_date" => CsvColumn::ImageDate,
+            "capture_date" => CsvColumn::CaptureDate,
+            "time_zone" => CsvColumn::TimeZone,
+            "master.uuid" => CsvColumn::MasterUuid,
+            "master.name" => CsvColumn::MasterName,
+            "master.file_name" => CsvColumn::MasterFileName,
+            "master.type" => CsvColumn::MasterType,
+            "master.subtype" => CsvColumn::MasterSubtype,
+            "project.uuid" => CsvColumn::ProjectUuid,
+            "project.name" => CsvColumn::ProjectName,
+            "path" => CsvColumn::Path,
+            "keywords" => CsvColumn::Keywords,
+            _ => return Err(Error::UnknownColumn(name.to_string())),
+        })
+    }
+}
+
+impl CsvColumn {
+    /// Parse the column names in `names`.
+    pub fn parse_list<S: AsRef<str>>(names: &[S]) -> Result<Vec<CsvColumn>> {
+        names.iter().map(|name| name.as_ref().parse()).collect()
+    }
+
+    /// The name in the header row.
+    pub fn name(&self) -> String {
+        match self {
+            CsvColumn::Uuid => "uuid".to_string(),
+            CsvColumn::Name => "name".to_string(),
+            CsvColumn::FileName => "file_name".to_string(),
+            CsvColumn::VersionNumber => "version_number".to_string(),
+            CsvColumn::Rating => "rating".to_string(),
+            CsvColumn::ColourLabel => "colour_label".to_string(),
+            CsvColumn::Flagged => "flagged".to_string(),
+            CsvColumn::Original => "original".to_string(),
+            CsvColumn::Hidden => "hidden".to_string(),
+            CsvColumn::InTrash => "in_trash".to_string(),
+            CsvColumn::ImageDate => "image_date".to_string(),
+            CsvColumn::CaptureDate => "capture_date".to_string(),
+            CsvColumn::TimeZone => "time_zone".to_string(),
+            CsvColumn::MasterUuid => "master.uuid".to_string(),
+            CsvColumn::MasterName => "master.name".to_string(),
+            CsvColumn::MasterFileName => "master.file_name".to_string(),
+            CsvColumn::MasterType => "master.type".to_string(),
+            CsvColumn::MasterSubtype => "master.subtype".to_string(),
+            CsvColumn::ProjectUuid => "project.uuid".to_string(),
+            CsvColumn::ProjectName => "project.name".to_string(),
+            CsvColumn::Path => "path".to_string(),
+            CsvColumn::Keywords => "keywords".to_string(),
+            CsvColumn::Exif(key) => format!("exif.{key}"),
+            CsvColumn::Iptc(key) => format!("iptc.{key}"),
+        }
+    }
+}
+
+/// What a row is built from.
+pub(crate) struct CsvRow<'a> {
+    pub version: &'a Version,
+    pub master: Option<&'a Master>,
+    pub project: Option<&'a Folder>,
+    pub path: Option<String>,
+}
+
+fn to_string<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+impl CsvRow<'_> {
+    /// The value of `column`. Missing values are empty.
+    fn value(&self, column: &CsvColumn) -> String {
+        let version = self.version;
+        match column {
+            CsvColumn::Uuid => to_string(version.uuid()),
+            CsvColumn::Name => to_string(&version.name),
+            CsvColumn::FileName => to_string(&version.file_name),
+            CsvColumn::VersionNumber => to_string(&version.version_number),
+            CsvColumn::Rating => to_string(&version.rating),
+            CsvColumn::ColourLabel => to_string(&version.colour_label_index),
+            CsvColumn::Flagged => to_string(&version.is_flagged),
+            CsvColumn::Original => to_string(&version.is_original),
+            CsvColumn::Hidden => to_string(&version.is_hidden),
+            CsvColumn::InTrash => to_string(&version.is_in_trash),
+            CsvColumn::ImageDate => to_string(&version.image_date.map(|date| date.to_rfc3339())),
+            CsvColumn::CaptureDate => to_string(
+                &version
+                    .capture_datetime_local()
+                    .map(|date| date.to_rfc3339()),
+            ),
+            CsvColumn::TimeZone => to_string(&version.timezone_name),
+            CsvColumn::MasterUuid => to_string(&version.master_uuid),
+            CsvColumn::MasterName => to_string(&self.master.and_then(|master| master.name.clone())),
+            CsvColumn::MasterFileName => {
+                to_string(&self.master.and_then(|master| master.filename.clone()))
+            }
+            CsvColumn::MasterType => {
+                to_string(&self.master.and_then(|master| master.master_type.clone()))
+            }
+            CsvColumn::MasterSubtype => {
+                to_string(&self.master.and_then(|master| master.subtype.clone()))
+            }
+            CsvColumn::ProjectUuid => to_string(&version.project_uuid),
+            CsvColumn::ProjectName => {
+                to_string(&self.project.and_then(|project| project.name.clone()))
+            }
+            CsvColumn::Path => to_string(&self.path),
+            CsvColumn::Keywords => version
+                .keyword_paths()
+                .iter()
+                .map(|path| path.join("|"))
+                .collect::<Vec<String>>()
+                .join("; "),
+            CsvColumn::Exif(key) => {
+                match version.exif.as_ref().and_then(|exif| exif.bag.get(key)) {
+                    Some(ExifValue::Int(value)) => value.to_string(),
+                    Some(ExifValue::Real(value)) => value.to_string(),
+                    Some(ExifValue::Str(value)) => value.clone(),
+                    Some(ExifValue::Date(value)) => value.to_rfc3339(),
+                    Some(ExifValue::None) | None => String::default(),
+                }
+            }
+            CsvColumn::Iptc(key) => {
+                match version.iptc.as_ref().and_then(|iptc| iptc.bag.get(key)) {
+                    Some(IptcValue::Str(value)) => value.clone(),
+                    Some(IptcValue::None) | None => String::default(),
+                }
+            }
+        }
+    }
+}
+
+/// Quote `field` if it contains a separator, a quote or a line
+/// break. Quotes are doubled.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_record<W: Write, I>(writer: &mut W, fields: I) -> Result<()>
+where
+    I: IntoIterator<Item = String>,
+{
+    let line = fields
+        .into_iter()
+        .map(|field| quote(&field))
+        .collect::<Vec<String>>()
+        .join(",");
+    write!(writer, "{line}\r\n").map_err(Error::Write)
+}
+
+/// Write the header row for `columns` then a row for each of `rows`.
+pub(crate) fn write_csv<'a, W, I>(mut writer: W, columns: &[CsvColumn], rows: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = CsvRow<'a>>,
+{
+    write_record(&mut writer, columns.iter().map(CsvColumn::name))?;
+    for row in rows {
+        write_record(&mut writer, columns.iter().map(|column| row.value(column)))?;
+    }
+    writer.flush().map_err(Error::Write)
+}
+
+#[cfg(test)]
+#[test]
+fn test_csv_columns() {
+    assert_eq!(quote("IMG_1234"), "IMG_1234");
+    assert_eq!(quote("Paris, France"), "\"Paris, France\"");
+    assert_eq!(quote("The \"Louvre\""), "\"The \"\"Louvre\"\"\"");
+    assert_eq!(quote("two\nlines"), "\"two\nlines\"");
+
+    let columns = CsvColumn::parse_list(&["uuid", "master.file_name", "exif.FocalLength"]).unwrap();
+    assert_eq!(columns[1], CsvColumn::MasterFileName);
+    assert_eq!(columns[2], CsvColumn::Exif("FocalLength".to_string()));
+    assert_eq!(columns[2].name(), "exif.FocalLength");
+    assert!(CsvColumn::parse_list(&DEFAULT_CSV_COLUMNS).is_ok());
+    assert!(matches!(
+        "master.size".parse::<CsvColumn>(),
+        Err(Error::UnknownColumn(_))
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn test_export_csv() {
+    use crate::testutils;
+    use crate::{Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_export_csv");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_folders(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let columns =
+        CsvColumn::parse_list(&["uuid", "name", "project.name", "path", "keywords"]).unwrap();
+    let mut buffer = vec![];
+    assert!(library.export_csv(&mut buffer, &columns).is_ok());
+    let csv = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "uuid,name,project.name,path,keywords");
+    assert_eq!(
+        lines[1],
+        "BF6nuoBnTumzoXyexdmXlw,img_8826 - Version 2,Paris,\
+         /Volumes/Photos/2011/img_8826.cr2,Places|Paris"
+    );
+}
@@ -7,17 +7,52 @@
 use std::collections::{BTreeMap, HashMap};
 use std::time::SystemTime;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
 use exempi2::Xmp;
 use plist::Value;
 
 use crate::audit::{Report, SkipReason};
+use crate::sqlutils::date_from_timestamp;
+use crate::time;
 use crate::xmp::ns::*;
 use crate::xmp::{ToXmp, XmpProperty, XmpTranslator};
 
+/// The number properties that are integers.
+const INTEGER_KEYS: [&str; 28] = [
+    "CaptureDayOfMonth",
+    "CaptureDayOfWeek",
+    "CaptureHourOfDay",
+    "CaptureMinuteOfHour",
+    "CaptureMonthOfYear",
+    "CaptureSecondOfMinute",
+    "CaptureYear",
+    "ColorSpace",
+    "Contrast",
+    "CustomRendered",
+    "Depth",
+    "ExposureMode",
+    "ExposureProgram",
+    "Flash",
+    "FocusMode",
+    "GainControl",
+    "ISOSpeedRating",
+    "LightSource",
+    "MeteringMode",
+    "Orientation",
+    "PixelHeight",
+    "PixelWidth",
+    "Saturation",
+    "SceneCaptureType",
+    "SensingMethod",
+    "Sharpness",
+    "WhiteBalance",
+    "WhiteBalanceIndex",
+];
+
 lazy_static::lazy_static! {
     /// HashMap for Exif properties (Aperture) to XMP.
     static ref EXIF_TO_XMP: HashMap<&'static str, XmpTranslator> = maplit::hashmap!{
+        "Altitude" => XmpTranslator::Custom,
         "ApertureValue" => XmpTranslator::Property(XmpProperty::new(
             NS_EXIF, "ApertureValue")),
         "Artist" => XmpTranslator::Property(XmpProperty::new(
@@ -61,23 +96,21 @@ lazy_static::lazy_static! {
             NS_EXIF_AUX, "ApproximateFocusDistance")),
         //    +- Exif.FocusMode
         "ISOSpeedRating" => XmpTranslator::Custom, // ISOSpeedRatings[] as int
-        "ImageDate" => XmpTranslator::Property(XmpProperty::new(
-            NS_EXIF, "DateTimeOriginal")),
+        // With the Capture* parts.
+        "ImageDate" => XmpTranslator::Custom,
         // It is possible that this will be overwritten by IPTC
         // Also it seems that by default Olympus files have this property
         // set to something irrelevant.
         "ImageDescription" => XmpTranslator::Property(XmpProperty::new(
             NS_DC, "description")),
-        "Latitude"  => XmpTranslator::Property(XmpProperty::new(
-            NS_EXIF, "GPSLatitude")),
+        "Latitude"  => XmpTranslator::Custom,
         "LensMaxMM" => XmpTranslator::None,
         "LensMinMM" => XmpTranslator::Custom,
         "LensModel" => XmpTranslator::Property(XmpProperty::new(
             NS_EXIF_AUX, "Lens")),
         "LightSource" => XmpTranslator::Property(XmpProperty::new(
             NS_EXIF, "LightSource")),
-        "Longitude" => XmpTranslator::Property(XmpProperty::new(
-            NS_EXIF, "GPSLongitude")),
+        "Longitude" => XmpTranslator::Custom,
         "Make" =>  XmpTranslator::Property(XmpProperty::new(
             NS_TIFF, "Make")),
         "MaxApertureValue" => XmpTranslator::Property(XmpProperty::new(
@@ -110,6 +143,7 @@ lazy_static::lazy_static! {
 }
 
 #[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExifValue {
     #[default]
     None,
@@ -120,6 +154,7 @@ pub enum ExifValue {
 }
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExifProperties {
     pub bag: BTreeMap<String, ExifValue>,
 }
@@ -159,6 +194,53 @@ impl ExifProperties {
         Some(ExifProperties { bag: values })
     }
 
+    /// Load the EXIF properties of all the versions from the
+    /// properties database `conn`. The key is the version model id.
+    pub fn from_db(conn: &rusqlite::Connection) -> crate::Result<HashMap<i64, ExifProperties>> {
+        let mut result: HashMap<i64, ExifProperties> = HashMap::new();
+
+        let mut stmt = conn
+            .prepare("SELECT versionId, propertyKey, numberProperty FROM RKExifNumberProperty")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let key: String = row.get(1)?;
+            let value = Self::number_value(&key, row.get(2)?);
+            result
+                .entry(row.get(0)?)
+                .or_default()
+                .bag
+                .insert(key, value);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT p.versionId, p.propertyKey, s.stringProperty \
+             FROM RKExifStringProperty p JOIN RKUniqueString s ON p.stringId = s.modelId",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            result
+                .entry(row.get(0)?)
+                .or_default()
+                .bag
+                .insert(row.get(1)?, ExifValue::Str(row.get(2)?));
+        }
+
+        Ok(result)
+    }
+
+    /// Return the value of the number property `key` from the
+    /// database. The database has them all as reals: only the keys
+    /// known to be integers become `Int`, like in the plists.
+    fn number_value(key: &str, value: f64) -> ExifValue {
+        if key == "ImageDate" {
+            date_from_timestamp(value).map_or(ExifValue::None, ExifValue::Date)
+        } else if INTEGER_KEYS.contains(&key) && value.fract() == 0.0 {
+            ExifValue::Int(value as i64)
+        } else {
+            ExifValue::Real(value)
+        }
+    }
+
     pub fn value_to_string(value: &ExifValue) -> Option<String> {
         match *value {
             ExifValue::Str(ref str) => Some(str.clone()),
@@ -167,64 +249,206 @@ impl ExifProperties {
         }
     }
 
+    /// Return the numeric value for `key`.
+    fn get_number(&self, key: &str) -> Option<f64> {
+        match self.bag.get(key) {
+            Some(&ExifValue::Int(i)) => Some(i as f64),
+            Some(&ExifValue::Real(f)) => Some(f),
+            _ => None,
+        }
+    }
+
     /// ISOSpeedRatings is an array in XMP.
-    fn iso(&self, xmp: &mut Xmp) {
-        let iso = self.bag.get("ISOSpeedRatings");
+    fn iso(&self, xmp: &mut Xmp) -> bool {
+        let iso = self.bag.get("ISOSpeedRating");
         if let Some(&ExifValue::Int(i)) = iso {
-            if let Err(err) = xmp.set_array_item(
-                NS_EXIF,
-                "ISOSpeedRatings",
-                0,
-                &format!("{}", i),
-                exempi2::PropFlags::NONE,
-            ) {
-                println!("Error converting ISO {:?}", err);
-            }
+            let _ = xmp.delete_property(NS_EXIF, "ISOSpeedRatings");
+            return xmp
+                .append_array_item(
+                    NS_EXIF,
+                    "ISOSpeedRatings",
+                    exempi2::PropFlags::ARRAY_IS_ORDERED,
+                    &format!("{}", i),
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok();
+        }
+        false
+    }
+
+    /// Return the fields of the XMP Flash struct from the EXIF
+    /// bitfield `flash`.
+    fn flash_fields(flash: i64) -> [(&'static str, String); 5] {
+        let boolean = |bit: i64| String::from(if flash & bit != 0 { "True" } else { "False" });
+        [
+            ("Fired", boolean(0x1)),
+            ("Return", ((flash >> 1) & 0x3).to_string()),
+            ("Mode", ((flash >> 3) & 0x3).to_string()),
+            ("Function", boolean(0x20)),
+            ("RedEyeMode", boolean(0x40)),
+        ]
+    }
+
+    /// Flash is a struct in XMP.
+    fn flash(&self, xmp: &mut Xmp) -> bool {
+        let flash = match self.bag.get("Flash") {
+            Some(&ExifValue::Int(flash)) => flash,
+            _ => return false,
+        };
+        Self::flash_fields(flash).iter().all(|(field, value)| {
+            XmpProperty::new_field(NS_EXIF, "Flash", XmpProperty::new(NS_EXIF, field))
+                .put_into_xmp(value, xmp)
+        })
+    }
+
+    /// Format the signed decimal `coordinate` as a XMP GPSCoordinate:
+    /// `DDD,MM.mmmmmmK`. `refs` are the positive and negative
+    /// hemisphere references.
+    fn gps_coordinate(coordinate: f64, refs: (char, char)) -> String {
+        let hemisphere = if coordinate < 0.0 { refs.1 } else { refs.0 };
+        // Round the minutes before splitting off the degrees, for
+        // 59.9999999 not to print as 60.
+        let micro_minutes = (coordinate.abs() * 60_000_000.0).round() as u64;
+        let degrees = micro_minutes / 60_000_000;
+        let minutes = (micro_minutes % 60_000_000) as f64 / 1_000_000.0;
+        format!("{},{:.6}{}", degrees, minutes, hemisphere)
+    }
+
+    fn gps(&self, key: &str, xmp: &mut Xmp) -> bool {
+        let (property, refs) = match key {
+            "Latitude" => ("GPSLatitude", ('N', 'S')),
+            _ => ("GPSLongitude", ('E', 'W')),
+        };
+        match self.get_number(key) {
+            Some(coordinate) => xmp
+                .set_property(
+                    NS_EXIF,
+                    property,
+                    &Self::gps_coordinate(coordinate, refs),
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok(),
+            None => false,
+        }
+    }
+
+    /// GPSAltitude is a rational, with a reference for below sea level.
+    fn altitude(&self, xmp: &mut Xmp) -> bool {
+        let altitude = match self.get_number("Altitude") {
+            Some(altitude) => altitude,
+            None => return false,
+        };
+        let altitude_ref = if altitude < 0.0 { "1" } else { "0" };
+        let value = format!("{}/100", (altitude.abs() * 100.0).round() as i64);
+        xmp.set_property(NS_EXIF, "GPSAltitude", &value, exempi2::PropFlags::NONE)
+            .is_ok()
+            && xmp
+                .set_property(
+                    NS_EXIF,
+                    "GPSAltitudeRef",
+                    altitude_ref,
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok()
+    }
+
+    /// Return the local capture date from the `Capture*` parts.
+    pub fn capture_date(&self) -> Option<NaiveDateTime> {
+        let part = |key| self.get_number(key).map(|part| part as u32);
+        NaiveDate::from_ymd_opt(
+            part("CaptureYear")? as i32,
+            part("CaptureMonthOfYear")?,
+            part("CaptureDayOfMonth")?,
+        )?
+        .and_hms_opt(
+            part("CaptureHourOfDay")?,
+            part("CaptureMinuteOfHour")?,
+            part("CaptureSecondOfMinute")?,
+        )
+    }
+
+    /// Return the original date in the time zone of the picture, see
+    /// `time::capture_date()`. The `Capture*` parts are from the
+    /// camera clock.
+    pub fn date_time_original(
+        &self,
+        picture_zone: Option<&str>,
+        camera_zone: Option<&str>,
+    ) -> Option<DateTime<FixedOffset>> {
+        let image_date = match self.bag.get("ImageDate") {
+            Some(ExifValue::Date(date)) => Some(date),
+            _ => None,
+        };
+        time::capture_date(
+            image_date,
+            self.capture_date().as_ref(),
+            picture_zone,
+            camera_zone,
+        )
+    }
+
+    fn date(&self, xmp: &mut Xmp, zones: (Option<&str>, Option<&str>)) -> bool {
+        match self.date_time_original(zones.0, zones.1) {
+            Some(date) => xmp
+                .set_property(
+                    NS_EXIF,
+                    "DateTimeOriginal",
+                    &time::xmp_date(&date),
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok(),
+            None => false,
         }
     }
 
     /// Will convert to the LensInfo
-    fn lens_info(&self, xmp: &mut Xmp) {
+    fn lens_info(&self, xmp: &mut Xmp) -> bool {
         let min = self.bag.get("LensMinMM");
         let max = self.bag.get("LensMaxMM");
         if min.is_none() || max.is_none() {
-            return;
+            return false;
         }
         let min = match *min.unwrap() {
             ExifValue::Int(i) => i as f64,
             ExifValue::Real(f) => f,
-            _ => return,
+            _ => return false,
         };
         let max = match *max.unwrap() {
             ExifValue::Int(i) => i as f64,
             ExifValue::Real(f) => f,
-            _ => return,
+            _ => return false,
         };
 
         let value = format!("{}/100 {}/100 0/1 0/1", min * 100.0, max * 100.0);
-        if let Err(err) =
-            xmp.set_property(NS_EXIF_AUX, "LensInfo", &value, exempi2::PropFlags::NONE)
-        {
-            println!("Error converting LensInfo {:?}", err);
-        }
+        xmp.set_property(NS_EXIF_AUX, "LensInfo", &value, exempi2::PropFlags::NONE)
+            .is_ok()
     }
 
-    fn custom_value_to_string(&self, key: &str, xmp: &mut Xmp) {
+    fn custom_value_to_string(
+        &self,
+        key: &str,
+        xmp: &mut Xmp,
+        zones: (Option<&str>, Option<&str>),
+    ) -> bool {
         match key {
-            "Flash" => {}
-            "ISOSpeedRatings" => {
-                self.iso(xmp);
-            }
-            "LensMinMM" => {
-                self.lens_info(xmp);
-            }
-            _ => {}
+            "Altitude" => self.altitude(xmp),
+            "CaptureDayOfMonth" | "ImageDate" => self.date(xmp, zones),
+            "Flash" => self.flash(xmp),
+            "ISOSpeedRating" => self.iso(xmp),
+            "Latitude" | "Longitude" => self.gps(key, xmp),
+            "LensMinMM" => self.lens_info(xmp),
+            _ => false,
         }
     }
-}
 
-impl ToXmp for ExifProperties {
-    fn to_xmp(&self, xmp: &mut Xmp) -> bool {
+    /// Convert to `xmp`, with the capture date in the time zone of
+    /// the picture `picture_zone`. The camera clock is in `camera_zone`.
+    pub fn to_xmp_in_zones(
+        &self,
+        xmp: &mut Xmp,
+        picture_zone: Option<&str>,
+        camera_zone: Option<&str>,
+    ) -> bool {
         for (key, value) in &self.bag {
             if let Some(translator) = EXIF_TO_XMP.get(&key.as_str()) {
                 match *translator {
@@ -234,7 +458,9 @@ impl ToXmp for ExifProperties {
                             prop.put_into_xmp(&value, xmp);
                         }
                     }
-                    XmpTranslator::Custom => self.custom_value_to_string(key, xmp),
+                    XmpTranslator::Custom => {
+                        self.custom_value_to_string(key, xmp, (picture_zone, camera_zone));
+                    }
                     _ => {}
                 }
             }
@@ -242,3 +468,82 @@ impl ToXmp for ExifProperties {
         true
     }
 }
+
+impl ToXmp for ExifProperties {
+    fn to_xmp(&self, xmp: &mut Xmp) -> bool {
+        self.to_xmp_in_zones(xmp, None, None)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_exif_custom_values() {
+    use chrono::TimeZone;
+
+    assert_eq!(
+        ExifProperties::gps_coordinate(45.5, ('N', 'S')),
+        "45,30.000000N"
+    );
+    assert_eq!(
+        ExifProperties::gps_coordinate(-73.25, ('E', 'W')),
+        "73,15.000000W"
+    );
+    assert_eq!(
+        ExifProperties::gps_coordinate(45.9999999999, ('N', 'S')),
+        "46,0.000000N"
+    );
+
+    assert_eq!(
+        ExifProperties::number_value("FocalLength", 50.0),
+        ExifValue::Real(50.0)
+    );
+    assert_eq!(
+        ExifProperties::number_value("ISOSpeedRating", 400.0),
+        ExifValue::Int(400)
+    );
+    assert_eq!(
+        ExifProperties::number_value("ShutterSpeed", 0.004),
+        ExifValue::Real(0.004)
+    );
+
+    // Fired, return detected, auto mode.
+    let flash = ExifProperties::flash_fields(0x1f);
+    assert_eq!(flash[0], ("Fired", "True".to_string()));
+    assert_eq!(flash[1], ("Return", "3".to_string()));
+    assert_eq!(flash[2], ("Mode", "3".to_string()));
+    assert_eq!(flash[3], ("Function", "False".to_string()));
+
+    let mut exif = ExifProperties::default();
+    exif.bag.insert(
+        "ImageDate".to_string(),
+        ExifValue::Date(Utc.with_ymd_and_hms(2007, 9, 17, 0, 5, 31).unwrap()),
+    );
+    let date_time_original = |exif: &ExifProperties, picture_zone, camera_zone| {
+        exif.date_time_original(picture_zone, camera_zone)
+            .map(|date| time::xmp_date(&date))
+    };
+    assert_eq!(
+        date_time_original(&exif, None, None).as_deref(),
+        Some("2007-09-17T00:05:31+00:00")
+    );
+    assert_eq!(
+        date_time_original(&exif, Some("America/Vancouver"), None).as_deref(),
+        Some("2007-09-16T17:05:31-07:00")
+    );
+    for (key, value) in [
+        ("CaptureYear", 2007),
+        ("CaptureMonthOfYear", 9),
+        ("CaptureDayOfMonth", 16),
+        ("CaptureHourOfDay", 17),
+        ("CaptureMinuteOfHour", 5),
+        ("CaptureSecondOfMinute", 31),
+    ] {
+        exif.bag.insert(key.to_string(), ExifValue::Int(value));
+    }
+    // Without the image date, the camera clock was on Toronto time.
+    exif.bag.remove("ImageDate");
+    assert_eq!(
+        date_time_original(&exif, Some("America/Vancouver"), Some("America/Toronto")).as_deref(),
+        Some("2007-09-16T14:05:31-07:00")
+    );
+}
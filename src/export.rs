@@ -0,0 +1,716 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Export the versions metadata as XMP, in sidecars or embedded in
+//! copies of the masters.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use exempi2::{Xmp, XmpFile};
+
+use crate::pairs::PairPolicy;
+use crate::resolver::ResolvedPath;
+use crate::version::Version;
+use crate::xmp::{ToXmp, XmpOptions, XmpReport};
+
+/// How to name the sidecar files.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SidecarNaming {
+    /// Append `.xmp` to the master file name: `IMG.CR2.xmp`.
+    #[default]
+    AppendExtension,
+    /// Replace the extension of the master file name: `IMG.xmp`.
+    ReplaceExtension,
+}
+
+/// What to do when the sidecar already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExistingSidecar {
+    /// Leave it untouched.
+    #[default]
+    Skip,
+    /// Replace it.
+    Overwrite,
+    /// Merge the properties into it. The library properties win.
+    Merge,
+}
+
+/// Which versions to export.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VersionSelection {
+    #[default]
+    All,
+    /// Only the original versions.
+    Originals,
+    /// Only the versions that aren't the original.
+    NonOriginals,
+}
+
+impl VersionSelection {
+    /// Whether `version` is selected.
+    pub fn selects(&self, version: &Version) -> bool {
+        let is_original = version.is_original.unwrap_or(false);
+        match *self {
+            VersionSelection::All => true,
+            VersionSelection::Originals => is_original,
+            VersionSelection::NonOriginals => !is_original,
+        }
+    }
+}
+
+/// Options for `Library::write_sidecars()`.
+///
+/// The versions past the first get the duplicate naming of darktable
+/// for their sidecar: `IMG_01.CR2.xmp` for the version 1 of
+/// `IMG.CR2`. With `SidecarNaming::ReplaceExtension` it is
+/// `IMG_01.xmp`, that no tool attaches to the master.
+#[derive(Clone, Debug, Default)]
+pub struct SidecarOptions {
+    pub naming: SidecarNaming,
+    pub existing: ExistingSidecar,
+    pub versions: VersionSelection,
+    /// Which masters of the RAW+JPEG pairs get a sidecar.
+    pub pairs: PairPolicy,
+    /// The options for the XMP content.
+    pub xmp: XmpOptions,
+}
+
+/// Options for `Library::export_masters()`.
+#[derive(Clone, Debug, Default)]
+pub struct EmbedOptions {
+    /// Overwrite the existing copies. Otherwise they are left
+    /// untouched.
+    pub overwrite: bool,
+    pub versions: VersionSelection,
+    /// Which masters of the RAW+JPEG pairs are copied.
+    pub pairs: PairPolicy,
+    /// The naming of the sidecars for the formats that can't embed
+    /// XMP.
+    pub sidecar_naming: SidecarNaming,
+    /// The options for the XMP content.
+    pub xmp: XmpOptions,
+}
+
+/// The outcome of the export of a version.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportStatus {
+    /// The file was written.
+    Written,
+    /// The existing sidecar was merged into.
+    Merged,
+    /// The file exists and was left untouched.
+    Exists,
+    /// The master was copied and the XMP embedded.
+    Embedded,
+    /// The master was copied and the XMP written in a sidecar as
+    /// the format can't embed it.
+    Sidecar,
+    /// The master file couldn't be resolved.
+    UnresolvedMaster,
+    /// The master file doesn't exist, it can't be copied.
+    MissingMaster,
+    /// The export failed.
+    Failed(String),
+}
+
+/// The export of a version.
+#[derive(Clone, Debug)]
+pub struct ExportEntry {
+    /// uuid of the version.
+    pub version_uuid: String,
+    /// The master file, as resolved. Sidecars are written for the
+    /// masters that don't exist.
+    pub master: Option<ResolvedPath>,
+    /// The file written.
+    pub destination: Option<PathBuf>,
+    /// The sidecar written next to the copy of the master.
+    pub sidecar: Option<PathBuf>,
+    pub status: ExportStatus,
+    /// What couldn't be translated to XMP.
+    pub xmp_report: XmpReport,
+}
+
+impl ExportEntry {
+    pub(crate) fn new(version_uuid: &str, master: Option<ResolvedPath>) -> ExportEntry {
+        ExportEntry {
+            version_uuid: version_uuid.to_owned(),
+            master,
+            destination: None,
+            sidecar: None,
+            status: ExportStatus::UnresolvedMaster,
+            xmp_report: XmpReport::default(),
+        }
+    }
+}
+
+/// Return the path of the file for `version` of the master at
+/// `master_path`, mirrored into `dest`. Versions past the first get
+/// their number appended to the file stem like darktable duplicates:
+/// `IMG_01.CR2`. The root of `master_path` is dropped, and `None` is
+/// returned if it has `..`, as it could escape `dest`.
+pub(crate) fn destination_path(
+    dest: &Path,
+    master_path: &Path,
+    version: &Version,
+) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in master_path.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::ParentDir => return None,
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    let stem = relative.file_stem()?.to_string_lossy().into_owned();
+    let mut file_name = match version.version_number {
+        Some(number) if number > 0 => format!("{}_{:02}", stem, number),
+        _ => stem,
+    };
+    if let Some(extension) = relative.extension() {
+        file_name.push('.');
+        file_name.push_str(&extension.to_string_lossy());
+    }
+    let parent = relative.parent().unwrap_or_else(|| Path::new(""));
+    Some(dest.join(parent).join(file_name))
+}
+
+/// Return the sidecar path for the file at `path`.
+pub(crate) fn sidecar_path(path: &Path, naming: SidecarNaming) -> PathBuf {
+    match naming {
+        SidecarNaming::AppendExtension => {
+            let mut path = path.as_os_str().to_owned();
+            path.push(".xmp");
+            PathBuf::from(path)
+        }
+        SidecarNaming::ReplaceExtension => path.with_extension("xmp"),
+    }
+}
+
+/// The error when the master path can't be mirrored into the
+/// destination.
+fn outside_destination(master_path: &Path) -> ExportStatus {
+    ExportStatus::Failed(format!(
+        "{} is outside the destination",
+        master_path.display()
+    ))
+}
+
+/// The error when two masters get the same file.
+fn collision(path: &Path) -> ExportStatus {
+    ExportStatus::Failed(format!(
+        "{} is already written for another master",
+        path.display()
+    ))
+}
+
+/// Convert `version` into `xmp`.
+fn version_to_xmp(
+    version: &Version,
+    xmp: &mut Xmp,
+    options: &XmpOptions,
+    report: &mut XmpReport,
+) -> std::result::Result<(), String> {
+    if version.to_xmp_with_options(xmp, options, report) {
+        Ok(())
+    } else {
+        Err("the conversion to XMP failed".to_string())
+    }
+}
+
+/// Write the sidecar of `version` into `dest`, at `master_path`
+/// mirrored. `entry` is updated with the outcome. `written` has the files written during the
+/// export: a sidecar already there is a collision, like `IMG.CR2`
+/// and `IMG.JPG` with `SidecarNaming::ReplaceExtension`.
+pub(crate) fn write_sidecar(
+    version: &Version,
+    master_path: &Path,
+    dest: &Path,
+    options: &SidecarOptions,
+    written: &mut HashSet<PathBuf>,
+    entry: &mut ExportEntry,
+) {
+    let path = match destination_path(dest, master_path, version) {
+        Some(path) => sidecar_path(&path, options.naming),
+        None => {
+            entry.status = outside_destination(master_path);
+            return;
+        }
+    };
+    entry.destination = Some(path.clone());
+    if !written.insert(path.clone()) {
+        entry.status = collision(&path);
+        return;
+    }
+
+    let exists = path.exists();
+    if exists && options.existing == ExistingSidecar::Skip {
+        entry.status = ExportStatus::Exists;
+        return;
+    }
+    let merge = exists && options.existing == ExistingSidecar::Merge;
+    let xmp = if merge {
+        fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|buffer| Xmp::from_buffer(buffer).map_err(|err| err.to_string()))
+    } else {
+        Ok(Xmp::new())
+    };
+    let result = xmp.and_then(|mut xmp| {
+        version_to_xmp(version, &mut xmp, &options.xmp, &mut entry.xmp_report)?;
+        write_xmp(&xmp, &path)
+    });
+    entry.status = match result {
+        Ok(_) if merge => ExportStatus::Merged,
+        Ok(_) => ExportStatus::Written,
+        Err(err) => ExportStatus::Failed(err),
+    };
+}
+
+/// The extensions of the formats XMP is embedded in.
+const EMBEDDABLE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "tif", "tiff", "png"];
+
+/// Whether XMP can be embedded in the file at `path`. RAW files get a
+/// sidecar instead.
+pub(crate) fn can_embed(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| EMBEDDABLE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Copy the master file of `version`, as resolved in `entry`, into
+/// `dest` at `master_path` mirrored, and put the XMP in the copy.
+/// The master is only read. `entry` is updated with the outcome. `written` has the files written during the
+/// export, see `write_sidecar()`.
+pub(crate) fn export_master(
+    version: &Version,
+    master_path: &Path,
+    dest: &Path,
+    options: &EmbedOptions,
+    written: &mut HashSet<PathBuf>,
+    entry: &mut ExportEntry,
+) {
+    let source = match entry.master {
+        Some(ref master) => master.path.clone(),
+        None => return,
+    };
+    let path = match destination_path(dest, master_path, version) {
+        Some(path) => path,
+        None => {
+            entry.status = outside_destination(master_path);
+            return;
+        }
+    };
+    entry.destination = Some(path.clone());
+    let sidecar = (!can_embed(&path)).then(|| sidecar_path(&path, options.sidecar_naming));
+    if let Some(collided) = [Some(&path), sidecar.as_ref()]
+        .into_iter()
+        .flatten()
+        .find(|path| written.contains(*path))
+    {
+        entry.status = collision(collided);
+        return;
+    }
+    written.insert(path.clone());
+    if let Some(ref sidecar) = sidecar {
+        written.insert(sidecar.clone());
+    }
+    if entry.master.as_ref().is_some_and(|master| !master.exists) {
+        entry.status = ExportStatus::MissingMaster;
+        return;
+    }
+
+    if path.exists() {
+        if !options.overwrite {
+            entry.status = ExportStatus::Exists;
+            return;
+        }
+        let same_file = match (fs::canonicalize(&source), fs::canonicalize(&path)) {
+            (Ok(source), Ok(path)) => source == path,
+            _ => false,
+        };
+        if same_file {
+            entry.status = ExportStatus::Failed("the destination is the master".to_string());
+            return;
+        }
+    }
+
+    let result = path
+        .parent()
+        .map(fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| fs::copy(&source, &path))
+        .map_err(|err| err.to_string())
+        .and_then(|_| match sidecar {
+            None => embed_xmp(version, &path, &options.xmp, &mut entry.xmp_report)
+                .map(|_| ExportStatus::Embedded),
+            Some(sidecar) => {
+                let mut xmp = Xmp::new();
+                let result = version_to_xmp(version, &mut xmp, &options.xmp, &mut entry.xmp_report)
+                    .and_then(|_| write_xmp(&xmp, &sidecar))
+                    .map(|_| ExportStatus::Sidecar);
+                entry.sidecar = Some(sidecar);
+                result
+            }
+        });
+    entry.status = result.unwrap_or_else(ExportStatus::Failed);
+}
+
+/// Embed the XMP of `version` in the file at `path`, merged with the
+/// XMP already there.
+fn embed_xmp(
+    version: &Version,
+    path: &Path,
+    options: &XmpOptions,
+    report: &mut XmpReport,
+) -> std::result::Result<(), String> {
+    let mut file = XmpFile::new_from_file(path, exempi2::OpenFlags::FOR_UPDATE)
+        .map_err(|err| err.to_string())?;
+    let mut xmp = file.get_new_xmp().unwrap_or_default();
+    version_to_xmp(version, &mut xmp, options, report)?;
+    if !file.can_put_xmp(&xmp) {
+        return Err("can't embed the XMP".to_string());
+    }
+    file.put_xmp(&xmp).map_err(|err| err.to_string())?;
+    file.close(exempi2::CloseFlags::SAFE_UPDATE)
+        .map_err(|err| err.to_string())
+}
+
+/// Serialize `xmp` into the file at `path`, creating the directories.
+fn write_xmp(xmp: &Xmp, path: &Path) -> std::result::Result<(), String> {
+    let buffer = xmp
+        .serialize(exempi2::SerialFlags::OMITPACKETWRAPPER, 0)
+        .map_err(|err| err.to_string())?;
+    let buffer = buffer.to_str().map_err(|err| err.to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    fs::write(path, buffer).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+#[test]
+fn test_sidecar_path() {
+    use crate::testutils;
+    use crate::PlistLoadable;
+
+    let mut version = Version::from_path(
+        testutils::get_test_file_path("Version-0.apversion").as_path(),
+        None,
+    )
+    .unwrap();
+    let dest = Path::new("/tmp/export");
+
+    let path = destination_path(
+        dest,
+        Path::new("/Volumes/Photos/2016/IMG_3136.CR2"),
+        &version,
+    )
+    .unwrap();
+    assert_eq!(
+        path,
+        Path::new("/tmp/export/Volumes/Photos/2016/IMG_3136.CR2")
+    );
+    assert_eq!(
+        sidecar_path(&path, SidecarNaming::AppendExtension),
+        Path::new("/tmp/export/Volumes/Photos/2016/IMG_3136.CR2.xmp")
+    );
+    assert_eq!(
+        sidecar_path(&path, SidecarNaming::ReplaceExtension),
+        Path::new("/tmp/export/Volumes/Photos/2016/IMG_3136.xmp")
+    );
+
+    assert!(VersionSelection::Originals.selects(&version));
+    assert!(!VersionSelection::NonOriginals.selects(&version));
+
+    version.version_number = Some(2);
+    version.is_original = Some(false);
+    let path = destination_path(dest, Path::new("Masters/2016/IMG_3136.CR2"), &version).unwrap();
+    assert_eq!(path, Path::new("/tmp/export/Masters/2016/IMG_3136_02.CR2"));
+    assert_eq!(
+        sidecar_path(&path, SidecarNaming::AppendExtension),
+        Path::new("/tmp/export/Masters/2016/IMG_3136_02.CR2.xmp")
+    );
+
+    // The master path stays inside the destination.
+    assert_eq!(
+        destination_path(dest, Path::new("./2016/IMG_3136.CR2"), &version),
+        Some(PathBuf::from("/tmp/export/2016/IMG_3136_02.CR2"))
+    );
+    assert!(destination_path(dest, Path::new("Masters/../../IMG_3136.CR2"), &version).is_none());
+    assert!(destination_path(dest, Path::new("../IMG_3136.CR2"), &version).is_none());
+    assert!(destination_path(dest, Path::new("/"), &version).is_none());
+    let mut entry = ExportEntry::new("version", None);
+    write_sidecar(
+        &version,
+        Path::new("../IMG_3136.CR2"),
+        dest,
+        &SidecarOptions::default(),
+        &mut HashSet::new(),
+        &mut entry,
+    );
+    assert!(matches!(entry.status, ExportStatus::Failed(_)));
+    assert!(entry.destination.is_none());
+    assert!(VersionSelection::NonOriginals.selects(&version));
+
+    assert!(can_embed(Path::new("IMG_3136.JPG")));
+    assert!(can_embed(Path::new("scan.tiff")));
+    assert!(!can_embed(Path::new("IMG_3136.CR2")));
+    assert!(!can_embed(Path::new("README")));
+}
+
+#[cfg(test)]
+#[test]
+fn test_write_sidecars_selection() {
+    use crate::testutils;
+    use crate::{
+        ExportStatus, Library, LoadPolicy, SidecarOptions, VersionSelection, PROGRESS_NONE,
+    };
+
+    let path = testutils::create_test_db_library("test_write_sidecars_selection");
+    let dest = path.join("export");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    // The masters aren't loaded: nothing can be resolved.
+    let entries = library
+        .write_sidecars(&dest, &SidecarOptions::default())
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.status == ExportStatus::UnresolvedMaster));
+
+    let options = SidecarOptions {
+        versions: VersionSelection::NonOriginals,
+        ..Default::default()
+    };
+    let entries = library.write_sidecars(&dest, &options).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].version_uuid, "BF6nuoBnTumzoXyexdmXlw");
+    assert!(entries[0].destination.is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_write_sidecars() {
+    use crate::testutils;
+    use crate::xmp::ns::NS_XMP;
+    use crate::{
+        ExistingSidecar, ExportStatus, Library, LoadPolicy, SidecarNaming, SidecarOptions,
+        PROGRESS_NONE,
+    };
+    use exempi2::{PropFlags, Xmp};
+
+    let path = testutils::create_test_db_library("test_write_sidecars");
+    let dest = path.join("export");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let read_xmp = |path: &Path| Xmp::from_buffer(fs::read(path).unwrap()).unwrap();
+    let rating = |xmp: &Xmp| xmp.get_property_i64(NS_XMP, "Rating", &mut PropFlags::empty());
+    let sidecar = dest.join("Volumes/Photos/2011/img_8826_01.cr2.xmp");
+    let options = |existing| SidecarOptions {
+        existing,
+        ..Default::default()
+    };
+
+    let entries = library
+        .write_sidecars(&dest, &options(ExistingSidecar::Skip))
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.status == ExportStatus::Written));
+    assert_eq!(entries[0].destination.as_deref(), Some(sidecar.as_path()));
+    assert_eq!(rating(&read_xmp(&sidecar)).ok(), Some(4));
+
+    // Put a property of another application in the sidecar.
+    let mut xmp = read_xmp(&sidecar);
+    xmp.set_property(NS_XMP, "CreatorTool", "darktable", PropFlags::NONE)
+        .unwrap();
+    let _ = xmp.delete_property(NS_XMP, "Rating");
+    let buffer = xmp
+        .serialize(exempi2::SerialFlags::OMITPACKETWRAPPER, 0)
+        .unwrap();
+    fs::write(&sidecar, buffer.to_str().unwrap()).unwrap();
+
+    let entries = library
+        .write_sidecars(&dest, &options(ExistingSidecar::Skip))
+        .unwrap();
+    assert_eq!(entries[0].status, ExportStatus::Exists);
+    assert!(rating(&read_xmp(&sidecar)).is_err());
+
+    let entries = library
+        .write_sidecars(&dest, &options(ExistingSidecar::Merge))
+        .unwrap();
+    assert_eq!(entries[0].status, ExportStatus::Merged);
+    let xmp = read_xmp(&sidecar);
+    assert_eq!(rating(&xmp).ok(), Some(4));
+    assert!(xmp.has_property(NS_XMP, "CreatorTool"));
+
+    let entries = library
+        .write_sidecars(&dest, &options(ExistingSidecar::Overwrite))
+        .unwrap();
+    assert_eq!(entries[0].status, ExportStatus::Written);
+    let xmp = read_xmp(&sidecar);
+    assert_eq!(rating(&xmp).ok(), Some(4));
+    assert!(!xmp.has_property(NS_XMP, "CreatorTool"));
+    let _ = fs::remove_dir_all(&dest);
+
+    // A JPEG master with the same name: IMG.xmp for both.
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    conn.execute_batch(
+        "INSERT INTO RKMaster (modelId, uuid, projectUuid, fileName, name, fileVolumeUuid, \
+           type, subtype, imagePath, fileIsReference, isInTrash) \
+           VALUES (2, 'rZ0Jc3sKQbWm1tLq8AoP7g', 'evHgvM2oQ3GR0j6gEMnNTQ', 'img_8826.jpg', \
+           'img_8826', 'vEyyIbUTQ5eKfCqU9oZ8uQ', 'IMGT', 'JPGST', '2011/img_8826.jpg', 1, 0); \
+         UPDATE RKVersion SET masterUuid = 'rZ0Jc3sKQbWm1tLq8AoP7g', versionNumber = 0 \
+           WHERE modelId = 2;",
+    )
+    .unwrap();
+    drop(conn);
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    let options = SidecarOptions {
+        naming: SidecarNaming::ReplaceExtension,
+        ..Default::default()
+    };
+    let entries = library.write_sidecars(&dest, &options).unwrap();
+    assert_eq!(entries[0].status, ExportStatus::Written);
+    assert!(matches!(entries[1].status, ExportStatus::Failed(_)));
+    assert_eq!(entries[0].destination, entries[1].destination);
+    let sidecar = dest.join("Volumes/Photos/2011/img_8826.xmp");
+    assert_eq!(rating(&read_xmp(&sidecar)).ok(), Some(4));
+}
+
+#[cfg(test)]
+#[test]
+fn test_export_masters() {
+    use crate::testutils;
+    use crate::{EmbedOptions, ExportStatus, Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_export_masters");
+    let dest = path.join("export");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    // The referenced master isn't there.
+    let entries = library
+        .export_masters(&dest, &EmbedOptions::default())
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    let master = entries[1].master.as_ref().unwrap();
+    assert_eq!(master.path, Path::new("/Volumes/Photos/2011/img_8826.cr2"));
+    assert!(!master.exists);
+    assert_eq!(
+        entries[1].destination.as_deref(),
+        Some(dest.join("Volumes/Photos/2011/img_8826.cr2").as_path())
+    );
+    assert_eq!(entries[1].status, ExportStatus::MissingMaster);
+    assert!(!dest.join("Volumes/Photos/2011/img_8826.cr2").exists());
+
+    // Existing copies are left untouched.
+    let mut resolver = library.path_resolver();
+    resolver.map_volume_name("Photos", path.join("photos"));
+    library.set_path_resolver(resolver);
+    let master = path.join("photos/2011/img_8826.cr2");
+    fs::create_dir_all(master.parent().unwrap()).unwrap();
+    fs::write(&master, "raw").unwrap();
+    // Relative to the library, as it is inside.
+    let copy = dest.join("photos/2011/img_8826.cr2");
+    fs::create_dir_all(copy.parent().unwrap()).unwrap();
+    fs::write(&copy, "copy").unwrap();
+    let entries = library
+        .export_masters(&dest, &EmbedOptions::default())
+        .unwrap();
+    assert!(entries[1].master.as_ref().unwrap().exists);
+    assert_eq!(entries[1].status, ExportStatus::Exists);
+    assert_eq!(fs::read_to_string(&copy).unwrap(), "copy");
+}
+
+#[cfg(test)]
+#[test]
+fn test_export_masters_copy() {
+    use crate::testutils;
+    use crate::xmp::ns::NS_XMP;
+    use crate::{EmbedOptions, ExportStatus, Library, LoadPolicy, PROGRESS_NONE};
+    use exempi2::{OpenFlags, PropFlags, Xmp, XmpFile};
+
+    let path = testutils::create_test_db_library("test_export_masters_copy");
+    let dest = path.join("export");
+    // Managed masters: the RAW and a JPEG for the second version.
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    conn.execute_batch(
+        "UPDATE RKMaster SET fileVolumeUuid = NULL, fileIsReference = 0; \
+         INSERT INTO RKMaster (modelId, uuid, projectUuid, fileName, name, \
+           type, subtype, imagePath, fileIsReference, isInTrash) \
+           VALUES (2, 'rZ0Jc3sKQbWm1tLq8AoP7g', 'evHgvM2oQ3GR0j6gEMnNTQ', 'img_8826.jpg', \
+           'img_8826', 'IMGT', 'JPGST', '2011/img_8826.jpg', 0, 0); \
+         UPDATE RKVersion SET masterUuid = 'rZ0Jc3sKQbWm1tLq8AoP7g' WHERE modelId = 2;",
+    )
+    .unwrap();
+    drop(conn);
+    let masters = path.join("Masters/2011");
+    fs::create_dir_all(&masters).unwrap();
+    fs::write(masters.join("img_8826.cr2"), "raw").unwrap();
+    let jpeg = fs::read(testutils::get_test_file_path("img_8826.jpg")).unwrap();
+    fs::write(masters.join("img_8826.jpg"), &jpeg).unwrap();
+
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let entries = library
+        .export_masters(&dest, &EmbedOptions::default())
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // The JPEG gets the XMP embedded.
+    let copy = dest.join("Masters/2011/img_8826_01.jpg");
+    assert_eq!(entries[0].version_uuid, "BF6nuoBnTumzoXyexdmXlw");
+    assert_eq!(entries[0].status, ExportStatus::Embedded);
+    assert_eq!(entries[0].destination.as_deref(), Some(copy.as_path()));
+    assert!(entries[0].sidecar.is_none());
+    let xmp = XmpFile::new_from_file(&copy, OpenFlags::READ)
+        .unwrap()
+        .get_new_xmp()
+        .unwrap();
+    assert_eq!(
+        xmp.get_property_i64(NS_XMP, "Rating", &mut PropFlags::empty())
+            .ok(),
+        Some(4)
+    );
+
+    // The RAW is copied as is, with a sidecar.
+    let copy = dest.join("Masters/2011/img_8826.cr2");
+    let sidecar = dest.join("Masters/2011/img_8826.cr2.xmp");
+    assert_eq!(entries[1].status, ExportStatus::Sidecar);
+    assert_eq!(entries[1].destination.as_deref(), Some(copy.as_path()));
+    assert_eq!(entries[1].sidecar.as_deref(), Some(sidecar.as_path()));
+    assert_eq!(fs::read_to_string(&copy).unwrap(), "raw");
+    assert!(Xmp::from_buffer(fs::read(&sidecar).unwrap()).is_ok());
+
+    // The masters are untouched.
+    assert_eq!(fs::read(masters.join("img_8826.jpg")).unwrap(), jpeg);
+    assert_eq!(
+        fs::read_to_string(masters.join("img_8826.cr2")).unwrap(),
+        "raw"
+    );
+}
@@ -0,0 +1,395 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Faces: the named persons and the faces detected in the masters.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::audit::{
+    audit_get_bool_value, audit_get_int_value, audit_get_real_value, audit_get_str_value, Report,
+    SkipReason,
+};
+use crate::rect::Rect;
+use crate::{Error, PlistLoadable, Result, SqliteLoadable};
+
+/// A named person. The detected faces with the same face key are
+/// this person.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceName {
+    pub uuid: Option<String>,
+    pub model_id: Option<i64>,
+    /// The key shared with the detected faces.
+    pub face_key: Option<i64>,
+    /// uuid of the version used as the key photo.
+    pub key_version_uuid: Option<String>,
+    pub name: Option<String>,
+    pub full_name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// A face in a version, for the XMP regions.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceRegion {
+    /// The name of the person, if the face is confirmed.
+    pub name: Option<String>,
+    /// Where the face is in the master.
+    pub rect: Rect,
+}
+
+/// The state of a detected face.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FaceState {
+    /// Not named yet.
+    Unnamed,
+    /// Named by the user.
+    Confirmed,
+    /// Rejected by the user: not a face or not this person.
+    Rejected,
+}
+
+/// A face detected in a master.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectedFace {
+    pub uuid: Option<String>,
+    pub model_id: Option<i64>,
+    /// uuid of the master the face is in. Applies to all its versions.
+    pub master_uuid: Option<String>,
+    /// The key of the `FaceName`, if named.
+    pub face_key: Option<i64>,
+    /// Where the face is.
+    pub rect: Option<Rect>,
+    pub rejected: Option<bool>,
+    /// Ignored by the user.
+    pub ignore: Option<bool>,
+}
+
+impl DetectedFace {
+    /// The state of the face. Ignored faces are rejected.
+    pub fn state(&self) -> FaceState {
+        if self.rejected == Some(true) || self.ignore == Some(true) {
+            FaceState::Rejected
+        } else if self.face_key.is_some_and(|key| key > 0) {
+            FaceState::Confirmed
+        } else {
+            FaceState::Unnamed
+        }
+    }
+}
+
+/// Get the rectangle from the corner columns of `row`.
+fn row_rect(row: &rusqlite::Row) -> rusqlite::Result<Option<Rect>> {
+    let top_left_x: Option<f64> = row.get("topLeftX")?;
+    let top_left_y: Option<f64> = row.get("topLeftY")?;
+    let bottom_right_x: Option<f64> = row.get("bottomRightX")?;
+    let bottom_right_y: Option<f64> = row.get("bottomRightY")?;
+    Ok(top_left_x
+        .zip(top_left_y)
+        .zip(bottom_right_x.zip(bottom_right_y))
+        .map(|(top_left, bottom_right)| Rect::from_corners(top_left, bottom_right)))
+}
+
+impl SqliteLoadable for FaceName {
+    fn tables() -> &'static str {
+        "RKFaceName"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, faceKey, keyVersionUuid, name, fullName, email"
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(FaceName {
+            uuid: row.get("uuid")?,
+            model_id: row.get("modelId")?,
+            face_key: row.get("faceKey")?,
+            key_version_uuid: row.get("keyVersionUuid")?,
+            name: row.get("name")?,
+            full_name: row.get("fullName")?,
+            email: row.get("email")?,
+        })
+    }
+}
+
+impl SqliteLoadable for DetectedFace {
+    fn tables() -> &'static str {
+        "RKDetectedFace"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, masterUuid, faceKey, topLeftX, topLeftY, bottomRightX, bottomRightY, \
+         rejected, ignore"
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(DetectedFace {
+            uuid: row.get("uuid")?,
+            model_id: row.get("modelId")?,
+            master_uuid: row.get("masterUuid")?,
+            face_key: row.get("faceKey")?,
+            rect: row_rect(row)?,
+            rejected: row.get("rejected")?,
+            ignore: row.get("ignore")?,
+        })
+    }
+}
+
+impl PlistLoadable for FaceName {
+    /// Load the face name from the plist at plist_path.
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<FaceName>
+    where
+        P: AsRef<Path>,
+    {
+        use crate::plutils::*;
+
+        let plist = parse_plist(&plist_path)?;
+        match plist {
+            Value::Dictionary(ref dict) => {
+                let result = Ok(FaceName {
+                    uuid: audit_get_str_value(dict, "uuid", &mut auditor),
+                    model_id: audit_get_int_value(dict, "modelId", &mut auditor),
+                    face_key: audit_get_int_value(dict, "faceKey", &mut auditor),
+                    key_version_uuid: audit_get_str_value(dict, "keyVersionUuid", &mut auditor),
+                    name: audit_get_str_value(dict, "name", &mut auditor),
+                    full_name: audit_get_str_value(dict, "fullName", &mut auditor),
+                    email: audit_get_str_value(dict, "email", &mut auditor),
+                });
+                if let Some(auditor) = &mut auditor {
+                    auditor.audit_ignored(dict, None);
+                }
+                result
+            }
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
+        }
+    }
+}
+
+impl PlistLoadable for DetectedFace {
+    /// Load the detected face from the plist at plist_path.
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<DetectedFace>
+    where
+        P: AsRef<Path>,
+    {
+        use crate::plutils::*;
+
+        let plist = parse_plist(&plist_path)?;
+        match plist {
+            Value::Dictionary(ref dict) => {
+                let top_left_x = audit_get_real_value(dict, "topLeftX", &mut auditor);
+                let top_left_y = audit_get_real_value(dict, "topLeftY", &mut auditor);
+                let bottom_right_x = audit_get_real_value(dict, "bottomRightX", &mut auditor);
+                let bottom_right_y = audit_get_real_value(dict, "bottomRightY", &mut auditor);
+                let result = Ok(DetectedFace {
+                    uuid: audit_get_str_value(dict, "uuid", &mut auditor),
+                    model_id: audit_get_int_value(dict, "modelId", &mut auditor),
+                    master_uuid: audit_get_str_value(dict, "masterUuid", &mut auditor),
+                    face_key: audit_get_int_value(dict, "faceKey", &mut auditor),
+                    rect: top_left_x
+                        .zip(top_left_y)
+                        .zip(bottom_right_x.zip(bottom_right_y))
+                        .map(|(top_left, bottom_right)| Rect::from_corners(top_left, bottom_right)),
+                    rejected: audit_get_bool_value(dict, "rejected", &mut auditor),
+                    ignore: audit_get_bool_value(dict, "ignore", &mut auditor),
+                });
+                if let Some(auditor) = &mut auditor {
+                    auditor.skip("plistWriteTimestamp", SkipReason::Ignore);
+                    auditor.audit_ignored(dict, None);
+                }
+                result
+            }
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
+        }
+    }
+}
+
+/// Query all the `T` from `conn`.
+fn query_all<T: SqliteLoadable>(conn: &rusqlite::Connection) -> Result<Vec<T>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM {}", T::columns(), T::tables()))?;
+    let rows = stmt.query_and_then([], |row| T::from_row(row))?;
+    rows.collect()
+}
+
+/// The faces of the library.
+#[derive(Debug, Default)]
+pub struct Faces {
+    /// The named persons, by face key.
+    pub names: BTreeMap<i64, FaceName>,
+    /// The detected faces.
+    detected: Vec<DetectedFace>,
+    /// The index in `detected` of the faces, by master uuid.
+    by_master: HashMap<String, Vec<usize>>,
+}
+
+impl Faces {
+    /// Build from `names` and `detected`. The names without a face
+    /// key are dropped.
+    pub(crate) fn new(names: Vec<FaceName>, detected: Vec<DetectedFace>) -> Faces {
+        let mut by_master: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, face) in detected.iter().enumerate() {
+            if let Some(ref master_uuid) = face.master_uuid {
+                by_master
+                    .entry(master_uuid.clone())
+                    .or_default()
+                    .push(index);
+            }
+        }
+        Faces {
+            names: names
+                .into_iter()
+                .filter_map(|name| name.face_key.map(|key| (key, name)))
+                .collect(),
+            detected,
+            by_master,
+        }
+    }
+
+    /// Load from the faces database `conn`.
+    pub fn from_db(conn: &rusqlite::Connection) -> Result<Faces> {
+        Ok(Faces::new(query_all(conn)?, query_all(conn)?))
+    }
+
+    /// Return the detected faces.
+    pub fn detected(&self) -> &[DetectedFace] {
+        &self.detected
+    }
+
+    /// Return the faces detected in the master `uuid`.
+    pub fn for_master(&self, uuid: &str) -> Vec<&DetectedFace> {
+        self.by_master
+            .get(uuid)
+            .map(|indices| indices.iter().map(|index| &self.detected[*index]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Return the regions of the faces in the master `uuid`. The
+    /// rejected faces are left out.
+    pub fn regions_for_master(&self, uuid: &str) -> Vec<FaceRegion> {
+        self.for_master(uuid)
+            .into_iter()
+            .filter(|face| face.state() != FaceState::Rejected)
+            .filter_map(|face| {
+                face.rect.map(|rect| FaceRegion {
+                    name: self
+                        .name_of(face)
+                        .and_then(|name| name.full_name.clone().or_else(|| name.name.clone())),
+                    rect,
+                })
+            })
+            .collect()
+    }
+
+    /// Return the person of `face` if it is confirmed.
+    pub fn name_of(&self, face: &DetectedFace) -> Option<&FaceName> {
+        if face.state() != FaceState::Confirmed {
+            return None;
+        }
+        face.face_key.and_then(|key| self.names.get(&key))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_faces() {
+    let faces = Faces::new(
+        vec![
+            FaceName {
+                face_key: Some(12),
+                name: Some("Hubert".to_string()),
+                ..Default::default()
+            },
+            FaceName::default(),
+        ],
+        vec![
+            DetectedFace {
+                master_uuid: Some("master".to_string()),
+                face_key: Some(12),
+                ..Default::default()
+            },
+            DetectedFace {
+                master_uuid: Some("master".to_string()),
+                face_key: Some(12),
+                rejected: Some(true),
+                ..Default::default()
+            },
+            DetectedFace {
+                master_uuid: Some("other".to_string()),
+                ..Default::default()
+            },
+        ],
+    );
+    assert_eq!(faces.names.len(), 1);
+
+    let detected = faces.for_master("master");
+    assert_eq!(detected.len(), 2);
+    assert_eq!(detected[0].state(), FaceState::Confirmed);
+    assert_eq!(
+        faces
+            .name_of(detected[0])
+            .and_then(|name| name.name.as_deref()),
+        Some("Hubert")
+    );
+    assert_eq!(detected[1].state(), FaceState::Rejected);
+    assert!(faces.name_of(detected[1]).is_none());
+    assert_eq!(faces.for_master("other")[0].state(), FaceState::Unnamed);
+    assert!(faces.for_master("unknown").is_empty());
+    // No rectangle, no region.
+    assert!(faces.regions_for_master("master").is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_load_faces() {
+    use crate::testutils;
+    use crate::{store, FaceState, Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_load_faces");
+    let sql = std::fs::read_to_string(testutils::get_test_file_path("Faces.sql")).unwrap();
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Faces.db")).unwrap();
+    conn.execute_batch(&sql).unwrap();
+    drop(conn);
+
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.faces().is_none());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert!(library.load_faces(PROGRESS_NONE).is_ok());
+
+    let faces = library.faces().unwrap();
+    assert_eq!(faces.names.len(), 1);
+    let detected = library.faces_for_version("BF6nuoBnTumzoXyexdmXlw");
+    assert_eq!(detected.len(), 2);
+    let named: Vec<&str> = detected
+        .iter()
+        .filter_map(|face| faces.name_of(face))
+        .filter_map(|name| name.full_name.as_deref())
+        .collect();
+    assert_eq!(named, vec!["Hubert Figuière"]);
+    assert!(detected
+        .iter()
+        .any(|face| face.state() == FaceState::Rejected));
+    let rect = detected[0].rect.unwrap();
+    assert!((rect.y - 0.2).abs() < 1e-9);
+    assert!((rect.height - 0.3).abs() < 1e-9);
+
+    // The rejected face has no region.
+    match library.get("BF6nuoBnTumzoXyexdmXlw") {
+        Some(store::Wrapper::Version(version)) => {
+            let regions = version.face_regions();
+            assert_eq!(regions.len(), 1);
+            assert_eq!(regions[0].name.as_deref(), Some("Hubert Figuière"));
+        }
+        _ => unreachable!(),
+    }
+}
@@ -14,16 +14,19 @@ use crate::audit::{
     audit_get_str_value, Report, SkipReason,
 };
 use crate::notes::NotesProperties;
+use crate::sqlutils::get_date_value;
 use crate::store;
 use crate::AplibObject;
 use crate::AplibType;
 use crate::PlistLoadable;
+use crate::{Error, Result, SqliteLoadable};
 
 #[derive(
     Clone, Copy, Debug, Default, num_derive::ToPrimitive, num_derive::FromPrimitive, PartialEq,
 )]
 #[repr(u32)]
 /// Type of folder
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     #[default]
     Invalid = 0,
@@ -35,6 +38,7 @@ pub enum Type {
 
 /// Folder object. This is a container of things in the library.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Folder {
     /// object uuid
     uuid: Option<String>,
@@ -71,17 +75,17 @@ pub struct Folder {
 }
 
 impl PlistLoadable for Folder {
-    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Option<Folder>
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Folder>
     where
         P: AsRef<Path>,
     {
         use crate::plutils::*;
 
-        let plist = parse_plist(plist_path);
+        let plist = parse_plist(&plist_path)?;
         match plist {
             Value::Dictionary(ref dict) => {
                 let notes = audit_get_array_value(dict, "notes", &mut auditor);
-                let result = Some(Folder {
+                let result = Ok(Folder {
                     path: audit_get_str_value(dict, "folderPath", &mut auditor),
                     folder_type: audit_get_int_value(dict, "folderType", &mut auditor)
                         .and_then(FromPrimitive::from_i64),
@@ -125,11 +129,57 @@ impl PlistLoadable for Folder {
                 }
                 result
             }
-            _ => None,
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
         }
     }
 }
 
+impl SqliteLoadable for Folder {
+    fn tables() -> &'static str {
+        "RKFolder"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, parentFolderUuid, folderType, folderPath, name, \
+         implicitAlbumUuid, colorLabelIndex, createDate, sortKeyPath, sortAscending, \
+         isHidden, isMagic, isFavorite, isInTrash, isExpanded, isHiddenWhenEmpty, \
+         posterVersionUuid"
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Folder {
+            uuid: row.get("uuid")?,
+            parent_uuid: row.get("parentFolderUuid")?,
+            model_id: row.get("modelId")?,
+            folder_type: row
+                .get::<_, Option<i64>>("folderType")?
+                .and_then(FromPrimitive::from_i64),
+            // Only in the plist.
+            db_version: None,
+            project_version: None,
+            path: row.get("folderPath")?,
+            name: row.get("name")?,
+            implicit_album_uuid: row.get("implicitAlbumUuid")?,
+            colour_label_index: row.get("colorLabelIndex")?,
+            create_date: get_date_value(row, "createDate")?,
+            sort_key_path: row.get("sortKeyPath")?,
+            sort_ascending: row.get("sortAscending")?,
+            is_hidden: row.get("isHidden")?,
+            is_magic: row.get("isMagic")?,
+            is_favourite: row.get("isFavorite")?,
+            is_in_trash: row.get("isInTrash")?,
+            is_expanded: row.get("isExpanded")?,
+            is_hidden_when_empty: row.get("isHiddenWhenEmpty")?,
+            poster_version_uuid: row.get("posterVersionUuid")?,
+            // Notes are in RKNote.
+            notes: None,
+        })
+    }
+}
+
 impl AplibObject for Folder {
     fn obj_type(&self) -> AplibType {
         AplibType::Folder
@@ -162,7 +212,7 @@ fn test_folder_parse() {
         testutils::get_test_file_path("a%TX9lmjQVWvuK9u6RNhGQ.apfolder").as_path(),
         None,
     );
-    assert!(folder.is_some());
+    assert!(folder.is_ok());
     let folder = folder.unwrap();
 
     assert_eq!(folder.uuid.as_ref().unwrap(), "a%TX9lmjQVWvuK9u6RNhGQ");
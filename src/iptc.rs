@@ -11,7 +11,7 @@ use plist::Value;
 
 use crate::audit::{Report, SkipReason};
 use crate::xmp::ns::*;
-use crate::xmp::{ToXmp, XmpProperty, XmpTranslator};
+use crate::xmp::{keywords_to_subject, ToXmp, XmpProperty, XmpTranslator};
 
 lazy_static::lazy_static! {
     /// HashMap for IPTC properties (Aperture) to XMP.
@@ -59,8 +59,7 @@ lazy_static::lazy_static! {
 //        "DateCreated" => "",
         "Headline" => XmpTranslator::Property(XmpProperty::new(
             NS_PHOTOSHOP, "Headline")),
-        "Keywords" => XmpTranslator::Property(XmpProperty::new(
-            NS_DC, "subject")),
+        "Keywords" => XmpTranslator::Custom,
 //        "Label" => "",
         "ObjectAttributeReference" => XmpTranslator::Property(
             XmpProperty::new(NS_IPTC4XMP, "IntellectualGenre")),
@@ -91,12 +90,14 @@ lazy_static::lazy_static! {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IptcValue {
     None,
     Str(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IptcProperties {
     pub bag: BTreeMap<String, IptcValue>,
 }
@@ -127,6 +128,27 @@ impl IptcProperties {
         }
         Some(IptcProperties { bag: values })
     }
+
+    /// Load the IPTC properties of all the versions from the
+    /// properties database `conn`. The key is the version model id.
+    pub fn from_db(conn: &rusqlite::Connection) -> crate::Result<HashMap<i64, IptcProperties>> {
+        let mut result: HashMap<i64, IptcProperties> = HashMap::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT p.versionId, p.propertyKey, s.stringProperty \
+             FROM RKIptcProperty p JOIN RKUniqueString s ON p.stringId = s.modelId",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            result
+                .entry(row.get(0)?)
+                .or_default()
+                .bag
+                .insert(row.get(1)?, IptcValue::Str(row.get(2)?));
+        }
+
+        Ok(result)
+    }
 }
 
 impl ToXmp for IptcProperties {
@@ -136,8 +158,20 @@ impl ToXmp for IptcProperties {
                 IptcValue::Str(ref str) => str,
                 _ => continue,
             };
-            if let Some(XmpTranslator::Property(ref prop)) = IPTC_TO_XMP.get(&key.as_str()) {
-                prop.put_into_xmp(value, xmp);
+            match IPTC_TO_XMP.get(&key.as_str()) {
+                Some(XmpTranslator::Property(ref prop)) => {
+                    prop.put_into_xmp(value, xmp);
+                }
+                Some(XmpTranslator::Custom) if key == "Keywords" => {
+                    // dc:subject is a bag.
+                    let keywords: Vec<&str> = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|keyword| !keyword.is_empty())
+                        .collect();
+                    keywords_to_subject(&keywords, xmp);
+                }
+                _ => {}
             }
         }
         true
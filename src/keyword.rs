@@ -4,6 +4,7 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
+use std::collections::HashMap;
 use std::path::Path;
 
 use crate::audit::{audit_get_int_value, Report};
@@ -11,9 +12,11 @@ use crate::plutils::*;
 use crate::store;
 use crate::AplibObject;
 use crate::AplibType;
+use crate::{Error, Result, SqliteLoadable};
 
 /// An Aperture keyword.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Keyword {
     /// The uuid
     uuid: Option<String>,
@@ -44,34 +47,172 @@ impl AplibObject for Keyword {
     fn is_valid(&self) -> bool {
         self.uuid.is_some()
     }
-    #[doc(hidden)]
-    fn wrap(_: Keyword) -> store::Wrapper {
-        store::Wrapper::None
+    fn wrap(obj: Keyword) -> store::Wrapper {
+        store::Wrapper::Keyword(Box::new(obj))
+    }
+}
+
+/// A keyword assigned to a version.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeywordAssignment {
+    /// uuid of the keyword.
+    pub keyword_uuid: String,
+    /// Names of the keyword hierarchy, root first.
+    pub path: Vec<String>,
+}
+
+/// The keyword assignments of the library, both ways.
+#[derive(Debug, Default)]
+pub(crate) struct KeywordAssignments {
+    /// The keywords by version uuid.
+    pub by_version: HashMap<String, Vec<KeywordAssignment>>,
+    /// The version uuids by keyword uuid.
+    pub by_keyword: HashMap<String, Vec<String>>,
+}
+
+impl KeywordAssignments {
+    /// Assign `keyword_uuid` to `version_uuid`. `paths` are the keyword
+    /// paths by uuid. Return false if the keyword is unknown.
+    pub fn assign(
+        &mut self,
+        version_uuid: &str,
+        keyword_uuid: &str,
+        paths: &HashMap<String, Vec<String>>,
+    ) -> bool {
+        let path = match paths.get(keyword_uuid) {
+            Some(path) => path.clone(),
+            None => return false,
+        };
+        self.by_version
+            .entry(version_uuid.to_owned())
+            .or_default()
+            .push(KeywordAssignment {
+                keyword_uuid: keyword_uuid.to_owned(),
+                path,
+            });
+        self.by_keyword
+            .entry(keyword_uuid.to_owned())
+            .or_default()
+            .push(version_uuid.to_owned());
+        true
+    }
+
+    /// Load the pairs of version uuid and keyword uuid from
+    /// `RKKeywordForVersion` in the library database `conn`.
+    pub fn pairs_from_db(conn: &rusqlite::Connection) -> Result<Vec<(String, String)>> {
+        let mut stmt = conn.prepare(
+            "SELECT v.uuid, k.uuid FROM RKKeywordForVersion kv \
+             JOIN RKVersion v ON kv.versionId = v.modelId \
+             JOIN RKKeyword k ON kv.keywordId = k.modelId",
+        )?;
+        let result = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+
+        Ok(result)
     }
 }
 
 /// Parse keywords from the .plist file
-pub fn parse_keywords<P>(path: P, auditor: &mut Option<&mut Report>) -> Option<Vec<Keyword>>
+pub fn parse_keywords<P>(path: P, auditor: &mut Option<&mut Report>) -> Result<Vec<Keyword>>
 where
     P: AsRef<Path>,
 {
-    let plist = parse_plist(path);
+    let plist = parse_plist(&path)?;
 
     match plist {
         Value::Dictionary(ref dict) => {
-            let version = audit_get_int_value(dict, "keywords_version", auditor)?;
-            // XXX deal with proper errors here.
+            let version =
+                audit_get_int_value(dict, "keywords_version", auditor).ok_or_else(|| {
+                    Error::MissingProperty(path.as_ref().to_path_buf(), "keywords_version")
+                })?;
             // Version 3.4.5 has version 7.
             if version != 6 && version != 7 {
-                println!("Wrong keyword version {} !", version);
+                return Err(Error::UnsupportedVersion(
+                    path.as_ref().to_path_buf(),
+                    version.to_string(),
+                ));
             }
-            Keyword::from_array(get_array_value(dict, "keywords"))
+            // A library without keywords may not have the array.
+            Ok(Keyword::from_array(get_array_value(dict, "keywords")).unwrap_or_default())
         }
-        _ => None,
+        _ => Err(Error::InvalidData(path.as_ref().to_path_buf())),
+    }
+}
+
+impl SqliteLoadable for Keyword {
+    fn tables() -> &'static str {
+        "RKKeyword k LEFT JOIN RKKeyword p ON k.parentId = p.modelId"
+    }
+
+    fn columns() -> &'static str {
+        "k.modelId, k.uuid, k.name, p.uuid"
+    }
+
+    /// Load the keyword from the row. The children aren't loaded.
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let model_id = row.get(0)?;
+        let uuid = row.get(1)?;
+        let name = row.get(2)?;
+        let parent_uuid = row.get(3)?;
+        Ok(Keyword {
+            uuid,
+            model_id,
+            parent_uuid,
+            name,
+            children: None,
+        })
     }
 }
 
 impl Keyword {
+    /// Flatten the keyword trees in `keywords`, parents first.
+    /// The children are moved out.
+    pub(crate) fn flatten(keywords: Vec<Keyword>) -> Vec<Keyword> {
+        let mut result = Vec::new();
+        for mut keyword in keywords {
+            let children = keyword.children.take();
+            result.push(keyword);
+            if let Some(children) = children {
+                result.append(&mut Keyword::flatten(children));
+            }
+        }
+        result
+    }
+
+    /// Return the path of each keyword in `keywords`, root first,
+    /// by uuid. `keywords` must be flat, see `Keyword::flatten()`.
+    pub(crate) fn paths<'a, I>(keywords: I) -> HashMap<String, Vec<String>>
+    where
+        I: IntoIterator<Item = &'a Keyword>,
+    {
+        let by_uuid: HashMap<&String, &Keyword> = keywords
+            .into_iter()
+            .filter_map(|keyword| keyword.uuid.as_ref().map(|uuid| (uuid, keyword)))
+            .collect();
+
+        let mut paths = HashMap::new();
+        for (uuid, keyword) in &by_uuid {
+            let mut path = vec![keyword.name.clone().unwrap_or_default()];
+            let mut parent = keyword.parent_uuid.as_ref();
+            // The length check prevent looping on a cycle.
+            while let Some(parent_keyword) = parent.and_then(|uuid| by_uuid.get(uuid)) {
+                if path.len() > by_uuid.len() {
+                    break;
+                }
+                path.push(parent_keyword.name.clone().unwrap_or_default());
+                parent = parent_keyword.parent_uuid.as_ref();
+            }
+            path.reverse();
+            paths.insert((*uuid).clone(), path);
+        }
+        paths
+    }
+
     /// convert a Plist array to a vec of keyword.
     fn from_array(oa: Option<Vec<Value>>) -> Option<Vec<Keyword>> {
         let a = oa?;
@@ -99,3 +240,145 @@ impl From<&plist::Dictionary> for Keyword {
         }
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_keyword_paths() {
+    let keyword = |uuid: &str, parent: Option<&str>, name: &str| Keyword {
+        uuid: Some(uuid.to_owned()),
+        model_id: None,
+        parent_uuid: parent.map(str::to_owned),
+        name: Some(name.to_owned()),
+        children: None,
+    };
+    let keywords = vec![
+        keyword("locations", None, "+locations"),
+        keyword("canada", Some("locations"), "canada"),
+        keyword("ontario", Some("canada"), "ontario"),
+    ];
+    let paths = Keyword::paths(&keywords);
+    assert_eq!(paths["locations"], vec!["+locations"]);
+    assert_eq!(paths["ontario"], vec!["+locations", "canada", "ontario"]);
+
+    let mut assignments = KeywordAssignments::default();
+    assert!(assignments.assign("version", "ontario", &paths));
+    assert!(!assignments.assign("version", "unknown", &paths));
+    assert_eq!(assignments.by_version["version"].len(), 1);
+    assert_eq!(assignments.by_version["version"][0].keyword_uuid, "ontario");
+    assert_eq!(assignments.by_keyword["ontario"], vec!["version"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_keywords_for_version() {
+    use crate::testutils;
+    use crate::Library;
+
+    let path = testutils::create_test_db_library("test_keywords_for_version");
+    let mut library = Library::new(&path);
+
+    // Not loaded yet.
+    assert!(library
+        .keywords_for_version("BF6nuoBnTumzoXyexdmXlw")
+        .is_empty());
+    assert!(library.load_keyword_assignments().is_ok());
+
+    assert_eq!(
+        library.keywords_for_version("BF6nuoBnTumzoXyexdmXlw"),
+        &[KeywordAssignment {
+            keyword_uuid: "hxLCb2k1T7mbDLm4PHdtHg".to_string(),
+            path: vec!["Places".to_string(), "Paris".to_string()],
+        }]
+    );
+    assert!(library
+        .keywords_for_version("VF%CkiTKQy+h53Oyr7KCOA")
+        .is_empty());
+    assert_eq!(
+        library.versions_for_keyword("hxLCb2k1T7mbDLm4PHdtHg"),
+        &["BF6nuoBnTumzoXyexdmXlw".to_string()]
+    );
+    assert!(library
+        .versions_for_keyword("ZqA2XTm9RNa%2nK2UyBqSQ")
+        .is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_keywords_for_version_unknown() {
+    use crate::audit::{Reporter, SkipReason};
+    use crate::testutils;
+    use crate::{Library, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_keywords_for_version_unknown");
+    let mut library = Library::new(&path);
+    library.set_auditor(Some(Reporter::new()));
+    assert!(library.load_keywords(PROGRESS_NONE).is_ok());
+
+    // A keyword added after the keywords got loaded.
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    conn.execute_batch(
+        "INSERT INTO RKKeyword VALUES (3, 'u9mQx3bVQ1eAb0Kc7yLZtg', 'Lyon', 1);
+         INSERT INTO RKKeywordForVersion VALUES (2, 1, 3);",
+    )
+    .unwrap();
+
+    assert!(library.load_keyword_assignments().is_ok());
+    assert!(library
+        .keywords_for_version("VF%CkiTKQy+h53Oyr7KCOA")
+        .is_empty());
+    assert_eq!(
+        library.keywords_for_version("BF6nuoBnTumzoXyexdmXlw").len(),
+        1
+    );
+
+    let auditor = library.auditor().unwrap();
+    assert!(matches!(
+        auditor
+            .get_skipped()
+            .get("VF%CkiTKQy+h53Oyr7KCOA.keywords.u9mQx3bVQ1eAb0Kc7yLZtg"),
+        Some(SkipReason::NotFound)
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn test_keyword_tree() {
+    use crate::audit::Reporter;
+    use crate::testutils;
+    use crate::{Library, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_keyword_tree");
+    let mut library = Library::new(&path);
+    library.set_auditor(Some(Reporter::new()));
+
+    assert!(library.load_keywords(PROGRESS_NONE).is_ok());
+    assert_eq!(library.keywords().len(), 2);
+    assert!(matches!(
+        library.get("hxLCb2k1T7mbDLm4PHdtHg"),
+        Some(store::Wrapper::Keyword(_))
+    ));
+
+    let roots = library.keyword_roots();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].name.as_deref(), Some("Places"));
+
+    let children = library.keyword_children("ZqA2XTm9RNa%2nK2UyBqSQ");
+    assert_eq!(children.len(), 1);
+    assert_eq!(
+        children[0].uuid().as_deref(),
+        Some("hxLCb2k1T7mbDLm4PHdtHg")
+    );
+    assert!(library
+        .keyword_children("hxLCb2k1T7mbDLm4PHdtHg")
+        .is_empty());
+
+    let ancestors = library.keyword_ancestors("hxLCb2k1T7mbDLm4PHdtHg");
+    assert_eq!(ancestors.len(), 1);
+    assert_eq!(ancestors[0].name.as_deref(), Some("Places"));
+
+    assert_eq!(
+        library.keyword_path("hxLCb2k1T7mbDLm4PHdtHg").as_deref(),
+        Some("Places|Paris")
+    );
+    assert_eq!(library.keyword_path("unknown"), None);
+}
@@ -4,18 +4,32 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
+mod adjustments;
 mod album;
 pub mod audit;
+mod csvexport;
 mod custominfo;
 mod exif;
+mod export;
+mod faces;
 mod folder;
 mod iptc;
 mod keyword;
 mod library;
 mod master;
 mod notes;
+mod pairs;
+mod place;
 pub mod plutils;
+mod query;
+mod rect;
+mod resolver;
+mod sqlexport;
+mod sqlutils;
+mod stack;
 mod store;
+pub mod time;
+mod verify;
 mod version;
 mod volume;
 mod xmp;
@@ -23,30 +37,81 @@ mod xmp;
 #[cfg(test)]
 mod testutils;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+pub use adjustments::{Adjustment, Adjustments, CameraRawSettings, Operation};
 pub use album::Album;
 pub use album::Subclass as AlbumSubclass;
 use audit::Report;
 pub use audit::SkipReason;
+pub use csvexport::{CsvColumn, DEFAULT_CSV_COLUMNS};
+pub use export::{
+    EmbedOptions, ExistingSidecar, ExportEntry, ExportStatus, SidecarNaming, SidecarOptions,
+    VersionSelection,
+};
+pub use faces::{DetectedFace, FaceName, FaceRegion, FaceState, Faces};
 pub use folder::Folder;
 pub use folder::Type as FolderType;
-pub use keyword::Keyword;
-pub use library::{Library, ModelInfo, PROGRESS_NONE};
+pub use keyword::{Keyword, KeywordAssignment};
+pub use library::{Library, LoadPolicy, ModelInfo, PROGRESS_NONE};
 pub use master::Master;
+pub use pairs::{MasterPair, PairMember, PairPolicy};
+pub use place::Place;
+pub use query::{Comparison, Criterion, MatchType, SmartQuery};
+pub use rect::Rect;
+pub use resolver::{PathResolver, ResolvedPath};
+pub use sqlexport::SCHEMA as SQLITE_EXPORT_SCHEMA;
+pub use stack::Stack;
 pub use store::Wrapper as StoreWrapper;
+pub use verify::{Mismatch, Orphan, VerifyReport};
 pub use version::Version;
+pub use xmp::{FlagTarget, StackTarget, ToXmp, XmpOptions, XmpReport, APERTURE_LABELS};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("sqlite error {0}")]
     Sql(#[from] rusqlite::Error),
+    /// I/O error while accessing the path.
+    #[error("I/O error on {}: {}", .0.display(), .1)]
+    Io(PathBuf, #[source] std::io::Error),
+    /// The plist at path couldn't be decoded.
+    #[error("plist error in {}: {}", .0.display(), .1)]
+    Plist(PathBuf, #[source] plist::Error),
+    /// The plist at path doesn't have the expected structure.
+    #[error("invalid data in {}", .0.display())]
+    InvalidData(PathBuf),
+    /// A required property is missing from the plist at path.
+    #[error("property {} not found in {}", .1, .0.display())]
+    MissingProperty(PathBuf, &'static str),
+    /// The bundle isn't an Aperture library. Has the bundle identifier found.
+    #[error("{} is not an Aperture library ({})", .0.display(), .1)]
+    WrongBundle(PathBuf, String),
+    /// The version found in path isn't supported.
+    #[error("unsupported version {} in {}", .1, .0.display())]
+    UnsupportedVersion(PathBuf, String),
+    /// The library database at path couldn't be opened.
+    #[error("database {} not found", .0.display())]
+    MissingDatabase(PathBuf),
+    /// The query of the smart album uuid can't be evaluated.
+    #[error("unsupported query in album {0}")]
+    UnsupportedQuery(String),
+    /// The CSV column isn't known.
+    #[error("unknown column {0}")]
+    UnknownColumn(String),
+    /// Error writing the export.
+    #[error("write error {0}")]
+    Write(#[source] std::io::Error),
+    /// Error writing JSON.
+    #[cfg(feature = "serde")]
+    #[error("JSON error {0}")]
+    Json(#[from] serde_json::Error),
 }
 
-type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 /// `AplibObject` types.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AplibType {
     /// Album
     Album,
@@ -56,6 +121,8 @@ pub enum AplibType {
     Keyword,
     /// Master image
     Master,
+    /// Place
+    Place,
     /// Version
     Version,
     /// Volume
@@ -65,7 +132,7 @@ pub enum AplibType {
 /// Object that can be loaded from a single plist.
 pub trait PlistLoadable {
     /// Load object from plist `plist_path`
-    fn from_path<P>(plist_path: P, auditor: Option<&mut Report>) -> Option<Self>
+    fn from_path<P>(plist_path: P, auditor: Option<&mut Report>) -> Result<Self>
     where
         P: AsRef<Path>,
         Self: Sized;
@@ -11,16 +11,29 @@ use std::path::{Path, PathBuf};
 use once_cell::unsync::OnceCell;
 use plist::Value;
 
-use crate::album::Album;
+use crate::album::{Album, Subclass as AlbumSubclass};
 use crate::audit::{audit_get_str_value, Report, Reporter, SkipReason};
+use crate::csvexport::{self, CsvColumn, CsvRow};
+use crate::exif::{ExifProperties, ExifValue};
+use crate::export::{self, EmbedOptions, ExportEntry, SidecarOptions, VersionSelection};
+use crate::faces::{DetectedFace, FaceName, Faces};
 use crate::folder::Folder;
-use crate::keyword::{parse_keywords, Keyword};
+use crate::iptc::IptcProperties;
+use crate::keyword::{parse_keywords, Keyword, KeywordAssignment, KeywordAssignments};
 use crate::master::Master;
+use crate::pairs::{MasterPair, PairPolicy};
+use crate::place::Place;
 use crate::plutils;
+use crate::query::SmartQuery;
+use crate::resolver::{PathResolver, ResolvedPath};
+use crate::sqlexport;
+use crate::sqlutils;
+use crate::stack::Stack;
 use crate::store;
+use crate::verify::{Verifiable, Verifier, VerifyReport};
 use crate::version::Version;
 use crate::volume::Volume;
-use crate::{AplibObject, PlistLoadable, SqliteLoadable};
+use crate::{AplibObject, Error, PlistLoadable, Result, SqliteLoadable};
 
 // This is mostly from db_version = 110
 
@@ -35,12 +48,32 @@ const DATAMODEL_VERSION_PLIST: &str = "DataModelVersion.plist";
 const KEYWORDS_PLIST: &str = "Keywords.plist";
 const ALBUMS_DIR: &str = "Albums";
 const FOLDERS_DIR: &str = "Folders";
+const PLACES_DIR: &str = "Places";
 const VOLUMES_DIR: &str = "Volumes";
 const VERSIONS_BASE_DIR: &str = "Versions";
+const LIBRARY_DB: &str = "apdb/Library.apdb";
+const PROPERTIES_DB: &str = "apdb/Properties.apdb";
+const FACES_DB: &str = "apdb/Faces.db";
+const DETECTED_FACES_DIR: &str = "Faces/Detected";
+const FACE_NAMES_DIR: &str = "Faces/FaceNames";
 
 pub const PROGRESS_NONE: Option<fn(u64) -> bool> = None;
 
+/// Policy to load the objects either from the plists or
+/// from the database (Library.apdb).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LoadPolicy {
+    #[default]
+    /// Load from the plists. Use the database if there are none.
+    PlistFirst,
+    /// Load from the database. Use the plists if there is no database.
+    DatabaseFirst,
+    /// Load from the database only. Fail if there is no database.
+    DatabaseOnly,
+}
+
 /// Info of the library data model
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModelInfo {
     pub is_iphoto_library: Option<bool>,
     pub db_version: Option<i64>,
@@ -88,6 +121,36 @@ impl ModelInfo {
     }
 }
 
+/// A record of the JSON export. `obj_type` is the `AplibType` of the
+/// stored objects, or the name of the other types.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonRecord<'a, K, T> {
+    #[serde(rename = "type")]
+    obj_type: K,
+    object: &'a T,
+}
+
+/// Write the record of `object` to `writer`, on a line.
+#[cfg(feature = "serde")]
+fn write_json_record<W, K, T>(writer: &mut W, obj_type: K, object: &T) -> Result<()>
+where
+    W: std::io::Write,
+    K: serde::Serialize,
+    T: serde::Serialize,
+{
+    let record = JsonRecord { obj_type, object };
+    // The writer errors are write errors, like in the CSV export.
+    serde_json::to_writer(&mut *writer, &record).map_err(|err| {
+        if err.is_io() {
+            Error::Write(err.into())
+        } else {
+            Error::Json(err)
+        }
+    })?;
+    writer.write_all(b"\n").map_err(Error::Write)
+}
+
 /// Library is the Aperture library.
 pub struct Library {
     /// The path to the .aplib bundle (the directory)
@@ -100,20 +163,39 @@ pub struct Library {
     folders: HashSet<String>,
     /// All the albums UUID
     albums: HashSet<String>,
-    //    keywords: HashSet<String>,
+    /// All the keywords UUID
+    keywords: HashSet<String>,
     /// All the masters UUID
     masters: HashSet<String>,
     /// All the version UUID
     versions: HashSet<String>,
     /// All the volumes UUID
     volumes: HashSet<String>,
+    /// All the places UUID
+    places: HashSet<String>,
 
     /// The object store. The key is the UUID
     objects: HashMap<String, store::Wrapper>,
     /// Auditor for the audit mode.
     auditor: Option<Reporter>,
+    /// Where to load the objects from.
+    load_policy: LoadPolicy,
+    /// The keywords assigned to the versions.
+    keyword_assignments: Option<KeywordAssignments>,
+    /// The places of the versions, by version uuid.
+    version_places: HashMap<String, Vec<String>>,
+    /// The faces.
+    faces: Option<Faces>,
+    /// The stacks, sorted by uuid.
+    stacks: Option<Vec<Stack>>,
+    /// Resolve the paths of the masters.
+    resolver: PathResolver,
     /// Database connection
     database_conn: OnceCell<Option<rusqlite::Connection>>,
+    /// Properties database connection
+    properties_conn: OnceCell<Option<rusqlite::Connection>>,
+    /// Faces database connection
+    faces_conn: OnceCell<Option<rusqlite::Connection>>,
 }
 
 impl Library {
@@ -129,15 +211,24 @@ impl Library {
 
             folders: HashSet::new(),
             albums: HashSet::new(),
-            //            keywords: HashSet::new(),
+            keywords: HashSet::new(),
             masters: HashSet::new(),
             versions: HashSet::new(),
             volumes: HashSet::new(),
+            places: HashSet::new(),
 
             objects: HashMap::new(),
             auditor: None,
+            load_policy: LoadPolicy::default(),
+            keyword_assignments: None,
+            version_places: HashMap::new(),
+            faces: None,
+            stacks: None,
+            resolver: PathResolver::new(p.as_ref()),
 
             database_conn: OnceCell::new(),
+            properties_conn: OnceCell::new(),
+            faces_conn: OnceCell::new(),
         }
     }
 
@@ -150,12 +241,38 @@ impl Library {
         self.auditor.as_ref()
     }
 
+    /// Set the load policy. It applies to the objects loaded
+    /// after.
+    pub fn set_load_policy(&mut self, policy: LoadPolicy) {
+        self.load_policy = policy;
+    }
+    /// Get the load policy.
+    pub fn load_policy(&self) -> LoadPolicy {
+        self.load_policy
+    }
+
     /// Get the main database from the library.
     pub fn database(&self) -> &Option<rusqlite::Connection> {
         self.database_conn.get_or_init(|| {
-            let dbpath = self.path.join("Database/apdb/Library.apdb");
-            let connection = rusqlite::Connection::open(dbpath);
-            connection.ok()
+            let dbpath = self.build_path(LIBRARY_DB, true);
+            sqlutils::open_database(dbpath).ok()
+        })
+    }
+
+    /// Get the properties database from the library. It contains
+    /// the EXIF and IPTC properties.
+    pub fn properties_database(&self) -> &Option<rusqlite::Connection> {
+        self.properties_conn.get_or_init(|| {
+            let dbpath = self.build_path(PROPERTIES_DB, true);
+            sqlutils::open_database(dbpath).ok()
+        })
+    }
+
+    /// Get the faces database from the library.
+    pub fn faces_database(&self) -> &Option<rusqlite::Connection> {
+        self.faces_conn.get_or_init(|| {
+            let dbpath = self.build_path(FACES_DB, true);
+            sqlutils::open_database(dbpath).ok()
         })
     }
 
@@ -178,10 +295,10 @@ impl Library {
 
     /// Get the library version. Will parse the plist for that
     /// if needed.
-    pub fn library_version(&mut self) -> Result<&String, SkipReason> {
+    pub fn library_version(&mut self) -> Result<&String> {
         if self.version.is_empty() {
             let plist_path = self.build_path(INFO_PLIST, false);
-            let plist = plutils::parse_plist(&plist_path);
+            let plist = plutils::parse_plist(&plist_path)?;
             let audit = self.auditor.is_some();
             let mut report = if audit { Some(Report::new()) } else { None };
 
@@ -191,32 +308,23 @@ impl Library {
                         dict,
                         "CFBundleShortVersionString",
                         &mut report.as_mut(),
-                    );
-                    if version.is_none() {
-                        println!("FATAL no library version found");
-                        return Err(SkipReason::NotFound);
-                    }
-                    self.version = version.unwrap();
+                    )
+                    .ok_or_else(|| {
+                        Error::MissingProperty(plist_path.clone(), "CFBundleShortVersionString")
+                    })?;
 
                     let bundle_id =
-                        audit_get_str_value(dict, "CFBundleIdentifier", &mut report.as_mut());
-                    if let Some(id) = bundle_id {
-                        if id != BUNDLE_IDENTIFIER {
-                            if audit {
-                                if let Some(ref mut r) = report {
-                                    r.skip("CFBundleIdentifier", SkipReason::InvalidData);
-                                }
-                            }
-                            println!("FATAL not a library");
-                            return Err(SkipReason::InvalidData);
-                        }
-                    } else if audit {
+                        audit_get_str_value(dict, "CFBundleIdentifier", &mut report.as_mut())
+                            .ok_or_else(|| {
+                                Error::MissingProperty(plist_path.clone(), "CFBundleIdentifier")
+                            })?;
+                    if bundle_id != BUNDLE_IDENTIFIER {
                         if let Some(ref mut r) = report {
-                            r.skip("CFBundleIdentifier", SkipReason::NotFound);
+                            r.skip("CFBundleIdentifier", SkipReason::InvalidData);
                         }
-                        println!("FATAL no bundle identifier");
-                        return Err(SkipReason::NotFound);
+                        return Err(Error::WrongBundle(plist_path, bundle_id));
                     }
+                    self.version = version;
 
                     if audit {
                         if let Some(ref mut r) = report {
@@ -235,6 +343,7 @@ impl Library {
                             .unwrap()
                             .skip(&plist_path.to_string_lossy(), SkipReason::InvalidType);
                     }
+                    return Err(Error::InvalidData(plist_path));
                 }
             }
         }
@@ -253,52 +362,58 @@ impl Library {
         ppath
     }
 
+    /// Read all the entries of directory at `path`.
+    fn read_dir(path: &Path) -> Result<Vec<fs::DirEntry>> {
+        fs::read_dir(path)
+            .and_then(|entries| entries.collect())
+            .map_err(|err| Error::Io(path.to_path_buf(), err))
+    }
+
+    /// Return true if `path` has the extension `ext`.
+    fn has_extension(path: &Path, ext: &str) -> bool {
+        path.extension().is_some_and(|e| e == ext)
+    }
+
     /// list items in dir with extension ext.
-    /// Return a vector with full path for each.
-    fn list_items(&self, dir: &str, ext: &str) -> Vec<PathBuf> {
+    /// Return a vector with full path for each. A missing directory
+    /// is an empty list.
+    fn list_items(&self, dir: &str, ext: &str) -> Result<Vec<PathBuf>> {
         let ppath = self.build_path(dir, true);
-        let mut list: Vec<PathBuf> = Vec::new();
-
-        if !fs::metadata(&ppath).unwrap().is_dir() {
-            // XXX return a Result
-            return list;
-        }
 
-        for entry in fs::read_dir(&ppath).unwrap() {
-            let p = entry.unwrap().path();
-            if p.extension().unwrap() == ext {
-                list.push(p.to_owned());
-            }
+        if !ppath.is_dir() {
+            return Ok(Vec::new());
         }
 
-        list
+        Ok(Library::read_dir(&ppath)?
+            .iter()
+            .map(|entry| entry.path())
+            .filter(|p| Library::has_extension(p, ext))
+            .collect())
     }
 
     /// Return the model info block
-    pub fn get_model_info(&self) -> Option<ModelInfo> {
+    pub fn get_model_info(&self) -> Result<ModelInfo> {
         let ppath = self.build_path(DATAMODEL_VERSION_PLIST, true);
-        let plist = plutils::parse_plist(ppath);
+        let plist = plutils::parse_plist(&ppath)?;
 
-        ModelInfo::parse(&plist)
+        ModelInfo::parse(&plist).ok_or(Error::InvalidData(ppath))
     }
 
-    /// Load items from directory `dir` with extension `ext`
+    /// Load the objects from the plist files in `file_list`
     /// and store the uuids into `set`
-    fn load_items<T, F>(
+    fn load_plist_files<T, F>(
         &mut self,
-        dir: &str,
-        ext: &str,
+        file_list: Vec<PathBuf>,
         set: &mut HashSet<String>,
         mut pg: Option<F>,
     ) where
         T: PlistLoadable + AplibObject,
         F: FnMut(u64) -> bool,
     {
-        let file_list = self.list_items(dir, ext);
         let audit = self.auditor.is_some();
         for file in file_list {
             let mut report = if audit { Some(Report::new()) } else { None };
-            if let Some(obj) = T::from_path(&file, report.as_mut()) {
+            if let Ok(obj) = T::from_path(&file, report.as_mut()) {
                 let mut store = false;
                 if let Some(ref uuid) = *obj.uuid() {
                     set.insert(uuid.to_owned());
@@ -313,31 +428,87 @@ impl Library {
                 if store {
                     self.store(T::wrap(obj));
                 }
-            } else {
-                if audit {
-                    self.auditor
-                        .as_mut()
-                        .unwrap()
-                        .skip(&file.to_string_lossy(), SkipReason::ParseFailed);
-                }
-                println!("Failed to decode object from {:?}", file);
+            } else if audit {
+                self.auditor
+                    .as_mut()
+                    .unwrap()
+                    .skip(&file.to_string_lossy(), SkipReason::ParseFailed);
             }
             if let Some(pg) = pg.as_mut() {
                 if !pg(1) {
-                    println!("Cancelled");
-                    return;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Return the plist files to load according to the load policy,
+    /// or `None` if the objects are to be loaded from the database.
+    /// `list_files` list the plist files. `Error::MissingDatabase` if
+    /// the policy requires the database and there is none.
+    fn plist_files<L>(&self, list_files: L) -> Result<Option<Vec<PathBuf>>>
+    where
+        L: FnOnce(&Library) -> Result<Vec<PathBuf>>,
+    {
+        match self.load_policy {
+            LoadPolicy::PlistFirst => {
+                let file_list = list_files(self)?;
+                Ok(if file_list.is_empty() {
+                    None
+                } else {
+                    Some(file_list)
+                })
+            }
+            LoadPolicy::DatabaseFirst => {
+                if self.database().is_some() {
+                    Ok(None)
+                } else {
+                    list_files(self).map(Some)
+                }
+            }
+            LoadPolicy::DatabaseOnly => {
+                if self.database().is_some() {
+                    Ok(None)
+                } else {
+                    Err(Error::MissingDatabase(self.build_path(LIBRARY_DB, true)))
                 }
             }
         }
     }
 
     /// Load albums. Once done the result it cached.
-    pub fn load_albums<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) {
+    /// When loaded from the database, the content of user albums
+    /// comes from `RKAlbumVersion`.
+    pub fn load_albums<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
         if self.albums.is_empty() {
             let mut albums: HashSet<String> = HashSet::new();
-            self.load_items::<Album, F>(ALBUMS_DIR, "apalbum", &mut albums, pg);
+            match self.plist_files(|library| library.list_items(ALBUMS_DIR, "apalbum"))? {
+                Some(file_list) => self.load_plist_files::<Album, F>(file_list, &mut albums, pg),
+                None => {
+                    let mut objects = self.query_db_objects::<Album>()?;
+                    self.load_albums_content(&mut objects)?;
+                    self.store_objects(objects, &mut albums, pg);
+                }
+            }
             self.albums = albums;
         }
+        Ok(())
+    }
+
+    /// Fill the content of user `albums` loaded from the database.
+    fn load_albums_content(&self, albums: &mut [Album]) -> Result<()> {
+        if let Some(conn) = self.database() {
+            let mut content = Album::content_from_db(conn)?;
+            for album in albums {
+                if album.subclass != Some(AlbumSubclass::User) {
+                    continue;
+                }
+                if let Some(uuid) = album.uuid() {
+                    album.content = Some(content.remove(uuid).unwrap_or_default());
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Get albums uuids.
@@ -346,12 +517,17 @@ impl Library {
     }
 
     /// Load folders. Once done the result is cached.
-    pub fn load_folders<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) {
+    pub fn load_folders<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
         if self.folders.is_empty() {
             let mut folders: HashSet<String> = HashSet::new();
-            self.load_items::<Folder, F>(FOLDERS_DIR, "apfolder", &mut folders, pg);
+            self.load_plist_or_db_items::<Folder, F, _>(
+                |library| library.list_items(FOLDERS_DIR, "apfolder"),
+                &mut folders,
+                pg,
+            )?;
             self.folders = folders;
         }
+        Ok(())
     }
 
     /// Get folders uuids.
@@ -359,194 +535,535 @@ impl Library {
         &self.folders
     }
 
-    fn recurse_list_directory(path: &Path, level: i32) -> Vec<PathBuf> {
+    fn recurse_list_directory(path: &Path, level: i32) -> Result<Vec<PathBuf>> {
         let mut list: Vec<PathBuf> = Vec::new();
-        for entry in fs::read_dir(path).unwrap() {
-            let entry = entry.unwrap();
-            if entry.metadata().unwrap().is_dir() {
+        for entry in Library::read_dir(path)? {
+            let metadata = entry
+                .metadata()
+                .map_err(|err| Error::Io(entry.path(), err))?;
+            if metadata.is_dir() {
                 if level == 0 {
                     list.push(entry.path());
                 } else {
-                    let mut sublist = Library::recurse_list_directory(&entry.path(), level - 1);
+                    let mut sublist = Library::recurse_list_directory(&entry.path(), level - 1)?;
                     list.append(&mut sublist)
                 }
             }
         }
 
-        list
+        Ok(list)
     }
 
-    fn list_items_dirs(&self, dir: &str) -> Vec<PathBuf> {
+    fn list_items_dirs(&self, dir: &str) -> Result<Vec<PathBuf>> {
         let ppath = self.build_path(dir, true);
 
-        if !fs::metadata(&ppath).unwrap().is_dir() {
-            // XXX return a Result
-            return Vec::new();
+        if !ppath.is_dir() {
+            return Ok(Vec::new());
         }
 
         Library::recurse_list_directory(&ppath, 4)
     }
 
     // XXX shall this a list_items() be merged?
-    fn list_recursive_items(&self, dir: &str, ext: &str) -> Vec<PathBuf> {
-        let list = self.list_items_dirs(dir);
+    fn list_recursive_items(&self, dir: &str, ext: &str) -> Result<Vec<PathBuf>> {
+        let list = self.list_items_dirs(dir)?;
         let mut items = Vec::new();
 
         for dir in list {
-            if !fs::metadata(&dir).unwrap().is_dir() {
-                continue;
-            }
-
-            for entry in fs::read_dir(&dir).unwrap() {
-                let entry = entry.unwrap();
+            for entry in Library::read_dir(&dir)? {
                 let p = entry.path();
-                if p.extension().unwrap() == ext {
-                    items.push(entry.path().to_owned());
+                if Library::has_extension(&p, ext) {
+                    items.push(p);
                 }
             }
         }
 
-        items
+        Ok(items)
     }
 
-    fn load_volumes_items<T, F>(&mut self, ext: &str, set: &mut HashSet<String>, mut pg: Option<F>)
+    /// Load all the objects of type `T` from the database.
+    /// Without a database there is no object.
+    fn query_db_objects<T>(&mut self) -> Result<Vec<T>>
     where
-        T: PlistLoadable + SqliteLoadable + AplibObject,
-        F: FnMut(u64) -> bool,
+        T: SqliteLoadable + AplibObject,
     {
         use rusqlite::params;
 
-        let file_list = self.list_items(VOLUMES_DIR, ext);
-        if file_list.is_empty() {
-            // open the database and load from there.
-            let mut objects = Vec::new();
-            if let Some(conn) = self.database() {
-                let query = format!("SELECT {} FROM {}", T::columns(), T::tables());
-                if let Ok(mut stmt) = conn.prepare(&query) {
-                    if let Ok(volumes) = stmt.query_and_then(params![], |row| T::from_row(row)) {
-                        volumes
-                            .into_iter()
-                            .filter(|vol| vol.is_ok())
-                            .for_each(|vol| {
-                                let vol = vol.unwrap();
-                                if let Some(uuid) = vol.uuid() {
-                                    set.insert(uuid.clone());
-                                    objects.push(vol);
-                                }
-                            });
+        let mut rows = Vec::new();
+        if let Some(conn) = self.database() {
+            let query = format!("SELECT {} FROM {}", T::columns(), T::tables());
+            let mut stmt = conn.prepare(&query)?;
+            rows = stmt
+                .query_and_then(params![], |row| T::from_row(row))?
+                .collect();
+        }
+
+        let mut objects = Vec::new();
+        for (idx, row) in rows.into_iter().enumerate() {
+            match row {
+                Ok(obj) if obj.uuid().is_some() => objects.push(obj),
+                _ => {
+                    if let Some(auditor) = self.auditor.as_mut() {
+                        auditor.skip(
+                            &format!("{}[{}]", T::tables(), idx),
+                            SkipReason::ParseFailed,
+                        );
                     }
                 }
             }
+        }
 
-            objects.into_iter().for_each(|vol| {
-                self.store(T::wrap(vol));
-            });
+        Ok(objects)
+    }
 
-            return;
-        }
-        let audit = self.auditor.is_some();
-        for file in file_list {
-            let mut report = if audit { Some(Report::new()) } else { None };
-            if let Some(obj) = T::from_path(&file, report.as_mut()) {
-                let mut store = false;
-                if let Some(ref uuid) = *obj.uuid() {
-                    set.insert(uuid.to_owned());
-                    store = true;
-                    if audit {
-                        self.auditor
-                            .as_mut()
-                            .unwrap()
-                            .parsed(&file.to_string_lossy(), report.unwrap());
-                    }
-                }
-                if store {
-                    self.store(T::wrap(obj));
-                }
-            } else {
-                if audit {
-                    self.auditor
-                        .as_mut()
-                        .unwrap()
-                        .skip(&file.to_string_lossy(), SkipReason::ParseFailed);
-                }
-                println!("Error decoding object from {:?}", file);
+    /// Store `objects` and their uuids into `set`
+    fn store_objects<T, F>(&mut self, objects: Vec<T>, set: &mut HashSet<String>, mut pg: Option<F>)
+    where
+        T: AplibObject,
+        F: FnMut(u64) -> bool,
+    {
+        for obj in objects {
+            if let Some(ref uuid) = *obj.uuid() {
+                set.insert(uuid.to_owned());
+                self.store(T::wrap(obj));
             }
             if let Some(pg) = pg.as_mut() {
                 if !pg(1) {
-                    println!("Cancelled!");
                     break;
                 }
             }
         }
     }
 
-    fn load_versions_items<T, F>(&mut self, ext: &str, set: &mut HashSet<String>, mut pg: Option<F>)
+    /// Load the objects from the plist files listed by `list_files`,
+    /// or from the database, according to the load policy.
+    /// and store the uuids into `set`
+    fn load_plist_or_db_items<T, F, L>(
+        &mut self,
+        list_files: L,
+        set: &mut HashSet<String>,
+        pg: Option<F>,
+    ) -> Result<()>
     where
-        T: PlistLoadable + AplibObject,
+        T: PlistLoadable + SqliteLoadable + AplibObject,
         F: FnMut(u64) -> bool,
+        L: FnOnce(&Library) -> Result<Vec<PathBuf>>,
     {
-        let file_list = self.list_recursive_items(VERSIONS_BASE_DIR, ext);
-        let audit = self.auditor.is_some();
-        for file in file_list {
-            let mut report = if audit { Some(Report::new()) } else { None };
-            if let Some(obj) = T::from_path(&file, report.as_mut()) {
-                let mut store = false;
-                if let Some(ref uuid) = *obj.uuid() {
-                    set.insert(uuid.to_owned());
-                    store = true;
-                    if audit {
-                        self.auditor
-                            .as_mut()
-                            .unwrap()
-                            .parsed(&file.to_string_lossy(), report.unwrap());
-                    }
-                }
-                if store {
-                    self.store(T::wrap(obj));
-                }
-            } else {
-                if audit {
-                    self.auditor
-                        .as_mut()
-                        .unwrap()
-                        .skip(&file.to_string_lossy(), SkipReason::ParseFailed);
-                }
-                println!("Error decoding object from {:?}", file);
+        match self.plist_files(list_files)? {
+            Some(file_list) => self.load_plist_files::<T, F>(file_list, set, pg),
+            None => {
+                let objects = self.query_db_objects::<T>()?;
+                self.store_objects(objects, set, pg);
             }
-            if let Some(pg) = pg.as_mut() {
-                if !pg(1) {
-                    println!("Cancelled!");
-                    break;
-                }
+        }
+        Ok(())
+    }
+
+    /// Fill the EXIF and IPTC properties of `versions` loaded from
+    /// the database.
+    fn load_versions_properties(&self, versions: &mut [Version]) -> Result<()> {
+        if let Some(conn) = self.properties_database() {
+            let mut exif = ExifProperties::from_db(conn)?;
+            let mut iptc = IptcProperties::from_db(conn)?;
+            for version in versions {
+                let model_id = version.model_id();
+                version.exif = exif.remove(&model_id);
+                version.iptc = iptc.remove(&model_id);
             }
         }
+        Ok(())
+    }
+
+    /// Fill the keywords of `versions` loaded from the database.
+    fn load_versions_keywords(&mut self, versions: &mut [Version]) -> Result<()> {
+        // Otherwise the assignments come from the version plists.
+        let has_keywords = match self.database() {
+            Some(conn) => sqlutils::has_table(conn, "RKKeywordForVersion")?,
+            None => false,
+        };
+        if !has_keywords {
+            return Ok(());
+        }
+        self.load_keyword_assignments()?;
+        let assignments = self.keyword_assignments.as_ref();
+        for version in versions {
+            version.assigned_keywords = version
+                .uuid()
+                .as_ref()
+                .and_then(|uuid| assignments?.by_version.get(uuid))
+                .map(|assigned| {
+                    assigned
+                        .iter()
+                        .map(|assignment| assignment.path.clone())
+                        .collect()
+                });
+        }
+        Ok(())
     }
 
     /// Load volumess.
-    pub fn load_volumes<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) {
+    pub fn load_volumes<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
         if self.volumes.is_empty() {
             let mut volumes: HashSet<String> = HashSet::new();
-            self.load_volumes_items::<Volume, F>("apvolume", &mut volumes, pg);
+            self.load_plist_or_db_items::<Volume, F, _>(
+                |library| library.list_items(VOLUMES_DIR, "apvolume"),
+                &mut volumes,
+                pg,
+            )?;
             self.volumes = volumes;
         }
+        Ok(())
     }
 
     /// Load versions.
-    pub fn load_versions<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) {
+    pub fn load_versions<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
         if self.versions.is_empty() {
             let mut versions: HashSet<String> = HashSet::new();
-            self.load_versions_items::<Version, F>("apversion", &mut versions, pg);
+            match self.plist_files(|library| {
+                library.list_recursive_items(VERSIONS_BASE_DIR, "apversion")
+            })? {
+                Some(file_list) => {
+                    self.load_plist_files::<Version, F>(file_list, &mut versions, pg)
+                }
+                None => {
+                    let mut objects = self.query_db_objects::<Version>()?;
+                    self.load_versions_properties(&mut objects)?;
+                    self.load_versions_keywords(&mut objects)?;
+                    self.store_objects(objects, &mut versions, pg);
+                }
+            }
             self.versions = versions;
+            if !self.places.is_empty() {
+                self.link_places()?;
+            }
+            if self.faces.is_some() {
+                self.link_faces();
+            }
+            if self.stacks.is_some() {
+                self.link_stacks();
+            }
         }
+        Ok(())
     }
 
     /// Load masters.
-    pub fn load_masters<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) {
+    pub fn load_masters<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
         if self.masters.is_empty() {
             let mut masters: HashSet<String> = HashSet::new();
-            self.load_versions_items::<Master, F>("apmaster", &mut masters, pg);
+            self.load_plist_or_db_items::<Master, F, _>(
+                |library| library.list_recursive_items(VERSIONS_BASE_DIR, "apmaster"),
+                &mut masters,
+                pg,
+            )?;
             self.masters = masters;
         }
+        Ok(())
+    }
+
+    /// Load places. Once done the result is cached. The loaded
+    /// versions are linked to their places, and the versions loaded
+    /// after.
+    pub fn load_places<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
+        if self.places.is_empty() {
+            let mut places: HashSet<String> = HashSet::new();
+            match self.plist_files(|library| library.list_items(PLACES_DIR, "applace"))? {
+                Some(file_list) => self.load_plist_files::<Place, F>(file_list, &mut places, pg),
+                None => {
+                    // Older libraries don't have places.
+                    let has_places = match self.database() {
+                        Some(conn) => sqlutils::has_table(conn, "RKPlace")?,
+                        None => false,
+                    };
+                    if has_places {
+                        let objects = self.query_db_objects::<Place>()?;
+                        self.store_objects(objects, &mut places, pg);
+                    }
+                }
+            }
+            self.places = places;
+            self.link_places()?;
+        }
+        Ok(())
+    }
+
+    /// Return places uuids.
+    pub fn places(&self) -> &HashSet<String> {
+        &self.places
+    }
+
+    fn get_place(&self, uuid: &str) -> Option<&Place> {
+        match self.get(uuid) {
+            Some(store::Wrapper::Place(place)) => Some(place),
+            _ => None,
+        }
+    }
+
+    /// Return the hierarchy of the place `uuid`, from the root to the
+    /// place. Empty if the place isn't loaded.
+    pub fn place_path(&self, uuid: &str) -> Vec<&Place> {
+        let mut path = vec![];
+        let mut current = self.get_place(uuid);
+        while let Some(place) = current {
+            // The length check prevents looping on a cycle.
+            if path.len() > self.places.len() {
+                break;
+            }
+            path.push(place);
+            current = place
+                .parent()
+                .as_ref()
+                .and_then(|parent| self.get_place(parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// Return the places the version `uuid` is in.
+    pub fn places_for_version(&self, uuid: &str) -> Vec<&Place> {
+        self.version_places
+            .get(uuid)
+            .map(|places| {
+                places
+                    .iter()
+                    .filter_map(|place| self.get_place(place))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Link the loaded versions to the loaded places, from
+    /// `RKPlaceForVersion` if the database has it, by their GPS
+    /// location otherwise. The versions get the paths of their places.
+    fn link_places(&mut self) -> Result<()> {
+        let from_db = match self.database() {
+            Some(conn) => sqlutils::has_table(conn, "RKPlaceForVersion")?,
+            None => false,
+        };
+        let mut links: HashMap<String, Vec<String>> = HashMap::new();
+        if from_db {
+            if let Some(conn) = self.database() {
+                for (version_uuid, place_uuid) in Place::pairs_from_db(conn)? {
+                    links.entry(version_uuid).or_default().push(place_uuid);
+                }
+            }
+        } else {
+            for uuid in &self.versions {
+                let location = match self.get(uuid) {
+                    Some(store::Wrapper::Version(version)) => {
+                        version.exif.as_ref().and_then(|exif| {
+                            let coordinate = |key| match exif.bag.get(key) {
+                                Some(ExifValue::Real(value)) => Some(*value),
+                                Some(ExifValue::Int(value)) => Some(*value as f64),
+                                _ => None,
+                            };
+                            coordinate("Latitude").zip(coordinate("Longitude"))
+                        })
+                    }
+                    _ => None,
+                };
+                if let Some((latitude, longitude)) = location {
+                    let places: Vec<String> = self
+                        .places
+                        .iter()
+                        .filter(|place| {
+                            self.get_place(place)
+                                .is_some_and(|place| place.contains(latitude, longitude))
+                        })
+                        .cloned()
+                        .collect();
+                    if !places.is_empty() {
+                        links.insert(uuid.clone(), places);
+                    }
+                }
+            }
+        }
+
+        // Only the innermost places, their path has the others.
+        let mut paths: HashMap<String, Vec<Vec<String>>> = HashMap::new();
+        for (version_uuid, places) in links.iter_mut() {
+            places.sort();
+            let place_paths: Vec<Vec<&Place>> =
+                places.iter().map(|place| self.place_path(place)).collect();
+            let ancestors: HashSet<&String> = place_paths
+                .iter()
+                .flat_map(|path| path[..path.len().saturating_sub(1)].iter())
+                .filter_map(|place| place.uuid().as_ref())
+                .collect();
+            let mut version_paths: Vec<Vec<String>> = places
+                .iter()
+                .zip(place_paths)
+                .filter(|(place, path)| !path.is_empty() && !ancestors.contains(place))
+                .map(|(_, path)| {
+                    path.iter()
+                        .map(|place| place.name.clone().unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            version_paths.sort();
+            paths.insert(version_uuid.clone(), version_paths);
+        }
+        for uuid in &self.versions {
+            if let Some(store::Wrapper::Version(version)) = self.objects.get_mut(uuid) {
+                version.places = paths.remove(uuid);
+            }
+        }
+        self.version_places = links;
+        Ok(())
+    }
+
+    /// Parse the plist files in `file_list`, auditing them.
+    fn parse_audited_plists<T, F>(&mut self, file_list: Vec<PathBuf>, pg: &mut Option<F>) -> Vec<T>
+    where
+        T: PlistLoadable,
+        F: FnMut(u64) -> bool,
+    {
+        let mut objects = vec![];
+        for file in file_list {
+            let mut report = self.auditor.as_ref().map(|_| Report::new());
+            match T::from_path(&file, report.as_mut()) {
+                Ok(obj) => {
+                    if let (Some(auditor), Some(report)) = (self.auditor.as_mut(), report) {
+                        auditor.parsed(&file.to_string_lossy(), report);
+                    }
+                    objects.push(obj);
+                }
+                Err(_) => {
+                    if let Some(auditor) = self.auditor.as_mut() {
+                        auditor.skip(&file.to_string_lossy(), SkipReason::ParseFailed);
+                    }
+                }
+            }
+            if let Some(pg) = pg.as_mut() {
+                if !pg(1) {
+                    break;
+                }
+            }
+        }
+        objects
+    }
+
+    /// Load the faces, from `Faces.db` or from the plists in
+    /// `Database/Faces`, according to the load policy. Once done the
+    /// result is cached. The loaded versions get the regions of the
+    /// faces, and the versions loaded later.
+    pub fn load_faces<F: FnMut(u64) -> bool>(&mut self, mut pg: Option<F>) -> Result<()> {
+        if self.faces.is_some() {
+            return Ok(());
+        }
+        let use_db = match self.load_policy {
+            LoadPolicy::PlistFirst => self
+                .list_items(DETECTED_FACES_DIR, "apdetected")?
+                .is_empty(),
+            LoadPolicy::DatabaseFirst => self.faces_database().is_some(),
+            LoadPolicy::DatabaseOnly => true,
+        };
+        let faces = match self.faces_database() {
+            Some(conn) if use_db => {
+                // Libraries that never ran face detection have no faces.
+                if sqlutils::has_table(conn, FaceName::tables())? {
+                    Faces::from_db(conn)?
+                } else {
+                    Faces::default()
+                }
+            }
+            _ if use_db => Faces::default(),
+            _ => {
+                let names = self.list_items(FACE_NAMES_DIR, "apfacename")?;
+                let names = self.parse_audited_plists::<FaceName, F>(names, &mut pg);
+                let detected = self.list_items(DETECTED_FACES_DIR, "apdetected")?;
+                let detected = self.parse_audited_plists::<DetectedFace, F>(detected, &mut pg);
+                Faces::new(names, detected)
+            }
+        };
+        self.faces = Some(faces);
+        self.link_faces();
+        Ok(())
+    }
+
+    /// Give the loaded versions the regions of the faces in their
+    /// master.
+    fn link_faces(&mut self) {
+        let faces = match self.faces.as_ref() {
+            Some(faces) => faces,
+            None => return,
+        };
+        for uuid in &self.versions {
+            if let Some(store::Wrapper::Version(version)) = self.objects.get_mut(uuid) {
+                let regions = version
+                    .master_uuid
+                    .as_deref()
+                    .map(|master_uuid| faces.regions_for_master(master_uuid))
+                    .unwrap_or_default();
+                version.faces = if regions.is_empty() {
+                    None
+                } else {
+                    Some(regions)
+                };
+            }
+        }
+    }
+
+    /// Load the stacks from `RKStackedVersion`. They are only in the
+    /// database. Once done the result is cached. The loaded versions
+    /// are linked to their stack, and the versions loaded later.
+    pub fn load_stacks(&mut self) -> Result<()> {
+        if self.stacks.is_some() {
+            return Ok(());
+        }
+        let stacks = match self.database() {
+            Some(conn) if sqlutils::has_table(conn, "RKStackedVersion")? => Stack::from_db(conn)?,
+            _ => vec![],
+        };
+        self.stacks = Some(stacks);
+        self.link_stacks();
+        Ok(())
+    }
+
+    /// Return the stacks, sorted by uuid. Empty until loaded.
+    pub fn stacks(&self) -> &[Stack] {
+        self.stacks.as_deref().unwrap_or_default()
+    }
+
+    /// Return the stack the version `uuid` is in.
+    pub fn stack_of(&self, uuid: &str) -> Option<&Stack> {
+        self.stacks()
+            .iter()
+            .find(|stack| stack.versions.iter().any(|version| version == uuid))
+    }
+
+    /// Link the loaded versions to their stack.
+    fn link_stacks(&mut self) {
+        let mut links: HashMap<&str, (&str, bool)> = HashMap::new();
+        for stack in self.stacks.iter().flatten() {
+            for version in &stack.versions {
+                links.insert(version, (&stack.uuid, stack.is_pick(version)));
+            }
+        }
+        for uuid in &self.versions {
+            if let Some(store::Wrapper::Version(version)) = self.objects.get_mut(uuid) {
+                let link = links.get(uuid.as_str());
+                version.stack_uuid = link.map(|(stack, _)| stack.to_string());
+                version.is_stack_pick = link.map(|(_, is_pick)| *is_pick);
+            }
+        }
+    }
+
+    /// Return the faces, if loaded.
+    pub fn faces(&self) -> Option<&Faces> {
+        self.faces.as_ref()
+    }
+
+    /// Return the faces detected in the version `uuid`, the ones of
+    /// its master. The faces and the version must be loaded.
+    pub fn faces_for_version(&self, uuid: &str) -> Vec<&DetectedFace> {
+        let master_uuid = match self.get(uuid) {
+            Some(store::Wrapper::Version(version)) => version.master_uuid.as_deref(),
+            _ => None,
+        };
+        match (self.faces.as_ref(), master_uuid) {
+            (Some(faces), Some(master_uuid)) => faces.for_master(master_uuid),
+            _ => vec![],
+        }
     }
 
     /// Return masters uuids.
@@ -564,38 +1081,51 @@ impl Library {
         &self.volumes
     }
 
-    /// Resolve the path of a master to it's macOS on disk location
-    /// either to an existing volume or relative to the library.
-    pub fn resolve_master_path(&self, uuid: &str) -> Option<String> {
-        match self.get(uuid) {
-            Some(crate::StoreWrapper::Master(master)) => {
-                let image_path = master.image_path.as_ref().unwrap();
-                if let Some(volume_uuid) = master.file_volume_uuid.as_ref() {
-                    self.get(volume_uuid).and_then(|object| {
-                        if let crate::StoreWrapper::Volume(volume) = object {
-                            Some(format!(
-                                "/Volumes/{}/{image_path}",
-                                volume.volume_name.clone().unwrap_or_else(String::default)
-                            ))
-                        } else {
-                            None
-                        }
-                    })
-                } else {
-                    Some(format!("Masters/{image_path}"))
-                }
-            }
-            _ => None,
-        }
+    /// Return the path resolver for the masters of this library. The
+    /// referenced masters are in `/Volumes` until mapped.
+    pub fn path_resolver(&self) -> PathResolver {
+        self.resolver.clone()
+    }
+
+    /// Set the path resolver used for the exports.
+    pub fn set_path_resolver(&mut self, resolver: PathResolver) {
+        self.resolver = resolver;
+    }
+
+    /// Resolve the file of the master `uuid` with the path resolver.
+    /// The master, and its volume if referenced, must be loaded.
+    pub fn resolve_master_file(&self, uuid: &str) -> Option<ResolvedPath> {
+        let master = match self.get(uuid) {
+            Some(store::Wrapper::Master(master)) => master,
+            _ => return None,
+        };
+        let volume = match master.file_volume_uuid.as_ref() {
+            Some(volume_uuid) => match self.get(volume_uuid) {
+                Some(store::Wrapper::Volume(volume)) => Some(volume.as_ref()),
+                _ => return None,
+            },
+            None => None,
+        };
+        self.resolver.resolve(master, volume)
+    }
+
+    /// The path of the master file `resolved` mirrored by the exports:
+    /// relative to the library for the files inside, like the managed
+    /// masters.
+    fn mirrored_path<'a>(&self, resolved: &'a ResolvedPath) -> &'a Path {
+        resolved
+            .path
+            .strip_prefix(self.resolver.library_root())
+            .unwrap_or(&resolved.path)
     }
 
     /// List keywords.
-    pub fn list_keywords(&mut self) -> Option<Vec<Keyword>> {
+    pub fn list_keywords(&mut self) -> Result<Vec<Keyword>> {
         let audit = self.auditor.is_some();
         let mut report = if audit { Some(Report::new()) } else { None };
         let result = parse_keywords(self.build_path(KEYWORDS_PLIST, true), &mut report.as_mut());
         if audit {
-            if result.is_some() {
+            if result.is_ok() {
                 self.auditor
                     .as_mut()
                     .unwrap()
@@ -609,4 +1139,813 @@ impl Library {
         }
         result
     }
+
+    /// Load keywords. Once done the result is cached. The keywords
+    /// are stored flat, see `Library::keyword_children()` for the tree.
+    pub fn load_keywords<F: FnMut(u64) -> bool>(&mut self, pg: Option<F>) -> Result<()> {
+        if self.keywords.is_empty() {
+            let mut keywords: HashSet<String> = HashSet::new();
+            let objects = match self.plist_files(|library| {
+                let path = library.build_path(KEYWORDS_PLIST, true);
+                Ok(if path.exists() { vec![path] } else { vec![] })
+            })? {
+                Some(_) => Keyword::flatten(self.list_keywords()?),
+                None => self.query_db_objects::<Keyword>()?,
+            };
+            self.store_objects(objects, &mut keywords, pg);
+            self.keywords = keywords;
+        }
+        Ok(())
+    }
+
+    /// Get keywords uuids.
+    pub fn keywords(&self) -> &HashSet<String> {
+        &self.keywords
+    }
+
+    /// Get the keyword `uuid` from the store.
+    fn get_keyword(&self, uuid: &str) -> Option<&Keyword> {
+        match self.get(uuid) {
+            Some(store::Wrapper::Keyword(keyword)) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    /// Sort `keywords` by name.
+    fn sort_keywords(mut keywords: Vec<&Keyword>) -> Vec<&Keyword> {
+        keywords.sort_by(|a, b| a.name.cmp(&b.name));
+        keywords
+    }
+
+    /// Return the top level keywords, sorted by name.
+    pub fn keyword_roots(&self) -> Vec<&Keyword> {
+        Library::sort_keywords(
+            self.keywords
+                .iter()
+                .filter_map(|uuid| self.get_keyword(uuid))
+                .filter(|keyword| {
+                    keyword
+                        .parent()
+                        .as_ref()
+                        .and_then(|parent| self.get_keyword(parent))
+                        .is_none()
+                })
+                .collect(),
+        )
+    }
+
+    /// Return the children of the keyword `uuid`, sorted by name.
+    pub fn keyword_children(&self, uuid: &str) -> Vec<&Keyword> {
+        Library::sort_keywords(
+            self.keywords
+                .iter()
+                .filter_map(|uuid| self.get_keyword(uuid))
+                .filter(|keyword| keyword.parent().as_deref() == Some(uuid))
+                .collect(),
+        )
+    }
+
+    /// Return the ancestors of the keyword `uuid`, parent first.
+    pub fn keyword_ancestors(&self, uuid: &str) -> Vec<&Keyword> {
+        let mut ancestors: Vec<&Keyword> = Vec::new();
+        let mut parent = self
+            .get_keyword(uuid)
+            .and_then(|keyword| keyword.parent().as_ref());
+        while let Some(keyword) = parent.and_then(|parent| self.get_keyword(parent)) {
+            // Don't loop on a cycle.
+            if ancestors.len() > self.keywords.len() {
+                break;
+            }
+            ancestors.push(keyword);
+            parent = keyword.parent().as_ref();
+        }
+        ancestors
+    }
+
+    /// Return the path of the keyword `uuid`, the names from the root
+    /// joined by `|` like Lightroom does.
+    pub fn keyword_path(&self, uuid: &str) -> Option<String> {
+        let keyword = self.get_keyword(uuid)?;
+        let mut names: Vec<&str> = self
+            .keyword_ancestors(uuid)
+            .iter()
+            .rev()
+            .map(|keyword| keyword.name.as_deref().unwrap_or_default())
+            .collect();
+        names.push(keyword.name.as_deref().unwrap_or_default());
+        Some(names.join("|"))
+    }
+
+    /// Load the keyword assignments, from `RKKeywordForVersion` if
+    /// the database has it, from the version plists otherwise.
+    /// Once done the result is cached. Needed before
+    /// `keywords_for_version()` and `versions_for_keyword()`.
+    pub fn load_keyword_assignments(&mut self) -> Result<()> {
+        if self.keyword_assignments.is_none() {
+            self.load_keywords(PROGRESS_NONE)?;
+            let paths = Keyword::paths(
+                self.keywords
+                    .iter()
+                    .filter_map(|uuid| self.get_keyword(uuid)),
+            );
+            let from_db = match self.database() {
+                Some(conn) => sqlutils::has_table(conn, "RKKeywordForVersion")?,
+                None => false,
+            };
+            let assignments = if from_db {
+                self.keyword_assignments_from_db(&paths)?
+            } else {
+                self.keyword_assignments_from_plists(&paths)?
+            };
+            self.keyword_assignments = Some(assignments);
+        }
+        Ok(())
+    }
+
+    /// The keyword uuids from the database that aren't in the keyword
+    /// `paths` are reported as not found.
+    fn keyword_assignments_from_db(
+        &mut self,
+        paths: &HashMap<String, Vec<String>>,
+    ) -> Result<KeywordAssignments> {
+        let mut assignments = KeywordAssignments::default();
+        let mut unresolved = Vec::new();
+        if let Some(conn) = self.database() {
+            for (version_uuid, keyword_uuid) in KeywordAssignments::pairs_from_db(conn)? {
+                if !assignments.assign(&version_uuid, &keyword_uuid, paths) {
+                    unresolved.push(format!("{}.keywords.{}", version_uuid, keyword_uuid));
+                }
+            }
+        }
+        if let Some(auditor) = self.auditor.as_mut() {
+            for key in unresolved {
+                auditor.skip(&key, SkipReason::NotFound);
+            }
+        }
+        Ok(assignments)
+    }
+
+    /// The version plists have the keyword paths that are resolved
+    /// with the keyword `paths`.
+    fn keyword_assignments_from_plists(
+        &mut self,
+        paths: &HashMap<String, Vec<String>>,
+    ) -> Result<KeywordAssignments> {
+        let by_path: HashMap<&Vec<String>, &String> =
+            paths.iter().map(|(uuid, path)| (path, uuid)).collect();
+        self.load_versions(PROGRESS_NONE)?;
+
+        let mut assignments = KeywordAssignments::default();
+        let mut unresolved = Vec::new();
+        for uuid in &self.versions {
+            if let Some(store::Wrapper::Version(version)) = self.objects.get(uuid) {
+                for (idx, path) in version.keyword_paths().iter().enumerate() {
+                    match by_path.get(path) {
+                        Some(keyword_uuid) => {
+                            assignments.assign(uuid, keyword_uuid, paths);
+                        }
+                        None => unresolved.push(format!("{}.keywords[{}]", uuid, idx)),
+                    }
+                }
+            }
+        }
+        if let Some(auditor) = self.auditor.as_mut() {
+            for key in unresolved {
+                auditor.skip(&key, SkipReason::NotFound);
+            }
+        }
+        Ok(assignments)
+    }
+
+    /// Return the keywords assigned to the version `uuid`.
+    /// Empty until `load_keyword_assignments()` is called.
+    pub fn keywords_for_version(&self, uuid: &str) -> &[KeywordAssignment] {
+        self.keyword_assignments
+            .as_ref()
+            .and_then(|assignments| assignments.by_version.get(uuid))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return the uuids of the versions the keyword `uuid` is
+    /// assigned to. Empty until `load_keyword_assignments()` is called.
+    pub fn versions_for_keyword(&self, uuid: &str) -> &[String] {
+        self.keyword_assignments
+            .as_ref()
+            .and_then(|assignments| assignments.by_keyword.get(uuid))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return the uuids of the loaded versions in the folder `uuid`
+    /// or in its subfolders, excluding the ones in the trash.
+    fn folder_versions(&self, uuid: &str) -> Vec<String> {
+        let mut folders = HashSet::new();
+        folders.insert(uuid.to_string());
+        for folder_uuid in &self.folders {
+            let mut current = Some(folder_uuid.clone());
+            let mut depth = 0;
+            // The depth check prevents looping on a cycle.
+            while let Some(parent) = current {
+                if parent == uuid {
+                    folders.insert(folder_uuid.clone());
+                    break;
+                }
+                depth += 1;
+                if depth > self.folders.len() {
+                    break;
+                }
+                current = self.get(&parent).and_then(store::Wrapper::parent_uuid);
+            }
+        }
+        self.versions
+            .iter()
+            .filter(|uuid| match self.get(uuid) {
+                Some(store::Wrapper::Version(version)) => {
+                    version.is_in_trash != Some(true)
+                        && version
+                            .project_uuid
+                            .as_ref()
+                            .map(|project| folders.contains(project))
+                            .unwrap_or(false)
+                }
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Resolve the content of the album `uuid` into version uuids,
+    /// sorted. User albums have their content, implicit albums the
+    /// versions of the folder they represent, and smart albums the
+    /// versions matching their query, in the query folder if any.
+    /// Only the loaded folders and versions are considered.
+    /// Return `None` if the album isn't loaded, and
+    /// `Error::UnsupportedQuery` if the query has a comparison that
+    /// isn't known.
+    pub fn resolve_album_content(&mut self, uuid: &str) -> Result<Option<Vec<String>>> {
+        let album = match self.get(uuid) {
+            Some(store::Wrapper::Album(album)) => album,
+            _ => return Ok(None),
+        };
+        let mut content = match album.subclass {
+            Some(AlbumSubclass::User) => album.content.clone().unwrap_or_default(),
+            Some(AlbumSubclass::Implicit) => {
+                // The folder links to its implicit album.
+                let folder = self
+                    .folders
+                    .iter()
+                    .find(|folder_uuid| match self.get(folder_uuid) {
+                        Some(store::Wrapper::Folder(folder)) => {
+                            folder.implicit_album_uuid.as_deref() == Some(uuid)
+                        }
+                        _ => false,
+                    })
+                    .cloned()
+                    .or_else(|| album.parent().clone());
+                folder
+                    .map(|folder| self.folder_versions(&folder))
+                    .unwrap_or_default()
+            }
+            Some(AlbumSubclass::Smart) => {
+                let query = album.query.clone();
+                let versions = match album.query_folder_uuid {
+                    Some(ref folder) => self.folder_versions(folder),
+                    None => self
+                        .versions
+                        .iter()
+                        .filter(|uuid| match self.get(uuid) {
+                            Some(store::Wrapper::Version(version)) => {
+                                version.is_in_trash != Some(true)
+                            }
+                            _ => false,
+                        })
+                        .cloned()
+                        .collect(),
+                };
+                match query {
+                    Some(query) if !query.is_evaluable() => {
+                        return Err(Error::UnsupportedQuery(uuid.to_string()))
+                    }
+                    Some(query) => self.query_versions(&query, versions)?,
+                    None => vec![],
+                }
+            }
+            _ => vec![],
+        };
+        content.sort();
+        Ok(Some(content))
+    }
+
+    /// Return the uuids in `versions` that match `query`.
+    fn query_versions(&mut self, query: &SmartQuery, versions: Vec<String>) -> Result<Vec<String>> {
+        if query.uses_keywords() {
+            self.load_keyword_assignments()?;
+        }
+        let assignments = self.keyword_assignments.as_ref();
+        Ok(versions
+            .into_iter()
+            .filter(|uuid| match self.get(uuid) {
+                Some(store::Wrapper::Version(version)) => {
+                    let keywords = assignments
+                        .and_then(|assignments| assignments.by_version.get(uuid))
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    query.matches(version, keywords)
+                }
+                _ => false,
+            })
+            .collect())
+    }
+
+    /// Return the versions in `selection`, sorted by uuid.
+    fn selected_versions(&self, selection: VersionSelection) -> Vec<&Version> {
+        let mut versions: Vec<&Version> = self
+            .versions
+            .iter()
+            .filter_map(|uuid| match self.get(uuid) {
+                Some(store::Wrapper::Version(version)) => Some(version.as_ref()),
+                _ => None,
+            })
+            .filter(|version| selection.selects(version))
+            .collect();
+        versions.sort_by(|a, b| a.uuid().cmp(b.uuid()));
+        versions
+    }
+
+    /// Return the RAW+JPEG pairs of the loaded masters, from the
+    /// `alternateMasterUuid` of the masters and the RAW and non-RAW
+    /// masters of the versions. The pairs are sorted by RAW master.
+    /// The pairs sharing a master are flagged as conflicting. The
+    /// masters and versions must be loaded.
+    pub fn master_pairs(&self) -> Vec<MasterPair> {
+        let master = |uuid: &str| match self.get(uuid) {
+            Some(store::Wrapper::Master(master)) => Some(master.as_ref()),
+            _ => None,
+        };
+        let versions: Vec<&Version> = self
+            .versions
+            .iter()
+            .filter_map(|uuid| match self.get(uuid) {
+                Some(store::Wrapper::Version(version)) => Some(version.as_ref()),
+                _ => None,
+            })
+            .collect();
+
+        let mut pairs: HashSet<(String, String)> = HashSet::new();
+        for uuid in &self.masters {
+            let alternate = master(uuid)
+                .and_then(|master| master.alternate_master.as_ref())
+                .and_then(|alternate| master(alternate).map(|master| (alternate, master)));
+            if let (Some(this), Some((alternate_uuid, alternate))) = (master(uuid), alternate) {
+                match (this.is_raw(), alternate.is_raw()) {
+                    (true, false) => pairs.insert((uuid.clone(), alternate_uuid.clone())),
+                    (false, true) => pairs.insert((alternate_uuid.clone(), uuid.clone())),
+                    // Not a RAW+JPEG pair.
+                    _ => false,
+                };
+            }
+        }
+        for version in &versions {
+            if let (Some(raw), Some(jpeg)) = (&version.raw_master_uuid, &version.nonraw_master_uuid)
+            {
+                if raw != jpeg && master(raw).is_some() && master(jpeg).is_some() {
+                    pairs.insert((raw.clone(), jpeg.clone()));
+                }
+            }
+        }
+
+        let points_to = |from: &str, to: &str| {
+            master(from)
+                .and_then(|master| master.alternate_master.as_deref())
+                .is_some_and(|alternate| alternate == to)
+        };
+        let mut pairs: Vec<MasterPair> = pairs
+            .into_iter()
+            .map(|(raw, jpeg)| MasterPair {
+                reciprocal: points_to(&raw, &jpeg) && points_to(&jpeg, &raw),
+                raw,
+                jpeg,
+                versions: vec![],
+                conflicting: false,
+            })
+            .collect();
+        pairs.sort_by(|a, b| a.raw.cmp(&b.raw));
+
+        let index = Self::pair_index(&pairs);
+        for indices in index.values().filter(|indices| indices.len() > 1) {
+            for i in indices {
+                pairs[*i].conflicting = true;
+            }
+        }
+        for version in versions {
+            let master_uuid = version.master_uuid.as_deref().unwrap_or_default();
+            let uuid = match version.uuid() {
+                Some(uuid) => uuid,
+                None => continue,
+            };
+            for i in index.get(master_uuid).into_iter().flatten() {
+                let pair = &mut pairs[*i];
+                if let Some(member) = pair.member(master_uuid) {
+                    pair.versions.push((uuid.clone(), member));
+                }
+            }
+        }
+        for pair in &mut pairs {
+            pair.versions.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        pairs
+    }
+
+    /// Return the indices in `pairs` of the pairs of each master.
+    fn pair_index(pairs: &[MasterPair]) -> HashMap<String, Vec<usize>> {
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, pair) in pairs.iter().enumerate() {
+            index.entry(pair.raw.clone()).or_default().push(i);
+            index.entry(pair.jpeg.clone()).or_default().push(i);
+        }
+        index
+    }
+
+    /// Return the pairs of version uuid and master uuid to export for
+    /// the versions selected by `selection`, according to `policy`.
+    pub(crate) fn export_masters_for(
+        &self,
+        selection: VersionSelection,
+        policy: PairPolicy,
+    ) -> Vec<(&Version, String)> {
+        let pairs = if policy == PairPolicy::Current {
+            vec![]
+        } else {
+            self.master_pairs()
+        };
+        let index = Self::pair_index(&pairs);
+        self.selected_versions(selection)
+            .into_iter()
+            .flat_map(|version| {
+                let master_uuid = version.master_uuid.as_deref().unwrap_or_default();
+                // The conflicting pairs are ignored.
+                let pair = index
+                    .get(master_uuid)
+                    .and_then(|indices| indices.first())
+                    .map(|i| &pairs[*i])
+                    .filter(|pair| !pair.conflicting);
+                policy
+                    .masters(master_uuid, pair)
+                    .into_iter()
+                    .map(|uuid| (version, uuid.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Write an XMP sidecar for each version into `dest`, mirroring
+    /// the paths of the masters. The volumes, masters and versions
+    /// must be loaded. Return the outcome for each version, and for
+    /// each master of the RAW+JPEG pairs exported.
+    pub fn write_sidecars<P>(&self, dest: P, options: &SidecarOptions) -> Result<Vec<ExportEntry>>
+    where
+        P: AsRef<Path>,
+    {
+        let dest = dest.as_ref();
+        fs::create_dir_all(dest).map_err(|err| Error::Io(dest.to_path_buf(), err))?;
+
+        let mut written = HashSet::new();
+        Ok(self
+            .export_masters_for(options.versions, options.pairs)
+            .into_iter()
+            .map(|(version, master_uuid)| {
+                let master = self.resolve_master_file(&master_uuid);
+                let uuid = version.uuid().as_deref().unwrap_or_default();
+                let mut entry = ExportEntry::new(uuid, master.clone());
+                if let Some(ref master) = master {
+                    let master_path = self.mirrored_path(master);
+                    export::write_sidecar(
+                        version,
+                        master_path,
+                        dest,
+                        options,
+                        &mut written,
+                        &mut entry,
+                    );
+                }
+                entry
+            })
+            .collect())
+    }
+
+    /// Copy the master of each version into `dest`, mirroring the
+    /// paths of the masters, and put the version XMP into the copy.
+    /// The masters are never modified. The formats that can't embed
+    /// XMP, like RAW, get a sidecar. The volumes, masters and versions
+    /// must be loaded. Return the outcome for each version, and for
+    /// each master of the RAW+JPEG pairs exported.
+    pub fn export_masters<P>(&self, dest: P, options: &EmbedOptions) -> Result<Vec<ExportEntry>>
+    where
+        P: AsRef<Path>,
+    {
+        let dest = dest.as_ref();
+        fs::create_dir_all(dest).map_err(|err| Error::Io(dest.to_path_buf(), err))?;
+
+        let mut written = HashSet::new();
+        Ok(self
+            .export_masters_for(options.versions, options.pairs)
+            .into_iter()
+            .map(|(version, master_uuid)| {
+                let master = self.resolve_master_file(&master_uuid);
+                let uuid = version.uuid().as_deref().unwrap_or_default();
+                let mut entry = ExportEntry::new(uuid, master.clone());
+                if let Some(ref master) = master {
+                    let master_path = self.mirrored_path(master);
+                    export::export_master(
+                        version,
+                        master_path,
+                        dest,
+                        options,
+                        &mut written,
+                        &mut entry,
+                    );
+                }
+                entry
+            })
+            .collect())
+    }
+
+    /// Write the loaded objects to `writer` as NDJSON, one record per
+    /// line: `{"type": "Version", "object": {...}}`. The objects are
+    /// sorted by type, then by uuid. The stacks, then the face names
+    /// and the detected faces follow, if loaded.
+    #[cfg(feature = "serde")]
+    pub fn export_json<W: std::io::Write>(&self, mut writer: W) -> Result<()> {
+        for set in [
+            &self.volumes,
+            &self.folders,
+            &self.albums,
+            &self.keywords,
+            &self.masters,
+            &self.versions,
+            &self.places,
+        ] {
+            let mut uuids: Vec<&String> = set.iter().collect();
+            uuids.sort();
+            for object in uuids.into_iter().filter_map(|uuid| self.get(uuid)) {
+                if let Some(obj_type) = object.obj_type() {
+                    write_json_record(&mut writer, obj_type, object)?;
+                }
+            }
+        }
+        for stack in self.stacks() {
+            write_json_record(&mut writer, "Stack", stack)?;
+        }
+        if let Some(faces) = self.faces() {
+            for name in faces.names.values() {
+                write_json_record(&mut writer, "FaceName", name)?;
+            }
+            let mut detected: Vec<&DetectedFace> = faces.detected().iter().collect();
+            detected.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+            for face in detected {
+                write_json_record(&mut writer, "DetectedFace", face)?;
+            }
+        }
+        writer.flush().map_err(Error::Write)?;
+        Ok(())
+    }
+
+    /// Write a CSV row with `columns` for each loaded version to
+    /// `writer`, after a header row. The versions are sorted by uuid.
+    /// The masters, folders and volumes must be loaded for their
+    /// columns to be filled.
+    pub fn export_csv<W: std::io::Write>(&self, writer: W, columns: &[CsvColumn]) -> Result<()> {
+        let rows = self
+            .selected_versions(VersionSelection::All)
+            .into_iter()
+            .map(|version| CsvRow {
+                version,
+                master: version
+                    .master_uuid
+                    .as_ref()
+                    .and_then(|uuid| match self.get(uuid) {
+                        Some(store::Wrapper::Master(master)) => Some(master.as_ref()),
+                        _ => None,
+                    }),
+                project: version
+                    .project_uuid
+                    .as_ref()
+                    .and_then(|uuid| match self.get(uuid) {
+                        Some(store::Wrapper::Folder(folder)) => Some(folder.as_ref()),
+                        _ => None,
+                    }),
+                path: version
+                    .master_uuid
+                    .as_ref()
+                    .and_then(|uuid| self.resolve_master_file(uuid))
+                    .map(|resolved| resolved.path.to_string_lossy().into_owned()),
+            });
+        csvexport::write_csv(writer, columns, rows)
+    }
+
+    /// Write the loaded objects into a new sqlite database at `path`
+    /// with the schema `SQLITE_EXPORT_SCHEMA`. The keywords must be
+    /// loaded for the versions keywords to be exported.
+    pub fn export_sqlite<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(Error::Io(
+                path.to_path_buf(),
+                std::io::Error::from(std::io::ErrorKind::AlreadyExists),
+            ));
+        }
+        let mut conn = rusqlite::Connection::open(path)?;
+        sqlexport::export(self, &mut conn)
+    }
+
+    /// Verify the plists against the library database, without
+    /// loading anything into the library. Plists that fail to parse
+    /// are ignored.
+    pub fn verify_database(&mut self) -> Result<VerifyReport> {
+        if self.database().is_none() {
+            return Err(Error::MissingDatabase(self.build_path(LIBRARY_DB, true)));
+        }
+
+        let mut verifier = Verifier::default();
+        let file_list = self.list_items(VOLUMES_DIR, "apvolume")?;
+        self.verify_items::<Volume>(file_list, &mut verifier)?;
+        let file_list = self.list_items(FOLDERS_DIR, "apfolder")?;
+        self.verify_items::<Folder>(file_list, &mut verifier)?;
+        let file_list = self.list_recursive_items(VERSIONS_BASE_DIR, "apmaster")?;
+        self.verify_items::<Master>(file_list, &mut verifier)?;
+        let file_list = self.list_recursive_items(VERSIONS_BASE_DIR, "apversion")?;
+        self.verify_items::<Version>(file_list, &mut verifier)?;
+
+        let plists = Library::parse_plist_objects(self.list_items(ALBUMS_DIR, "apalbum")?);
+        let mut objects = self.query_db_objects::<Album>()?;
+        self.load_albums_content(&mut objects)?;
+        verifier.compare(plists, objects);
+
+        let plists = match parse_keywords(self.build_path(KEYWORDS_PLIST, true), &mut None) {
+            Ok(keywords) => Keyword::flatten(keywords),
+            Err(Error::Io(_, err)) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+        let objects = self.query_db_objects::<Keyword>()?;
+        verifier.compare(plists, objects);
+
+        if let Some(conn) = self.database() {
+            verifier.orphan_album_versions(Album::orphan_content_from_db(conn)?);
+        }
+
+        Ok(verifier.finish())
+    }
+
+    /// Parse the plist files in `file_list` without storing the objects.
+    fn parse_plist_objects<T: PlistLoadable>(file_list: Vec<PathBuf>) -> Vec<T> {
+        file_list
+            .iter()
+            .filter_map(|path| T::from_path(path, None).ok())
+            .collect()
+    }
+
+    /// Compare the objects of type `T` from the plist files in
+    /// `file_list` with the ones in the database.
+    fn verify_items<T>(&mut self, file_list: Vec<PathBuf>, verifier: &mut Verifier) -> Result<()>
+    where
+        T: PlistLoadable + SqliteLoadable + Verifiable,
+    {
+        let plists = Library::parse_plist_objects::<T>(file_list);
+        let objects = self.query_db_objects::<T>()?;
+        verifier.compare(plists, objects);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_missing_library() {
+    let mut library = Library::new("/nonexistent/Aperture Library.aplibrary");
+
+    assert!(matches!(library.library_version(), Err(Error::Io(_, _))));
+    assert!(matches!(library.get_model_info(), Err(Error::Io(_, _))));
+    assert!(matches!(library.list_keywords(), Err(Error::Io(_, _))));
+
+    // Missing directories are just empty.
+    assert!(library.load_albums(PROGRESS_NONE).is_ok());
+    assert!(library.albums().is_empty());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert!(library.versions().is_empty());
+
+    // Unless the database is required.
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(matches!(
+        library.load_masters(PROGRESS_NONE),
+        Err(Error::MissingDatabase(_))
+    ));
+}
+
+#[cfg(test)]
+#[test]
+fn test_load_from_database() {
+    use crate::testutils;
+
+    let path = testutils::create_test_db_library("test_load_from_database");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_folders(PROGRESS_NONE).is_ok());
+    assert!(library.load_albums(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    assert_eq!(library.volumes().len(), 1);
+    assert_eq!(library.folders().len(), 1);
+    assert_eq!(library.albums().len(), 2);
+    assert_eq!(library.masters().len(), 1);
+    assert_eq!(library.versions().len(), 2);
+
+    match library.get("evHgvM2oQ3GR0j6gEMnNTQ") {
+        Some(store::Wrapper::Folder(folder)) => {
+            assert_eq!(folder.name.as_deref(), Some("Paris"));
+            assert_eq!(folder.folder_type, Some(crate::FolderType::Project));
+        }
+        _ => unreachable!("Folder not found"),
+    }
+    match library.get("x6yNun58SB2sImfCarTJHA") {
+        Some(store::Wrapper::Album(album)) => {
+            assert_eq!(album.subclass, Some(AlbumSubclass::User));
+            assert_eq!(
+                album.content.as_deref(),
+                Some(&["BF6nuoBnTumzoXyexdmXlw".to_string()][..])
+            );
+        }
+        _ => unreachable!("Album not found"),
+    }
+    match library.get("J0+f3AmESPer4GHGv4BgAQ") {
+        Some(store::Wrapper::Album(album)) => {
+            assert_eq!(album.subclass, Some(AlbumSubclass::Implicit));
+            assert!(album.content.is_none());
+        }
+        _ => unreachable!("Album not found"),
+    }
+    match library.get("BF6nuoBnTumzoXyexdmXlw") {
+        Some(store::Wrapper::Version(version)) => {
+            assert_eq!(version.rating, Some(4));
+            assert_eq!(version.is_flagged, Some(true));
+            assert_eq!(version.keyword_paths(), vec![vec!["Places", "Paris"]]);
+            assert_eq!(
+                version.master_uuid.as_deref(),
+                Some("JpLq7STrRMmgm5YZTm6IzA")
+            );
+        }
+        _ => unreachable!("Version not found"),
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+#[test]
+fn test_export_json() {
+    use crate::testutils;
+
+    let path = testutils::create_test_db_library("test_export_json");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert!(library.load_stacks().is_ok());
+
+    let mut buffer = vec![];
+    assert!(library.export_json(&mut buffer).is_ok());
+    let records: Vec<serde_json::Value> = String::from_utf8(buffer)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // The keywords are loaded with the versions.
+    assert_eq!(records.len(), 6);
+    assert_eq!(records[0]["type"], "Volume");
+    assert_eq!(records[0]["object"]["volume_name"], "Photos");
+    assert_eq!(records[1]["type"], "Keyword");
+    assert_eq!(records[3]["type"], "Version");
+    assert_eq!(records[3]["object"]["uuid"], "BF6nuoBnTumzoXyexdmXlw");
+    assert_eq!(records[3]["object"]["rating"], 4);
+    assert_eq!(
+        records[3]["object"]["assigned_keywords"],
+        serde_json::json!([["Places", "Paris"]])
+    );
+    assert_eq!(records[5]["type"], "Stack");
+    assert_eq!(records[5]["object"]["uuid"], "sT4ckKd0QmGm3yJx9Xq1bA");
+
+    // The writer errors are write errors.
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::WriteZero.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    assert!(matches!(library.export_json(Full), Err(Error::Write(_))));
 }
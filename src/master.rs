@@ -13,13 +13,16 @@ use crate::audit::{
     audit_get_int_value, audit_get_str_value, Report, SkipReason,
 };
 use crate::notes::NotesProperties;
+use crate::sqlutils::get_date_value;
 use crate::store;
 use crate::AplibObject;
 use crate::AplibType;
 use crate::PlistLoadable;
+use crate::{Error, Result, SqliteLoadable};
 
 /// A `Master` is a file backing an image (`Version`)
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Master {
     uuid: Option<String>,
     model_id: Option<i64>,
@@ -59,16 +62,16 @@ pub struct Master {
 }
 
 impl PlistLoadable for Master {
-    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Option<Master>
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Master>
     where
         P: AsRef<Path>,
     {
         use crate::plutils::*;
-        let plist = parse_plist(plist_path);
+        let plist = parse_plist(&plist_path)?;
         match plist {
             Value::Dictionary(ref dict) => {
                 let notes = audit_get_array_value(dict, "notes", &mut auditor);
-                let result = Some(Master {
+                let result = Ok(Master {
                     uuid: audit_get_str_value(dict, "uuid", &mut auditor),
                     alternate_master: audit_get_str_value(
                         dict,
@@ -144,11 +147,68 @@ impl PlistLoadable for Master {
                 }
                 result
             }
-            _ => None,
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
         }
     }
 }
 
+impl SqliteLoadable for Master {
+    fn tables() -> &'static str {
+        "RKMaster"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, projectUuid, alternateMasterUuid, originalVersionUuid, \
+         importGroupUuid, fileName, name, originalVersionName, originalFileName, \
+         fileVolumeUuid, type, subtype, imagePath, fileSize, fileIsReference, \
+         isExternallyEditable, isInTrash, isMissing, isTrulyRaw, colorSpaceName, \
+         createDate, imageDate, fileCreationDate, fileModificationDate, hasFocusPoints, \
+         imageFormat, pixelFormat, colorSpaceDefinition, faceDetectionState"
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Master {
+            uuid: row.get("uuid")?,
+            model_id: row.get("modelId")?,
+            project_uuid: row.get("projectUuid")?,
+            alternate_master: row.get("alternateMasterUuid")?,
+            original_version_uuid: row.get("originalVersionUuid")?,
+            import_group_uuid: row.get("importGroupUuid")?,
+            filename: row.get("fileName")?,
+            name: row.get("name")?,
+            original_version_name: row.get("originalVersionName")?,
+            original_file_name: row.get("originalFileName")?,
+            file_volume_uuid: row.get("fileVolumeUuid")?,
+            // Only in the plist.
+            db_version: None,
+            master_type: row.get("type")?,
+            subtype: row.get("subtype")?,
+            image_path: row.get("imagePath")?,
+            file_size: row.get("fileSize")?,
+            is_reference: row.get("fileIsReference")?,
+            is_externaly_editable: row.get("isExternallyEditable")?,
+            is_in_trash: row.get("isInTrash")?,
+            is_missing: row.get("isMissing")?,
+            is_truly_raw: row.get("isTrulyRaw")?,
+            color_space_name: row.get("colorSpaceName")?,
+            create_date: get_date_value(row, "createDate")?,
+            image_date: get_date_value(row, "imageDate")?,
+            file_creation_date: get_date_value(row, "fileCreationDate")?,
+            file_modification_date: get_date_value(row, "fileModificationDate")?,
+            has_focus_points: row.get("hasFocusPoints")?,
+            image_format: row.get("imageFormat")?,
+            pixel_format: row.get("pixelFormat")?,
+            colour_space_definition: row.get("colorSpaceDefinition")?,
+            // Notes are in RKNote.
+            notes: None,
+            face_detection_state: row.get("faceDetectionState")?,
+        })
+    }
+}
+
 impl AplibObject for Master {
     fn obj_type(&self) -> AplibType {
         AplibType::Master
@@ -170,7 +230,13 @@ impl AplibObject for Master {
     }
 }
 
-impl Master {}
+impl Master {
+    /// Whether the master is a RAW file: the `RAWST` subtype, or
+    /// truly RAW.
+    pub fn is_raw(&self) -> bool {
+        self.subtype.as_deref() == Some("RAWST") || self.is_truly_raw == Some(true)
+    }
+}
 
 #[cfg(test)]
 #[test]
@@ -181,7 +247,7 @@ fn test_master_parse() {
         testutils::get_test_file_path("Master.apmaster").as_path(),
         None,
     );
-    assert!(master.is_some());
+    assert!(master.is_ok());
     let master = master.unwrap();
 
     assert_eq!(master.uuid.as_ref().unwrap(), "JpLq7STrRMmgm5YZTm6IzA");
@@ -201,7 +267,51 @@ fn test_master_parse() {
     assert_eq!(master.filename.as_ref().unwrap(), "img_8826.cr2");
     assert_eq!(master.master_type.as_ref().unwrap(), "IMGT");
     assert_eq!(master.subtype.as_ref().unwrap(), "RAWST");
+    assert!(master.is_raw());
 
     // XXX fix when have actual audit.
     //    println!("report {:?}", report);
 }
+
+#[cfg(test)]
+#[test]
+fn test_master_from_row() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE RKMaster (modelId INTEGER, uuid VARCHAR, projectUuid VARCHAR, \
+         alternateMasterUuid VARCHAR, originalVersionUuid VARCHAR, importGroupUuid VARCHAR, \
+         fileName VARCHAR, name VARCHAR, originalVersionName VARCHAR, originalFileName VARCHAR, \
+         fileVolumeUuid VARCHAR, type VARCHAR, subtype VARCHAR, imagePath VARCHAR, \
+         fileSize INTEGER, fileIsReference INTEGER, isExternallyEditable INTEGER, \
+         isInTrash INTEGER, isMissing INTEGER, isTrulyRaw INTEGER, colorSpaceName VARCHAR, \
+         createDate TIMESTAMP, imageDate TIMESTAMP, fileCreationDate TIMESTAMP, \
+         fileModificationDate TIMESTAMP, hasFocusPoints INTEGER, imageFormat INTEGER, \
+         pixelFormat INTEGER, colorSpaceDefinition BLOB, faceDetectionState INTEGER);
+         INSERT INTO RKMaster (modelId, uuid, projectUuid, fileName, type, subtype, \
+         fileIsReference, isInTrash, imageDate) VALUES (42, 'JpLq7STrRMmgm5YZTm6IzA', \
+         'evHgvM2oQ3GR0j6gEMnNTQ', 'img_8826.cr2', 'IMGT', 'RAWST', 1, 0, 336528000.0);",
+    )
+    .unwrap();
+
+    let query = format!("SELECT {} FROM {}", Master::columns(), Master::tables());
+    let master = conn
+        .query_row(&query, [], |row| Ok(Master::from_row(row)))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(master.uuid.as_ref().unwrap(), "JpLq7STrRMmgm5YZTm6IzA");
+    assert_eq!(master.model_id(), 42);
+    assert_eq!(
+        master.project_uuid.as_ref().unwrap(),
+        "evHgvM2oQ3GR0j6gEMnNTQ"
+    );
+    assert_eq!(master.filename.as_ref().unwrap(), "img_8826.cr2");
+    assert_eq!(master.subtype.as_ref().unwrap(), "RAWST");
+    assert!(master.is_reference.unwrap());
+    assert!(!master.is_in_trash.unwrap());
+    assert!(master.is_missing.is_none());
+    assert_eq!(
+        master.image_date.unwrap().to_rfc3339(),
+        "2011-09-01T00:00:00+00:00"
+    );
+}
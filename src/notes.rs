@@ -13,14 +13,15 @@ use crate::audit::{
 use crate::plutils::Value;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NotesProperties {
-    attached_to_uuid: Option<String>,
-    create_date: Option<DateTime<Utc>>,
-    data: Option<Vec<u8>>,
-    model_id: Option<i64>,
-    note: Option<String>,
-    uuid: Option<String>,
-    property_key: Option<String>,
+    pub attached_to_uuid: Option<String>,
+    pub create_date: Option<DateTime<Utc>>,
+    pub data: Option<Vec<u8>>,
+    pub model_id: Option<i64>,
+    pub note: Option<String>,
+    pub uuid: Option<String>,
+    pub property_key: Option<String>,
 }
 
 impl NotesProperties {
@@ -0,0 +1,250 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! RAW+JPEG pairs: the two masters of a shot imported as RAW+JPEG.
+
+/// A master of a pair.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PairMember {
+    /// The RAW master, `MasterPair::raw`.
+    Raw,
+    /// The JPEG master, `MasterPair::jpeg`.
+    Jpeg,
+}
+
+/// A RAW master and its JPEG master.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterPair {
+    /// uuid of the RAW master.
+    pub raw: String,
+    /// uuid of the JPEG master.
+    pub jpeg: String,
+    /// Whether both masters point to each other with
+    /// `alternateMasterUuid`.
+    pub reciprocal: bool,
+    /// The uuid of the versions of the pair and the master they
+    /// use, sorted by uuid.
+    pub versions: Vec<(String, PairMember)>,
+    /// Whether a master is in other pairs too. The exports then only
+    /// use the master of the versions, see `PairPolicy`.
+    pub conflicting: bool,
+}
+
+impl MasterPair {
+    /// Return the uuid of the master `member`.
+    pub fn master(&self, member: PairMember) -> &str {
+        match member {
+            PairMember::Raw => &self.raw,
+            PairMember::Jpeg => &self.jpeg,
+        }
+    }
+
+    /// Return which master `uuid` is in the pair.
+    pub fn member(&self, uuid: &str) -> Option<PairMember> {
+        if uuid == self.raw {
+            Some(PairMember::Raw)
+        } else if uuid == self.jpeg {
+            Some(PairMember::Jpeg)
+        } else {
+            None
+        }
+    }
+}
+
+/// Which masters of the RAW+JPEG pairs to export. The conflicting
+/// pairs are ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PairPolicy {
+    /// The master the version uses.
+    #[default]
+    Current,
+    /// The RAW master.
+    Raw,
+    /// The JPEG master.
+    Jpeg,
+    /// Both masters. With `SidecarNaming::ReplaceExtension` the
+    /// masters with the same name get the same sidecar: the second is
+    /// `ExportStatus::Failed`.
+    Both,
+}
+
+impl PairPolicy {
+    /// Return the uuid of the masters to export for the version that
+    /// uses the master `master_uuid`, in `pair` if it has one.
+    pub(crate) fn masters<'a>(
+        &self,
+        master_uuid: &'a str,
+        pair: Option<&'a MasterPair>,
+    ) -> Vec<&'a str> {
+        let pair = match pair {
+            Some(pair) => pair,
+            None => return vec![master_uuid],
+        };
+        match *self {
+            PairPolicy::Current => vec![master_uuid],
+            PairPolicy::Raw => vec![pair.master(PairMember::Raw)],
+            PairPolicy::Jpeg => vec![pair.master(PairMember::Jpeg)],
+            PairPolicy::Both => vec![pair.master(PairMember::Raw), pair.master(PairMember::Jpeg)],
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_pair_policy() {
+    let pair = MasterPair {
+        raw: "raw".to_string(),
+        jpeg: "jpeg".to_string(),
+        reciprocal: true,
+        versions: vec![],
+        conflicting: false,
+    };
+    assert_eq!(pair.member("jpeg"), Some(PairMember::Jpeg));
+    assert_eq!(pair.member("other"), None);
+
+    assert_eq!(
+        PairPolicy::Current.masters("jpeg", Some(&pair)),
+        vec!["jpeg"]
+    );
+    assert_eq!(PairPolicy::Raw.masters("jpeg", Some(&pair)), vec!["raw"]);
+    assert_eq!(PairPolicy::Jpeg.masters("raw", Some(&pair)), vec!["jpeg"]);
+    assert_eq!(
+        PairPolicy::Both.masters("jpeg", Some(&pair)),
+        vec!["raw", "jpeg"]
+    );
+    // Not in a pair.
+    assert_eq!(PairPolicy::Raw.masters("tiff", None), vec!["tiff"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_master_pairs() {
+    use crate::testutils;
+    use crate::{
+        ExportStatus, Library, LoadPolicy, PairMember, SidecarNaming, SidecarOptions,
+        VersionSelection, PROGRESS_NONE,
+    };
+    use std::fs;
+
+    let path = testutils::create_test_db_library("test_master_pairs");
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    // The JPEG only points to the RAW.
+    conn.execute_batch(
+        "INSERT INTO RKMaster (modelId, uuid, projectUuid, alternateMasterUuid, fileName, \
+           name, fileVolumeUuid, type, subtype, imagePath, fileIsReference, isInTrash) \
+           VALUES (2, 'rZ0Jc3sKQbWm1tLq8AoP7g', 'evHgvM2oQ3GR0j6gEMnNTQ', \
+           'JpLq7STrRMmgm5YZTm6IzA', 'img_8826.jpg', 'img_8826', 'vEyyIbUTQ5eKfCqU9oZ8uQ', \
+           'IMGT', 'JPGST', '2011/img_8826.jpg', 1, 0); \
+         UPDATE RKVersion SET nonRawMasterUuid = 'rZ0Jc3sKQbWm1tLq8AoP7g'; \
+         UPDATE RKVersion SET masterUuid = 'rZ0Jc3sKQbWm1tLq8AoP7g' WHERE modelId = 2;",
+    )
+    .unwrap();
+    drop(conn);
+
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let pairs = library.master_pairs();
+    assert_eq!(pairs.len(), 1);
+    let pair = &pairs[0];
+    assert_eq!(pair.raw, "JpLq7STrRMmgm5YZTm6IzA");
+    assert_eq!(pair.jpeg, "rZ0Jc3sKQbWm1tLq8AoP7g");
+    assert!(!pair.reciprocal);
+    assert_eq!(
+        pair.versions,
+        vec![
+            ("BF6nuoBnTumzoXyexdmXlw".to_string(), PairMember::Jpeg),
+            ("VF%CkiTKQy+h53Oyr7KCOA".to_string(), PairMember::Raw),
+        ]
+    );
+
+    let masters = |policy| {
+        library
+            .export_masters_for(VersionSelection::All, policy)
+            .into_iter()
+            .map(|(_, master)| master)
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        masters(PairPolicy::Current),
+        vec!["rZ0Jc3sKQbWm1tLq8AoP7g", "JpLq7STrRMmgm5YZTm6IzA"]
+    );
+    assert_eq!(
+        masters(PairPolicy::Raw),
+        vec!["JpLq7STrRMmgm5YZTm6IzA", "JpLq7STrRMmgm5YZTm6IzA"]
+    );
+    assert_eq!(masters(PairPolicy::Both).len(), 4);
+
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    conn.execute_batch(
+        "UPDATE RKMaster SET alternateMasterUuid = 'rZ0Jc3sKQbWm1tLq8AoP7g' WHERE modelId = 1;",
+    )
+    .unwrap();
+    drop(conn);
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert!(library.master_pairs()[0].reciprocal);
+
+    // Both masters with IMG.xmp: the second sidecar is a collision.
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    let dest = path.join("export");
+    let sidecars = dest.join("Volumes/Photos/2011");
+    fs::create_dir_all(&sidecars).unwrap();
+    fs::write(sidecars.join("img_8826.xmp"), "").unwrap();
+    fs::write(sidecars.join("img_8826_01.xmp"), "").unwrap();
+    let options = SidecarOptions {
+        naming: SidecarNaming::ReplaceExtension,
+        pairs: PairPolicy::Both,
+        ..Default::default()
+    };
+    let statuses: Vec<ExportStatus> = library
+        .write_sidecars(&dest, &options)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.status)
+        .collect();
+    assert_eq!(statuses.len(), 4);
+    assert_eq!(statuses[0], ExportStatus::Exists);
+    assert!(matches!(statuses[1], ExportStatus::Failed(_)));
+    assert_eq!(statuses[2], ExportStatus::Exists);
+    assert!(matches!(statuses[3], ExportStatus::Failed(_)));
+
+    // Another JPEG points to the RAW: both pairs are conflicting.
+    let conn = rusqlite::Connection::open(path.join("Database/apdb/Library.apdb")).unwrap();
+    conn.execute_batch(
+        "INSERT INTO RKMaster (modelId, uuid, projectUuid, alternateMasterUuid, fileName, \
+           name, fileVolumeUuid, type, subtype, imagePath, fileIsReference, isInTrash) \
+           VALUES (3, 'Qm4Nd8cTRXa2pVf6LwYbHg', 'evHgvM2oQ3GR0j6gEMnNTQ', \
+           'JpLq7STrRMmgm5YZTm6IzA', 'img_8826-1.jpg', 'img_8826-1', \
+           'vEyyIbUTQ5eKfCqU9oZ8uQ', 'IMGT', 'JPGST', '2011/img_8826-1.jpg', 1, 0);",
+    )
+    .unwrap();
+    drop(conn);
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    let pairs = library.master_pairs();
+    assert_eq!(pairs.len(), 2);
+    assert!(pairs.iter().all(|pair| pair.conflicting));
+    // The versions of the RAW are in both.
+    assert!(pairs.iter().all(|pair| pair
+        .versions
+        .contains(&("VF%CkiTKQy+h53Oyr7KCOA".to_string(), PairMember::Raw))));
+    let masters: Vec<String> = library
+        .export_masters_for(VersionSelection::All, PairPolicy::Both)
+        .into_iter()
+        .map(|(_, master)| master)
+        .collect();
+    assert_eq!(
+        masters,
+        vec!["rZ0Jc3sKQbWm1tLq8AoP7g", "JpLq7STrRMmgm5YZTm6IzA"]
+    );
+}
@@ -0,0 +1,458 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+use std::path::Path;
+
+use crate::audit::{
+    audit_get_array_value, audit_get_bool_value, audit_get_int_value, audit_get_real_value,
+    audit_get_str_value, Report, SkipReason,
+};
+use crate::store;
+use crate::xmp::ns::NS_IPTC4XMP_EXT;
+use crate::{AplibObject, AplibType, Error, PlistLoadable, Result, SqliteLoadable};
+
+/// Mean radius of the Earth, in metres.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// A place, from the Places view. Places nest: country, state, city...
+/// A place is an area, either a polygon or a circle around its centre.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Place {
+    uuid: Option<String>,
+    model_id: Option<i64>,
+    /// uuid of the enclosing place.
+    parent_uuid: Option<String>,
+
+    pub name: Option<String>,
+    /// The kind of place.
+    pub place_type: Option<i64>,
+    /// Whether it was created by the user.
+    pub is_user_place: Option<bool>,
+    /// The centre, as latitude and longitude in degrees.
+    pub centroid: Option<(f64, f64)>,
+    /// The radius around the centre, in metres.
+    pub radius: Option<f64>,
+    /// The boundary, as latitude and longitude in degrees.
+    pub polygon: Option<Vec<(f64, f64)>>,
+}
+
+/// Parse the coordinates `"latitude,longitude"`. Braces and spaces
+/// are allowed: `"{48.85, 2.35}"`.
+pub(crate) fn parse_coordinates(value: &str) -> Option<(f64, f64)> {
+    let value = value.trim().trim_start_matches('{').trim_end_matches('}');
+    let (latitude, longitude) = value.split_once(',')?;
+    Some((
+        latitude.trim().parse().ok()?,
+        longitude.trim().parse().ok()?,
+    ))
+}
+
+/// The polygon of the bounding box `min` `max`.
+fn bounding_box(min: (f64, f64), max: (f64, f64)) -> Vec<(f64, f64)> {
+    vec![min, (min.0, max.1), max, (max.0, min.1)]
+}
+
+/// The polygon from the bounding box columns of `row`.
+fn row_bounding_box(row: &rusqlite::Row) -> rusqlite::Result<Option<Vec<(f64, f64)>>> {
+    let min_latitude: Option<f64> = row.get("minLatitude")?;
+    let min_longitude: Option<f64> = row.get("minLongitude")?;
+    let max_latitude: Option<f64> = row.get("maxLatitude")?;
+    let max_longitude: Option<f64> = row.get("maxLongitude")?;
+    Ok(min_latitude
+        .zip(min_longitude)
+        .zip(max_latitude.zip(max_longitude))
+        .map(|(min, max)| bounding_box(min, max)))
+}
+
+impl SqliteLoadable for Place {
+    fn tables() -> &'static str {
+        "RKPlace"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, parentUuid, defaultName, type, userPlace, centroid, \
+         minLatitude, minLongitude, maxLatitude, maxLongitude"
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let centroid: Option<String> = row.get("centroid")?;
+        Ok(Place {
+            uuid: row.get("uuid")?,
+            model_id: row.get("modelId")?,
+            parent_uuid: row.get("parentUuid")?,
+            name: row.get("defaultName")?,
+            place_type: row.get("type")?,
+            is_user_place: row.get("userPlace")?,
+            centroid: centroid.as_deref().and_then(parse_coordinates),
+            // Only in the plist.
+            radius: None,
+            polygon: row_bounding_box(row)?,
+        })
+    }
+}
+
+impl PlistLoadable for Place {
+    /// Load the place object from the plist at plist_path.
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Place>
+    where
+        P: AsRef<Path>,
+    {
+        use crate::plutils::*;
+
+        let plist = parse_plist(&plist_path)?;
+        match plist {
+            Value::Dictionary(ref dict) => {
+                let boundary = audit_get_array_value(dict, "boundary", &mut auditor);
+                let min_latitude = audit_get_real_value(dict, "minLatitude", &mut auditor);
+                let min_longitude = audit_get_real_value(dict, "minLongitude", &mut auditor);
+                let max_latitude = audit_get_real_value(dict, "maxLatitude", &mut auditor);
+                let max_longitude = audit_get_real_value(dict, "maxLongitude", &mut auditor);
+                let polygon = boundary
+                    .map(|boundary| {
+                        boundary
+                            .iter()
+                            .filter_map(Value::as_string)
+                            .filter_map(parse_coordinates)
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|polygon| polygon.len() > 2)
+                    .or_else(|| {
+                        min_latitude
+                            .zip(min_longitude)
+                            .zip(max_latitude.zip(max_longitude))
+                            .map(|(min, max)| bounding_box(min, max))
+                    });
+                let result = Ok(Place {
+                    uuid: audit_get_str_value(dict, "uuid", &mut auditor),
+                    model_id: audit_get_int_value(dict, "modelId", &mut auditor),
+                    parent_uuid: audit_get_str_value(dict, "parentUuid", &mut auditor),
+                    name: audit_get_str_value(dict, "defaultName", &mut auditor),
+                    place_type: audit_get_int_value(dict, "type", &mut auditor),
+                    is_user_place: audit_get_bool_value(dict, "userPlace", &mut auditor),
+                    centroid: audit_get_str_value(dict, "centroid", &mut auditor)
+                        .as_deref()
+                        .and_then(parse_coordinates),
+                    radius: audit_get_real_value(dict, "radius", &mut auditor),
+                    polygon,
+                });
+                if let Some(auditor) = &mut auditor {
+                    auditor.skip("plistWriteTimestamp", SkipReason::Ignore);
+                    auditor.audit_ignored(dict, None);
+                }
+                result
+            }
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
+        }
+    }
+}
+
+impl AplibObject for Place {
+    fn obj_type(&self) -> AplibType {
+        AplibType::Place
+    }
+    fn uuid(&self) -> &Option<String> {
+        &self.uuid
+    }
+    fn parent(&self) -> &Option<String> {
+        &self.parent_uuid
+    }
+    fn model_id(&self) -> i64 {
+        self.model_id.unwrap_or(0)
+    }
+    fn is_valid(&self) -> bool {
+        self.uuid.is_some()
+    }
+    fn wrap(obj: Place) -> store::Wrapper {
+        store::Wrapper::Place(Box::new(obj))
+    }
+}
+
+/// The distance in metres between `a` and `b`, as latitude and
+/// longitude in degrees.
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_long = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_long / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+impl Place {
+    /// Return the pairs of version uuid and place uuid from
+    /// `RKPlaceForVersion`.
+    pub fn pairs_from_db(conn: &rusqlite::Connection) -> Result<Vec<(String, String)>> {
+        let mut stmt = conn.prepare(
+            "SELECT v.uuid, p.uuid FROM RKPlaceForVersion pv \
+             JOIN RKVersion v ON pv.versionId = v.modelId \
+             JOIN RKPlace p ON pv.placeId = p.modelId",
+        )?;
+        let result = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+
+        Ok(result)
+    }
+
+    /// Whether the location at `latitude` and `longitude` is in the
+    /// place: inside the polygon, or else within the radius of the
+    /// centre.
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        if let Some(polygon) = self.polygon.as_ref().filter(|polygon| !polygon.is_empty()) {
+            // Ray casting along the latitude.
+            let mut inside = false;
+            let mut previous = polygon[polygon.len() - 1];
+            for &point in polygon {
+                if (point.1 > longitude) != (previous.1 > longitude)
+                    && latitude
+                        < (previous.0 - point.0) * (longitude - point.1) / (previous.1 - point.1)
+                            + point.0
+                {
+                    inside = !inside;
+                }
+                previous = point;
+            }
+            return inside;
+        }
+        match (self.centroid, self.radius) {
+            (Some(centroid), Some(radius)) => distance(centroid, (latitude, longitude)) <= radius,
+            _ => false,
+        }
+    }
+}
+
+/// The fields of the `Iptc4xmpExt:LocationShown` struct for the place
+/// `path`, the names from the root: country, province or state, city,
+/// then the sublocation. `LocationName` is the name of the place.
+pub(crate) fn location_shown_fields(path: &[String]) -> Vec<(&'static str, String)> {
+    const FIELDS: [&str; 3] = ["CountryName", "ProvinceState", "City"];
+
+    let mut fields: Vec<(&'static str, String)> = FIELDS
+        .iter()
+        .zip(path)
+        .map(|(field, name)| (*field, name.clone()))
+        .collect();
+    if path.len() > FIELDS.len() {
+        fields.push(("Sublocation", path[FIELDS.len()..].join(", ")));
+    }
+    if let Some(name) = path.last() {
+        fields.push(("LocationName", name.clone()));
+    }
+    fields
+}
+
+/// Write the places `paths` into `Iptc4xmpExt:LocationShown`,
+/// replacing the existing locations.
+pub(crate) fn location_shown_to_xmp(paths: &[Vec<String>], xmp: &mut exempi2::Xmp) -> bool {
+    let _ = exempi2::register_namespace(NS_IPTC4XMP_EXT, "Iptc4xmpExt");
+    let _ = xmp.delete_property(NS_IPTC4XMP_EXT, "LocationShown");
+    if paths.is_empty() {
+        return true;
+    }
+    let mut result = xmp
+        .set_property(
+            NS_IPTC4XMP_EXT,
+            "LocationShown",
+            "",
+            exempi2::PropFlags::VALUE_IS_ARRAY,
+        )
+        .is_ok();
+    for (index, path) in paths.iter().enumerate() {
+        for (field, value) in location_shown_fields(path) {
+            result &= xmp
+                .set_property(
+                    NS_IPTC4XMP_EXT,
+                    &format!("LocationShown[{}]/Iptc4xmpExt:{}", index + 1, field),
+                    &value,
+                    exempi2::PropFlags::NONE,
+                )
+                .is_ok();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[test]
+fn test_place_contains() {
+    assert_eq!(parse_coordinates("48.8566,2.3522"), Some((48.8566, 2.3522)));
+    assert_eq!(
+        parse_coordinates("{45.5017, -73.5673}"),
+        Some((45.5017, -73.5673))
+    );
+    assert_eq!(parse_coordinates("Paris"), None);
+
+    let paris = Place {
+        polygon: Some(bounding_box((48.81, 2.22), (48.91, 2.47))),
+        ..Default::default()
+    };
+    assert!(paris.contains(48.8566, 2.3522));
+    assert!(!paris.contains(48.95, 2.35));
+
+    // The Louvre, 300 m around.
+    let louvre = Place {
+        centroid: Some((48.8606, 2.3376)),
+        radius: Some(300.0),
+        ..Default::default()
+    };
+    assert!(louvre.contains(48.8611, 2.3358));
+    assert!(!louvre.contains(48.8584, 2.2945));
+    assert!(!Place::default().contains(48.8606, 2.3376));
+
+    let path: Vec<String> = ["France", "Île-de-France", "Paris", "Louvre", "Cour Carrée"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    assert_eq!(
+        location_shown_fields(&path),
+        vec![
+            ("CountryName", "France".to_string()),
+            ("ProvinceState", "Île-de-France".to_string()),
+            ("City", "Paris".to_string()),
+            ("Sublocation", "Louvre, Cour Carrée".to_string()),
+            ("LocationName", "Cour Carrée".to_string()),
+        ]
+    );
+    assert_eq!(
+        location_shown_fields(&path[..1]),
+        vec![
+            ("CountryName", "France".to_string()),
+            ("LocationName", "France".to_string()),
+        ]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_place_parse() {
+    use crate::testutils;
+
+    let mut report = Report::new();
+    let paris = Place::from_path(
+        testutils::get_test_file_path("pR1sXk0bTq6nH2yF5dWcLg.applace").as_path(),
+        Some(&mut report),
+    )
+    .unwrap();
+    assert_eq!(report.ignored_count(), 0);
+    assert_eq!(paris.uuid.as_deref(), Some("pR1sXk0bTq6nH2yF5dWcLg"));
+    assert_eq!(paris.model_id, Some(3));
+    assert!(paris.parent_uuid.is_none());
+    assert_eq!(paris.name.as_deref(), Some("Paris"));
+    assert_eq!(paris.place_type, Some(3));
+    assert_eq!(paris.is_user_place, Some(false));
+    assert_eq!(paris.centroid, Some((48.8566, 2.3522)));
+    assert!(paris.radius.is_none());
+    // The boundary.
+    assert_eq!(
+        paris.polygon,
+        Some(vec![
+            (48.81, 2.22),
+            (48.81, 2.47),
+            (48.91, 2.47),
+            (48.91, 2.22)
+        ])
+    );
+
+    let louvre = Place::from_path(
+        testutils::get_test_file_path("Lv8rEq3TQqWmC7xZ0aN4Hw.applace").as_path(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        louvre.parent_uuid.as_deref(),
+        Some("pR1sXk0bTq6nH2yF5dWcLg")
+    );
+    assert_eq!(louvre.is_user_place, Some(true));
+    assert_eq!(louvre.radius, Some(300.0));
+    // A boundary of one point isn't a polygon: the bounding box.
+    assert_eq!(
+        louvre.polygon,
+        Some(bounding_box((48.8579, 2.3335), (48.8633, 2.3417)))
+    );
+    assert!(louvre.contains(48.8611, 2.3358));
+}
+
+#[cfg(test)]
+#[test]
+fn test_load_places() {
+    use crate::testutils;
+    use crate::{Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_load_places");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert!(library.load_places(PROGRESS_NONE).is_ok());
+    assert_eq!(library.places().len(), 3);
+
+    let names = |places: Vec<&Place>| -> Vec<String> {
+        places
+            .iter()
+            .map(|place| place.name.clone().unwrap_or_default())
+            .collect()
+    };
+    assert_eq!(
+        names(library.place_path("WnU3MDx8Q0uAkWhd8y8vKw")),
+        vec!["France", "Île-de-France", "Paris"]
+    );
+    assert!(library.place_path("nonexistent").is_empty());
+    assert_eq!(
+        names(library.places_for_version("BF6nuoBnTumzoXyexdmXlw")),
+        vec!["France", "Paris"]
+    );
+    assert!(library
+        .places_for_version("VF%CkiTKQy+h53Oyr7KCOA")
+        .is_empty());
+
+    // Only the innermost place is kept.
+    match library.get("BF6nuoBnTumzoXyexdmXlw") {
+        Some(store::Wrapper::Version(version)) => assert_eq!(
+            version.places,
+            Some(vec![vec![
+                "France".to_string(),
+                "Île-de-France".to_string(),
+                "Paris".to_string()
+            ]])
+        ),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_load_places_plists() {
+    use crate::testutils;
+    use crate::{Library, LoadPolicy, PROGRESS_NONE};
+    use std::fs;
+
+    let path = testutils::create_test_db_library("test_load_places_plists");
+    let places_dir = path.join("Database/Places");
+    fs::create_dir_all(&places_dir).unwrap();
+    for file in [
+        "pR1sXk0bTq6nH2yF5dWcLg.applace",
+        "Lv8rEq3TQqWmC7xZ0aN4Hw.applace",
+    ] {
+        fs::copy(testutils::get_test_file_path(file), places_dir.join(file)).unwrap();
+    }
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::PlistFirst);
+    assert!(library.load_places(PROGRESS_NONE).is_ok());
+    // Not the places of the database.
+    assert_eq!(library.places().len(), 2);
+    let path_names: Vec<&str> = library
+        .place_path("Lv8rEq3TQqWmC7xZ0aN4Hw")
+        .iter()
+        .filter_map(|place| place.name.as_deref())
+        .collect();
+    assert_eq!(path_names, vec!["Paris", "Louvre"]);
+    match library.get("Lv8rEq3TQqWmC7xZ0aN4Hw") {
+        Some(store::Wrapper::Place(place)) => assert_eq!(place.radius, Some(300.0)),
+        _ => unreachable!(),
+    }
+}
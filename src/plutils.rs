@@ -9,18 +9,20 @@ pub use plist::Value;
 use std::path::Path;
 use std::time::SystemTime;
 
-pub fn parse_plist<P>(path: P) -> Value
+use crate::{Error, Result};
+
+/// Parse the plist file at `path`.
+pub fn parse_plist<P>(path: P) -> Result<Value>
 where
     P: AsRef<Path>,
 {
-    let result = Value::from_file(&path);
-    match result {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Error from plist::read with file {:?}", path.as_ref());
-            Value::Dictionary(plist::Dictionary::new())
+    Value::from_file(&path).map_err(|err| {
+        let path = path.as_ref().to_path_buf();
+        match err.into_io() {
+            Ok(err) => Error::Io(path, err),
+            Err(err) => Error::Plist(path, err),
         }
-    }
+    })
 }
 
 pub fn get_str_value(dict: &plist::Dictionary, key: &str) -> Option<String> {
@@ -44,6 +46,15 @@ pub fn get_uint_value(dict: &plist::Dictionary, key: &str) -> Option<u64> {
     }
 }
 
+/// Get the real `key`. Integers are converted.
+pub fn get_real_value(dict: &plist::Dictionary, key: &str) -> Option<f64> {
+    match dict.get(key) {
+        Some(&Value::Real(n)) => Some(n),
+        Some(&Value::Integer(n)) => n.as_signed().map(|n| n as f64),
+        _ => None,
+    }
+}
+
 pub fn get_bool_value(dict: &plist::Dictionary, key: &str) -> Option<bool> {
     match dict.get(key) {
         Some(Value::Boolean(b)) => Some(*b),
@@ -81,3 +92,87 @@ pub fn get_array_value(dict: &plist::Dictionary, key: &str) -> Option<Vec<Value>
         _ => None,
     }
 }
+
+/// Maximum nesting when resolving an archive, to not loop on cycles.
+const MAX_ARCHIVE_DEPTH: usize = 64;
+
+/// Decode the NSKeyedArchiver archive in `data` into a plist tree.
+/// NSArray are resolved into arrays, NSDictionary into dictionaries,
+/// NSDate into dates and NSString into strings. Other objects are
+/// dictionaries with the class name in the `$classname` key.
+pub fn unarchive(data: &[u8]) -> Option<Value> {
+    let archive = Value::from_reader(std::io::Cursor::new(data)).ok()?;
+    let archive = archive.as_dictionary()?;
+    if archive.get("$archiver").and_then(Value::as_string) != Some("NSKeyedArchiver") {
+        return None;
+    }
+    let objects = archive.get("$objects")?.as_array()?;
+    let root = archive.get("$top")?.as_dictionary()?.get("root")?;
+    resolve_archived(objects, root, 0)
+}
+
+/// Resolve `value` from the archive `objects`. `$null` is `None`.
+fn resolve_archived(objects: &[Value], value: &Value, depth: usize) -> Option<Value> {
+    if depth > MAX_ARCHIVE_DEPTH {
+        return None;
+    }
+    let object = match value {
+        Value::Uid(uid) => objects.get(uid.get() as usize)?,
+        _ => value,
+    };
+    let dict = match object {
+        Value::String(s) if s == "$null" => return None,
+        Value::Dictionary(dict) => dict,
+        _ => return Some(object.clone()),
+    };
+
+    let resolve_array = |array: &Value| -> Vec<Value> {
+        array
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|item| resolve_archived(objects, item, depth + 1))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    if let (Some(keys), Some(values)) = (dict.get("NS.keys"), dict.get("NS.objects")) {
+        let mut result = plist::Dictionary::new();
+        for (key, value) in resolve_array(keys).into_iter().zip(resolve_array(values)) {
+            if let Value::String(key) = key {
+                result.insert(key, value);
+            }
+        }
+        return Some(Value::Dictionary(result));
+    }
+    if let Some(values) = dict.get("NS.objects") {
+        return Some(Value::Array(resolve_array(values)));
+    }
+    if let Some(time) = dict.get("NS.time").and_then(Value::as_real) {
+        let date: SystemTime = crate::sqlutils::date_from_timestamp(time)?.into();
+        return Some(Value::Date(date.into()));
+    }
+    if let Some(string) = dict.get("NS.string") {
+        return resolve_archived(objects, string, depth + 1);
+    }
+
+    let mut result = plist::Dictionary::new();
+    for (key, value) in dict {
+        if key == "$class" {
+            let class_name = match value {
+                Value::Uid(uid) => objects.get(uid.get() as usize),
+                _ => None,
+            }
+            .and_then(Value::as_dictionary)
+            .and_then(|class| class.get("$classname"))
+            .cloned();
+            if let Some(class_name) = class_name {
+                result.insert("$classname".to_string(), class_name);
+            }
+        } else if let Some(value) = resolve_archived(objects, value, depth + 1) {
+            result.insert(key.clone(), value);
+        }
+    }
+    Some(Value::Dictionary(result))
+}
@@ -0,0 +1,798 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Smart album queries.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+
+use crate::audit::{Report, SkipReason};
+use crate::exif::ExifValue;
+use crate::iptc::IptcValue;
+use crate::keyword::KeywordAssignment;
+use crate::plutils::{get_array_value, get_int_value, get_str_value, unarchive, Value};
+use crate::version::Version;
+
+/// Class name of a group of queries.
+const MULTI_ITEM_QUERY: &str = "RKMultiItemQuery";
+/// Class name of a single criterion.
+const SINGLE_ITEM_QUERY: &str = "RKSingleItemQuery";
+/// `queryType` of the keyword queries.
+const KEYWORD_QUERY_TYPE: i64 = 4;
+
+/// How the queries of a group are combined. `queryMatchType`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchType {
+    /// Match any query.
+    Any,
+    /// Match all the queries.
+    All,
+}
+
+impl MatchType {
+    fn from_i64(value: i64) -> MatchType {
+        if value == 0 {
+            MatchType::Any
+        } else {
+            MatchType::All
+        }
+    }
+}
+
+/// Comparison of a criterion. `queryQualifier`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    /// Is equal.
+    Is,
+    /// Is greater or equal.
+    GreaterOrEqual,
+    /// Contains the text.
+    Contains,
+    /// Unknown qualifier.
+    Other(i64),
+}
+
+impl Comparison {
+    fn from_i64(value: i64) -> Comparison {
+        match value {
+            5 => Comparison::Is,
+            8 => Comparison::GreaterOrEqual,
+            23 => Comparison::Contains,
+            _ => Comparison::Other(value),
+        }
+    }
+
+    /// Compare the numbers `value` with `reference`.
+    fn compare_num(&self, value: f64, reference: f64) -> bool {
+        match self {
+            Comparison::Is => value == reference,
+            Comparison::GreaterOrEqual => value >= reference,
+            _ => false,
+        }
+    }
+
+    /// Compare the text `value` with `reference`, ignoring the case.
+    fn compare_text(&self, value: &str, reference: &str) -> bool {
+        let value = value.to_lowercase();
+        let reference = reference.to_lowercase();
+        match self {
+            Comparison::Is => value == reference,
+            Comparison::Contains => value.contains(&reference),
+            _ => false,
+        }
+    }
+}
+
+/// A criterion of a smart query.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Criterion {
+    /// A group of queries.
+    Group {
+        match_type: MatchType,
+        queries: Vec<SmartQuery>,
+    },
+    /// The rating.
+    Rating { comparison: Comparison, rating: i64 },
+    /// The flag.
+    Flagged {
+        comparison: Comparison,
+        flagged: bool,
+    },
+    /// The colour label index.
+    ColourLabel {
+        comparison: Comparison,
+        label: Option<i64>,
+    },
+    /// A keyword, by name.
+    Keyword {
+        comparison: Comparison,
+        keyword: String,
+    },
+    /// A date property between `start` and `end`, included.
+    DateRange {
+        property: String,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    },
+    /// A text property. `properties.any` is any text.
+    Text {
+        property: String,
+        comparison: Comparison,
+        text: String,
+    },
+    /// An EXIF property. The property name is without the
+    /// `exifProperties.` prefix.
+    Exif {
+        property: String,
+        comparison: Comparison,
+        value: Option<Value>,
+    },
+    /// A criterion that isn't understood.
+    Opaque {
+        class_name: Option<String>,
+        query_type: Option<i64>,
+        property: Option<String>,
+    },
+}
+
+/// A smart query, as found in the `UserQueryInfo` and `FilterInfo`
+/// of the albums.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmartQuery {
+    /// Whether the query is enabled. Disabled queries are ignored.
+    pub enabled: bool,
+    pub criterion: Criterion,
+}
+
+fn get_date(value: Option<&Value>) -> Option<DateTime<Utc>> {
+    match value {
+        Some(Value::Date(date)) => {
+            let t: SystemTime = (*date).into();
+            Some(t.into())
+        }
+        _ => None,
+    }
+}
+
+fn get_int(value: Option<&Value>) -> Option<i64> {
+    match value {
+        Some(Value::Integer(n)) => n.as_signed(),
+        Some(Value::Boolean(b)) => Some(*b as i64),
+        _ => None,
+    }
+}
+
+impl SmartQuery {
+    /// Decode the query from the archive `data`. `key` is the
+    /// property in the plist, for the audit.
+    pub(crate) fn from_archive(
+        data: &[u8],
+        key: &str,
+        auditor: &mut Option<&mut Report>,
+    ) -> Option<SmartQuery> {
+        let root = unarchive(data);
+        match root.as_ref().and_then(Value::as_dictionary) {
+            Some(dict) => {
+                let query = SmartQuery::from_dict(dict, key, auditor);
+                if let Some(ref mut report) = *auditor {
+                    report.parsed(key);
+                }
+                Some(query)
+            }
+            None => {
+                if let Some(ref mut report) = *auditor {
+                    report.skip(key, SkipReason::InvalidData);
+                }
+                None
+            }
+        }
+    }
+
+    /// Create the query from the dictionary `dict`. `key` is the
+    /// path of the query, for the audit.
+    fn from_dict(
+        dict: &plist::Dictionary,
+        key: &str,
+        auditor: &mut Option<&mut Report>,
+    ) -> SmartQuery {
+        let class_name = get_str_value(dict, "queryClassName");
+        let query_type = get_int_value(dict, "queryType");
+        let property = get_str_value(dict, "queryPropertyKey");
+        let comparison = Comparison::from_i64(get_int_value(dict, "queryQualifier").unwrap_or(0));
+        let first_value = dict.get("queryFirstValue");
+        let enabled = get_int(dict.get("queryIsEnabled")).unwrap_or(1) != 0;
+
+        let criterion = match (class_name.as_deref(), property.as_deref()) {
+            (Some(MULTI_ITEM_QUERY), _) => Criterion::Group {
+                match_type: MatchType::from_i64(get_int_value(dict, "queryMatchType").unwrap_or(1)),
+                queries: get_array_value(dict, "querySubqueries")
+                    .unwrap_or_default()
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, query)| {
+                        query.as_dictionary().map(|query| {
+                            SmartQuery::from_dict(
+                                query,
+                                &format!("{}.querySubqueries[{}]", key, idx),
+                                auditor,
+                            )
+                        })
+                    })
+                    .collect(),
+            },
+            (Some(SINGLE_ITEM_QUERY), Some("basicProperties.MainRating")) => Criterion::Rating {
+                comparison,
+                rating: get_int(first_value).unwrap_or(0),
+            },
+            (Some(SINGLE_ITEM_QUERY), Some("isFlagged")) => Criterion::Flagged {
+                comparison,
+                flagged: get_int(first_value).unwrap_or(1) != 0,
+            },
+            (Some(SINGLE_ITEM_QUERY), Some("basicProperties.ColorLabel")) => {
+                Criterion::ColourLabel {
+                    comparison,
+                    label: get_int(first_value),
+                }
+            }
+            (Some(SINGLE_ITEM_QUERY), _) if query_type == Some(KEYWORD_QUERY_TYPE) => {
+                Criterion::Keyword {
+                    comparison,
+                    keyword: first_value
+                        .and_then(Value::as_string)
+                        .unwrap_or_default()
+                        .to_string(),
+                }
+            }
+            (Some(SINGLE_ITEM_QUERY), Some(property)) if get_date(first_value).is_some() => {
+                Criterion::DateRange {
+                    property: property.to_string(),
+                    start: get_date(first_value),
+                    end: get_date(dict.get("querySecondValue")),
+                }
+            }
+            (Some(SINGLE_ITEM_QUERY), Some(property))
+                if property.starts_with("exifProperties.") =>
+            {
+                Criterion::Exif {
+                    property: property.trim_start_matches("exifProperties.").to_string(),
+                    comparison,
+                    value: first_value.cloned(),
+                }
+            }
+            (Some(SINGLE_ITEM_QUERY), Some(property))
+                if property == "properties.any"
+                    || property == "name"
+                    || property.starts_with("iptcProperties.") =>
+            {
+                Criterion::Text {
+                    property: property.to_string(),
+                    comparison,
+                    text: first_value
+                        .and_then(Value::as_string)
+                        .unwrap_or_default()
+                        .to_string(),
+                }
+            }
+            _ => {
+                if let Some(ref mut report) = *auditor {
+                    report.skip(key, SkipReason::UnknownProp);
+                }
+                Criterion::Opaque {
+                    class_name,
+                    query_type,
+                    property,
+                }
+            }
+        };
+
+        if let Some(Comparison::Other(_)) = criterion.comparison() {
+            if let Some(ref mut report) = *auditor {
+                report.skip(&format!("{}.queryQualifier", key), SkipReason::UnknownProp);
+            }
+        }
+
+        SmartQuery { enabled, criterion }
+    }
+}
+
+impl Criterion {
+    /// The comparison of the criterion, if it has one.
+    fn comparison(&self) -> Option<Comparison> {
+        match self {
+            Criterion::Rating { comparison, .. }
+            | Criterion::Flagged { comparison, .. }
+            | Criterion::ColourLabel { comparison, .. }
+            | Criterion::Keyword { comparison, .. }
+            | Criterion::Text { comparison, .. }
+            | Criterion::Exif { comparison, .. } => Some(*comparison),
+            _ => None,
+        }
+    }
+}
+
+impl SmartQuery {
+    /// Return true if the query has criteria that need the keywords.
+    pub fn uses_keywords(&self) -> bool {
+        match &self.criterion {
+            Criterion::Group { queries, .. } => queries.iter().any(SmartQuery::uses_keywords),
+            Criterion::Keyword { .. } => true,
+            Criterion::Text { property, .. } => property == "properties.any",
+            _ => false,
+        }
+    }
+
+    /// Return false if an enabled criterion has an unknown comparison.
+    /// The result of `matches()` is meaningless then.
+    pub fn is_evaluable(&self) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        match &self.criterion {
+            Criterion::Group { queries, .. } => queries.iter().all(SmartQuery::is_evaluable),
+            criterion => !matches!(criterion.comparison(), Some(Comparison::Other(_))),
+        }
+    }
+
+    /// Return true if `version`, with the `keywords`, matches the
+    /// query. A disabled query matches everything, and an opaque
+    /// criterion matches nothing. See `is_evaluable()`.
+    pub fn matches(&self, version: &Version, keywords: &[KeywordAssignment]) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        match &self.criterion {
+            Criterion::Group {
+                match_type,
+                queries,
+            } => {
+                let mut enabled = queries.iter().filter(|query| query.enabled).peekable();
+                if enabled.peek().is_none() {
+                    return true;
+                }
+                match match_type {
+                    MatchType::All => enabled.all(|query| query.matches(version, keywords)),
+                    MatchType::Any => enabled.any(|query| query.matches(version, keywords)),
+                }
+            }
+            Criterion::Rating { comparison, rating } => {
+                comparison.compare_num(version.rating.unwrap_or(0) as f64, *rating as f64)
+            }
+            Criterion::Flagged {
+                comparison,
+                flagged,
+            } => match comparison {
+                Comparison::Is => version.is_flagged.unwrap_or(false) == *flagged,
+                _ => false,
+            },
+            Criterion::ColourLabel { comparison, label } => {
+                // -1 is no label too.
+                let normalized = |label: Option<i64>| label.filter(|label| *label >= 0);
+                match comparison {
+                    Comparison::Is => normalized(version.colour_label_index) == normalized(*label),
+                    _ => false,
+                }
+            }
+            Criterion::Keyword {
+                comparison,
+                keyword,
+            } => keywords.iter().any(|assignment| {
+                assignment
+                    .path
+                    .last()
+                    .map(|name| comparison.compare_text(name, keyword))
+                    .unwrap_or(false)
+            }),
+            Criterion::DateRange {
+                property,
+                start,
+                end,
+            } => {
+                let date = if property.ends_with("ImageDate") {
+                    version.image_date
+                } else if property.ends_with("createDate") {
+                    version.create_date
+                } else {
+                    None
+                };
+                date.map(|date| {
+                    start.map(|start| date >= start).unwrap_or(true)
+                        && end.map(|end| date <= end).unwrap_or(true)
+                })
+                .unwrap_or(false)
+            }
+            Criterion::Text {
+                property,
+                comparison,
+                text,
+            } => SmartQuery::text_values(version, keywords, property)
+                .iter()
+                .any(|value| comparison.compare_text(value, text)),
+            Criterion::Exif {
+                property,
+                comparison,
+                value,
+            } => {
+                let exif = version
+                    .exif
+                    .as_ref()
+                    .and_then(|exif| exif.bag.get(property));
+                match (exif, value) {
+                    (Some(ExifValue::Str(exif)), Some(Value::String(value))) => {
+                        comparison.compare_text(exif, value)
+                    }
+                    (Some(ExifValue::Int(exif)), Some(value)) => value
+                        .as_real()
+                        .or_else(|| get_int(Some(value)).map(|n| n as f64))
+                        .map(|value| comparison.compare_num(*exif as f64, value))
+                        .unwrap_or(false),
+                    (Some(ExifValue::Real(exif)), Some(value)) => value
+                        .as_real()
+                        .or_else(|| get_int(Some(value)).map(|n| n as f64))
+                        .map(|value| comparison.compare_num(*exif, value))
+                        .unwrap_or(false),
+                    _ => false,
+                }
+            }
+            Criterion::Opaque { .. } => false,
+        }
+    }
+
+    /// The text values of `property` in `version`. `properties.any`
+    /// is the name, the file name, the IPTC properties and the keywords.
+    fn text_values(
+        version: &Version,
+        keywords: &[KeywordAssignment],
+        property: &str,
+    ) -> Vec<String> {
+        let iptc = |key: Option<&str>| -> Vec<String> {
+            version
+                .iptc
+                .as_ref()
+                .map(|iptc| {
+                    iptc.bag
+                        .iter()
+                        .filter(|(k, _)| key.map(|key| key == k.as_str()).unwrap_or(true))
+                        .filter_map(|(_, value)| match value {
+                            IptcValue::Str(s) => Some(s.clone()),
+                            IptcValue::None => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        if property == "properties.any" {
+            let mut values: Vec<String> = [&version.name, &version.file_name]
+                .iter()
+                .filter_map(|value| (*value).clone())
+                .collect();
+            values.append(&mut iptc(None));
+            values.extend(keywords.iter().flat_map(|kw| kw.path.last().cloned()));
+            values
+        } else if property == "name" {
+            version.name.iter().cloned().collect()
+        } else if let Some(key) = property.strip_prefix("iptcProperties.") {
+            iptc(Some(key))
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_from_archive() {
+    use crate::plutils::{get_data_value, parse_plist};
+    use crate::testutils;
+
+    let plist = parse_plist(testutils::get_test_file_path(
+        "gOnttfpzQoOxcwLpFS9DQg.apalbum",
+    ))
+    .unwrap();
+    let data = get_data_value(plist.as_dictionary().unwrap(), "FilterInfo").unwrap();
+
+    let mut report = Report::new();
+    let query = SmartQuery::from_archive(&data, "FilterInfo", &mut Some(&mut report)).unwrap();
+    assert!(query.enabled);
+    let queries = match query.criterion {
+        Criterion::Group {
+            match_type: MatchType::All,
+            queries,
+        } => queries,
+        _ => unreachable!("Not a match all group"),
+    };
+    assert_eq!(queries.len(), 5);
+    assert_eq!(
+        queries[0],
+        SmartQuery {
+            enabled: true,
+            criterion: Criterion::Rating {
+                comparison: Comparison::GreaterOrEqual,
+                rating: 0
+            }
+        }
+    );
+    assert_eq!(
+        queries[1].criterion,
+        Criterion::Flagged {
+            comparison: Comparison::Is,
+            flagged: true
+        }
+    );
+    assert!(!queries[1].enabled);
+    assert_eq!(
+        queries[2].criterion,
+        Criterion::ColourLabel {
+            comparison: Comparison::Is,
+            label: None
+        }
+    );
+    assert_eq!(
+        queries[3].criterion,
+        Criterion::Text {
+            property: "properties.any".to_string(),
+            comparison: Comparison::Contains,
+            text: String::new()
+        }
+    );
+    assert!(matches!(
+        queries[4].criterion,
+        Criterion::Group {
+            match_type: MatchType::Any,
+            ..
+        }
+    ));
+    assert_eq!(report.skipped_count(), 0);
+
+    let mut report = Report::new();
+    let mut dict = plist::Dictionary::new();
+    dict.insert("queryClassName".to_string(), Value::from("RKUnknownQuery"));
+    dict.insert("queryType".to_string(), Value::from(42));
+    let query = SmartQuery::from_dict(&dict, "UserQueryInfo", &mut Some(&mut report));
+    assert_eq!(
+        query.criterion,
+        Criterion::Opaque {
+            class_name: Some("RKUnknownQuery".to_string()),
+            query_type: Some(42),
+            property: None
+        }
+    );
+    assert!(report.get_skipped().contains_key("UserQueryInfo"));
+
+    // The keyword criterion is found by its query type, not by the
+    // property name.
+    let mut report = Report::new();
+    let mut dict = plist::Dictionary::new();
+    dict.insert("queryClassName".to_string(), Value::from(SINGLE_ITEM_QUERY));
+    dict.insert("queryType".to_string(), Value::from(KEYWORD_QUERY_TYPE));
+    dict.insert("queryQualifier".to_string(), Value::from(5));
+    dict.insert("queryFirstValue".to_string(), Value::from("Paris"));
+    let query = SmartQuery::from_dict(&dict, "UserQueryInfo", &mut Some(&mut report));
+    assert_eq!(
+        query.criterion,
+        Criterion::Keyword {
+            comparison: Comparison::Is,
+            keyword: "Paris".to_string()
+        }
+    );
+    assert_eq!(report.skipped_count(), 0);
+
+    let mut dict = plist::Dictionary::new();
+    dict.insert("queryClassName".to_string(), Value::from(SINGLE_ITEM_QUERY));
+    dict.insert("queryType".to_string(), Value::from(42));
+    dict.insert(
+        "queryPropertyKey".to_string(),
+        Value::from("noKeywordsProperty"),
+    );
+    let query = SmartQuery::from_dict(&dict, "UserQueryInfo", &mut Some(&mut report));
+    assert!(matches!(query.criterion, Criterion::Opaque { .. }));
+    assert!(report.get_skipped().contains_key("UserQueryInfo"));
+
+    let mut report = Report::new();
+    assert!(
+        SmartQuery::from_archive(b"garbage", "UserQueryInfo", &mut Some(&mut report)).is_none()
+    );
+    assert_eq!(report.skipped_count(), 1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_matches() {
+    use crate::testutils;
+    use crate::PlistLoadable;
+
+    let version =
+        Version::from_path(testutils::get_test_file_path("Version-0.apversion"), None).unwrap();
+    let keywords = vec![KeywordAssignment {
+        keyword_uuid: "toronto".to_string(),
+        path: vec!["canada".to_string(), "toronto".to_string()],
+    }];
+    let query = |criterion| SmartQuery {
+        enabled: true,
+        criterion,
+    };
+
+    let rating = |rating| {
+        query(Criterion::Rating {
+            comparison: Comparison::GreaterOrEqual,
+            rating,
+        })
+    };
+    assert!(rating(0).matches(&version, &keywords));
+    assert!(!rating(1).matches(&version, &keywords));
+
+    let flagged = query(Criterion::Flagged {
+        comparison: Comparison::Is,
+        flagged: true,
+    });
+    assert!(!flagged.matches(&version, &keywords));
+
+    // The version has -1 for no label.
+    let label = |label| {
+        query(Criterion::ColourLabel {
+            comparison: Comparison::Is,
+            label,
+        })
+    };
+    assert!(label(None).matches(&version, &keywords));
+    assert!(label(Some(-1)).matches(&version, &keywords));
+    assert!(!label(Some(2)).matches(&version, &keywords));
+
+    let keyword = query(Criterion::Keyword {
+        comparison: Comparison::Is,
+        keyword: "Toronto".to_string(),
+    });
+    assert!(keyword.matches(&version, &keywords));
+    assert!(!keyword.matches(&version, &[]));
+    assert!(keyword.uses_keywords());
+
+    let text = query(Criterion::Text {
+        property: "properties.any".to_string(),
+        comparison: Comparison::Contains,
+        text: "gatineau".to_string(),
+    });
+    assert!(text.matches(&version, &[]));
+
+    let exif = query(Criterion::Exif {
+        property: "LensModel".to_string(),
+        comparison: Comparison::Contains,
+        value: Some(Value::from("105")),
+    });
+    assert!(exif.matches(&version, &[]));
+
+    let group = |match_type| {
+        query(Criterion::Group {
+            match_type,
+            queries: vec![rating(0), flagged.clone()],
+        })
+    };
+    assert!(!group(MatchType::All).matches(&version, &keywords));
+    assert!(group(MatchType::Any).matches(&version, &keywords));
+
+    let opaque = query(Criterion::Opaque {
+        class_name: None,
+        query_type: None,
+        property: None,
+    });
+    assert!(!opaque.matches(&version, &keywords));
+    let disabled = SmartQuery {
+        enabled: false,
+        ..opaque
+    };
+    assert!(disabled.matches(&version, &keywords));
+}
+
+#[cfg(test)]
+#[test]
+fn test_query_unknown_comparison() {
+    let mut report = Report::new();
+    let mut dict = plist::Dictionary::new();
+    dict.insert("queryClassName".to_string(), Value::from(SINGLE_ITEM_QUERY));
+    dict.insert(
+        "queryPropertyKey".to_string(),
+        Value::from("basicProperties.MainRating"),
+    );
+    dict.insert("queryQualifier".to_string(), Value::from(99));
+    dict.insert("queryFirstValue".to_string(), Value::from(3));
+    let query = SmartQuery::from_dict(&dict, "UserQueryInfo", &mut Some(&mut report));
+    assert_eq!(
+        query.criterion,
+        Criterion::Rating {
+            comparison: Comparison::Other(99),
+            rating: 3
+        }
+    );
+    assert!(report
+        .get_skipped()
+        .contains_key("UserQueryInfo.queryQualifier"));
+    assert!(!query.is_evaluable());
+
+    let group = |enabled| SmartQuery {
+        enabled: true,
+        criterion: Criterion::Group {
+            match_type: MatchType::Any,
+            queries: vec![SmartQuery {
+                enabled,
+                criterion: query.criterion.clone(),
+            }],
+        },
+    };
+    assert!(!group(true).is_evaluable());
+    // Disabled queries are ignored.
+    assert!(group(false).is_evaluable());
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolve_album_content() {
+    use crate::testutils;
+    use crate::{plutils, Library, LoadPolicy, PROGRESS_NONE};
+    use std::fs;
+
+    let path = testutils::create_test_db_library("test_resolve_album_content");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_folders(PROGRESS_NONE).is_ok());
+    assert!(library.load_albums(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let both = vec![
+        "BF6nuoBnTumzoXyexdmXlw".to_string(),
+        "VF%CkiTKQy+h53Oyr7KCOA".to_string(),
+    ];
+    assert_eq!(
+        library
+            .resolve_album_content("x6yNun58SB2sImfCarTJHA")
+            .unwrap(),
+        Some(vec!["BF6nuoBnTumzoXyexdmXlw".to_string()])
+    );
+    assert_eq!(
+        library
+            .resolve_album_content("J0+f3AmESPer4GHGv4BgAQ")
+            .unwrap(),
+        Some(both.clone())
+    );
+    assert_eq!(library.resolve_album_content("unknown").unwrap(), None);
+
+    // A smart album, in the project, with a rating >= 0 query.
+    let filter = plutils::parse_plist(testutils::get_test_file_path(
+        "gOnttfpzQoOxcwLpFS9DQg.apalbum",
+    ))
+    .unwrap()
+    .as_dictionary()
+    .and_then(|dict| plutils::get_data_value(dict, "FilterInfo"))
+    .unwrap();
+    let mut info = plist::Dictionary::new();
+    info.insert("uuid".to_string(), Value::from("smartsmartsmartsmartAA"));
+    info.insert("albumSubclass".to_string(), Value::from(2));
+    info.insert(
+        "queryFolderUuid".to_string(),
+        Value::from("evHgvM2oQ3GR0j6gEMnNTQ"),
+    );
+    let mut dict = plist::Dictionary::new();
+    dict.insert("InfoDictionary".to_string(), Value::Dictionary(info));
+    dict.insert("UserQueryInfo".to_string(), Value::Data(filter));
+    let albums = path.join("Database").join("Albums");
+    fs::create_dir_all(&albums).unwrap();
+    Value::Dictionary(dict)
+        .to_file_binary(albums.join("smartsmartsmartsmartAA.apalbum"))
+        .unwrap();
+
+    let mut library = Library::new(&path);
+    assert!(library.load_folders(PROGRESS_NONE).is_ok());
+    assert!(library.load_albums(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+    assert_eq!(
+        library
+            .resolve_album_content("smartsmartsmartsmartAA")
+            .unwrap(),
+        Some(both)
+    );
+}
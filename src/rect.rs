@@ -0,0 +1,149 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Rectangles in the images, for the faces and the crop.
+
+/// A rectangle normalized to the image size, the origin at the top
+/// left.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    /// Build the rectangle from the top left and bottom right
+    /// corners, normalized with the origin at the bottom left like
+    /// Aperture stores them.
+    pub(crate) fn from_corners(top_left: (f64, f64), bottom_right: (f64, f64)) -> Rect {
+        Rect {
+            x: top_left.0,
+            y: 1.0 - top_left.1,
+            width: bottom_right.0 - top_left.0,
+            height: top_left.1 - bottom_right.1,
+        }
+    }
+
+    /// The centre.
+    pub fn centre(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Return the rectangle relative to `crop`, both normalized to
+    /// the same image. The part outside of `crop` is cut, `None` if
+    /// nothing is left.
+    pub fn crop(&self, crop: &Rect) -> Option<Rect> {
+        if crop.width <= 0.0 || crop.height <= 0.0 {
+            return None;
+        }
+        let left = ((self.x - crop.x) / crop.width).max(0.0);
+        let top = ((self.y - crop.y) / crop.height).max(0.0);
+        let right = ((self.x + self.width - crop.x) / crop.width).min(1.0);
+        let bottom = ((self.y + self.height - crop.y) / crop.height).min(1.0);
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(Rect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    /// Return the rectangle in the image rotated clockwise by
+    /// `degrees`. Only the multiples of 90 are supported, the others
+    /// are rounded to the nearest.
+    pub fn rotate(&self, degrees: i64) -> Rect {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        match ((degrees as f64 / 90.0).round() as i64).rem_euclid(4) {
+            1 => Rect {
+                x: 1.0 - y - height,
+                y: x,
+                width: height,
+                height: width,
+            },
+            2 => Rect {
+                x: 1.0 - x - width,
+                y: 1.0 - y - height,
+                width,
+                height,
+            },
+            3 => Rect {
+                x: y,
+                y: 1.0 - x - width,
+                width: height,
+                height: width,
+            },
+            _ => *self,
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_rect() {
+    let rect = Rect::from_corners((0.25, 0.75), (0.5, 0.25));
+    assert_eq!(
+        rect,
+        Rect {
+            x: 0.25,
+            y: 0.25,
+            width: 0.25,
+            height: 0.5
+        }
+    );
+    assert_eq!(rect.centre(), (0.375, 0.5));
+
+    let crop = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: 0.5,
+        height: 0.5,
+    };
+    assert_eq!(
+        rect.crop(&crop),
+        Some(Rect {
+            x: 0.5,
+            y: 0.5,
+            width: 0.5,
+            height: 0.5
+        })
+    );
+    assert!(Rect::from_corners((0.75, 1.0), (1.0, 0.0))
+        .crop(&crop)
+        .is_none());
+
+    assert_eq!(rect.rotate(0), rect);
+    assert_eq!(
+        rect.rotate(90),
+        Rect {
+            x: 0.25,
+            y: 0.25,
+            width: 0.5,
+            height: 0.25
+        }
+    );
+    assert_eq!(
+        rect.rotate(180),
+        Rect {
+            x: 0.5,
+            y: 0.25,
+            width: 0.25,
+            height: 0.5
+        }
+    );
+    assert_eq!(rect.rotate(-90), rect.rotate(270));
+    assert_eq!(rect.rotate(90).rotate(270), rect);
+}
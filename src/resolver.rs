@@ -0,0 +1,233 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Resolve the master files to paths on this host. Referenced
+//! masters are on volumes that were mounted in `/Volumes` on the Mac,
+//! they can be mounted elsewhere.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::master::Master;
+use crate::volume::Volume;
+use crate::{Error, Result};
+
+/// Where the volumes are mounted on macOS.
+const VOLUMES_ROOT: &str = "/Volumes";
+
+/// A master file resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedPath {
+    /// The absolute path of the file.
+    pub path: PathBuf,
+    /// Whether the file exists.
+    pub exists: bool,
+}
+
+/// Resolve the paths of the master files with the mount points of
+/// the volumes.
+#[derive(Clone, Debug)]
+pub struct PathResolver {
+    /// The library bundle, for the managed masters.
+    library_root: PathBuf,
+    /// Where the unmapped volumes are mounted.
+    volumes_root: PathBuf,
+    /// The mount points by volume name.
+    by_name: HashMap<String, PathBuf>,
+    /// The mount points by disk uuid. Win over the names.
+    by_disk_uuid: HashMap<String, PathBuf>,
+}
+
+impl PathResolver {
+    /// New resolver for the library bundle at `library_root`, made
+    /// absolute. The volumes are in `/Volumes` until mapped.
+    pub fn new<P: AsRef<Path>>(library_root: P) -> PathResolver {
+        let library_root = library_root.as_ref();
+        PathResolver {
+            library_root: std::path::absolute(library_root)
+                .unwrap_or_else(|_| library_root.to_path_buf()),
+            volumes_root: PathBuf::from(VOLUMES_ROOT),
+            by_name: HashMap::new(),
+            by_disk_uuid: HashMap::new(),
+        }
+    }
+
+    /// Return the library bundle, absolute.
+    pub fn library_root(&self) -> &Path {
+        &self.library_root
+    }
+
+    /// Set the directory the unmapped volumes are mounted in.
+    pub fn set_volumes_root<P: AsRef<Path>>(&mut self, root: P) {
+        self.volumes_root = root.as_ref().to_path_buf();
+    }
+
+    /// Map the volume `name` to `mount_point`.
+    pub fn map_volume_name<P: AsRef<Path>>(&mut self, name: &str, mount_point: P) {
+        self.by_name
+            .insert(name.to_string(), mount_point.as_ref().to_path_buf());
+    }
+
+    /// Map the volume with the disk `uuid` to `mount_point`.
+    pub fn map_disk_uuid<P: AsRef<Path>>(&mut self, uuid: &str, mount_point: P) {
+        self.by_disk_uuid
+            .insert(uuid.to_string(), mount_point.as_ref().to_path_buf());
+    }
+
+    /// Add the mappings from the file at `path`. Each line is
+    /// `<volume name> = <mount point>` or
+    /// `uuid:<disk uuid> = <mount point>`. Empty lines and lines
+    /// starting with `#` are ignored.
+    pub fn load_mappings<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        self.parse_mappings(&content)
+            .ok_or_else(|| Error::InvalidData(path.to_path_buf()))
+    }
+
+    /// Add the mappings in `content`. Return `None` if a line is
+    /// invalid.
+    fn parse_mappings(&mut self, content: &str) -> Option<()> {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, mount_point) = line.split_once('=')?;
+            let (key, mount_point) = (key.trim(), mount_point.trim());
+            if key.is_empty() || mount_point.is_empty() {
+                return None;
+            }
+            match key.strip_prefix("uuid:") {
+                Some(uuid) => self.map_disk_uuid(uuid.trim(), mount_point),
+                None => self.map_volume_name(key, mount_point),
+            }
+        }
+        Some(())
+    }
+
+    /// Return the mount point of the volume `name` on the disk
+    /// `disk_uuid`.
+    fn mount_point(&self, name: Option<&str>, disk_uuid: Option<&str>) -> PathBuf {
+        disk_uuid
+            .and_then(|uuid| self.by_disk_uuid.get(uuid))
+            .or_else(|| name.and_then(|name| self.by_name.get(name)))
+            .cloned()
+            .unwrap_or_else(|| self.volumes_root.join(name.unwrap_or_default()))
+    }
+
+    /// Return the path of the file of `master`, without checking it
+    /// exists. `volume` is the volume of referenced masters, `None`
+    /// for the masters managed in the library.
+    pub fn master_path(&self, master: &Master, volume: Option<&Volume>) -> Option<PathBuf> {
+        let image_path = master.image_path.as_ref()?;
+        let base = match volume {
+            Some(volume) => {
+                self.mount_point(volume.volume_name.as_deref(), volume.disk_uuid.as_deref())
+            }
+            None => self.library_root.join("Masters"),
+        };
+        Some(base.join(image_path.trim_start_matches('/')))
+    }
+
+    /// Resolve the file of `master`. See `master_path()`.
+    pub fn resolve(&self, master: &Master, volume: Option<&Volume>) -> Option<ResolvedPath> {
+        self.master_path(master, volume).map(|path| ResolvedPath {
+            exists: path.exists(),
+            path,
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_path_resolver() {
+    use crate::PlistLoadable;
+
+    let mut resolver = PathResolver::new("/data/Aperture Library.aplibrary");
+    assert!(resolver
+        .parse_mappings("# Drives\n\nPhotos = /mnt/photos\nuuid:3D1B = /media/archive\n")
+        .is_some());
+    assert!(resolver.parse_mappings("Photos /mnt/photos").is_none());
+    assert!(resolver.parse_mappings("= /mnt/photos").is_none());
+
+    assert_eq!(
+        resolver.mount_point(Some("Photos"), Some("0000")),
+        Path::new("/mnt/photos")
+    );
+    // The disk uuid wins over the name.
+    assert_eq!(
+        resolver.mount_point(Some("Photos"), Some("3D1B")),
+        Path::new("/media/archive")
+    );
+    assert_eq!(
+        resolver.mount_point(Some("Backup"), None),
+        Path::new("/Volumes/Backup")
+    );
+    resolver.set_volumes_root("/media/user");
+    assert_eq!(
+        resolver.mount_point(Some("Backup"), None),
+        Path::new("/media/user/Backup")
+    );
+
+    let master = Master::from_path(
+        crate::testutils::get_test_file_path("Master.apmaster"),
+        None,
+    )
+    .unwrap();
+    let image_path = master.image_path.clone().unwrap();
+    let resolved = resolver.resolve(&master, None).unwrap();
+    assert_eq!(
+        resolved.path,
+        Path::new("/data/Aperture Library.aplibrary/Masters")
+            .join(image_path.trim_start_matches('/'))
+    );
+    assert!(!resolved.exists);
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolve_master_file() {
+    use crate::testutils;
+    use crate::{Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_resolve_master_file");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+
+    let mut resolver = library.path_resolver();
+    resolver.map_volume_name("Photos", path.join("photos"));
+    // The volume isn't loaded.
+    assert!(library
+        .resolve_master_file("JpLq7STrRMmgm5YZTm6IzA")
+        .is_none());
+
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    let file = path.join("photos/2011/img_8826.cr2");
+    let resolved = library
+        .resolve_master_file("JpLq7STrRMmgm5YZTm6IzA")
+        .unwrap();
+    assert_eq!(
+        resolved.path,
+        Path::new("/Volumes/Photos/2011/img_8826.cr2")
+    );
+
+    // The master is found once the volume is mapped.
+    library.set_path_resolver(resolver);
+    let resolved = library
+        .resolve_master_file("JpLq7STrRMmgm5YZTm6IzA")
+        .unwrap();
+    assert_eq!(resolved.path, file);
+    assert!(!resolved.exists);
+
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(&file, "raw").unwrap();
+    let resolved = library
+        .resolve_master_file("JpLq7STrRMmgm5YZTm6IzA")
+        .unwrap();
+    assert!(resolved.exists);
+}
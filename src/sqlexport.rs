@@ -0,0 +1,471 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Export the library into a sqlite database with a stable schema,
+//! unlike `Library.apdb` whose schema depends on the Aperture version.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use rusqlite::types::Value as SqlValue;
+
+use crate::exif::ExifValue;
+use crate::iptc::IptcValue;
+use crate::keyword::Keyword;
+use crate::library::Library;
+use crate::notes::NotesProperties;
+use crate::store::Wrapper;
+use crate::{AplibObject, Result};
+
+/// The schema of the exported database. The objects are keyed by
+/// uuid. The dates are RFC 3339 strings in UTC and the booleans
+/// 0 or 1. The references aren't enforced as only the loaded objects
+/// are exported.
+pub const SCHEMA: &str = "
+-- Volumes the referenced masters are on.
+CREATE TABLE volumes (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  name TEXT,
+  disk_uuid TEXT
+);
+-- Folders and projects. folder_type is 1 for a folder, 2 for a project.
+CREATE TABLE folders (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  parent_uuid TEXT,
+  folder_type INTEGER,
+  name TEXT,
+  path TEXT,
+  implicit_album_uuid TEXT,
+  poster_version_uuid TEXT,
+  colour_label_index INTEGER,
+  create_date TEXT,
+  is_hidden INTEGER,
+  is_favourite INTEGER,
+  is_in_trash INTEGER
+);
+-- Albums. subclass is 1 for implicit, 2 for smart, 3 for user albums.
+CREATE TABLE albums (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  folder_uuid TEXT,
+  subclass INTEGER,
+  album_type INTEGER,
+  name TEXT,
+  query_folder_uuid TEXT,
+  colour_label_index INTEGER,
+  create_date TEXT,
+  is_hidden INTEGER,
+  is_favourite INTEGER,
+  is_in_trash INTEGER
+);
+-- Content of the user albums, in album order.
+CREATE TABLE album_versions (
+  album_uuid TEXT NOT NULL,
+  version_uuid TEXT NOT NULL,
+  position INTEGER NOT NULL,
+  PRIMARY KEY (album_uuid, position)
+);
+-- Master files. resolved_path is the absolute path of the file, with
+-- the path resolver of the library.
+CREATE TABLE masters (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  project_uuid TEXT,
+  volume_uuid TEXT,
+  alternate_master_uuid TEXT,
+  original_version_uuid TEXT,
+  name TEXT,
+  file_name TEXT,
+  image_path TEXT,
+  resolved_path TEXT,
+  master_type TEXT,
+  subtype TEXT,
+  file_size INTEGER,
+  is_reference INTEGER,
+  is_missing INTEGER,
+  is_in_trash INTEGER,
+  create_date TEXT,
+  image_date TEXT
+);
+-- Versions of the masters.
+CREATE TABLE versions (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  master_uuid TEXT,
+  project_uuid TEXT,
+  raw_master_uuid TEXT,
+  nonraw_master_uuid TEXT,
+  version_number INTEGER,
+  name TEXT,
+  file_name TEXT,
+  rating INTEGER,
+  colour_label_index INTEGER,
+  rotation INTEGER,
+  timezone_name TEXT,
+  create_date TEXT,
+  image_date TEXT,
+  is_original INTEGER,
+  is_flagged INTEGER,
+  is_hidden INTEGER,
+  is_in_trash INTEGER
+);
+-- Keywords. path is the names from the root joined by '|'.
+CREATE TABLE keywords (
+  uuid TEXT PRIMARY KEY,
+  model_id INTEGER,
+  parent_uuid TEXT,
+  name TEXT,
+  path TEXT
+);
+-- Keywords assigned to the versions.
+CREATE TABLE version_keywords (
+  version_uuid TEXT NOT NULL,
+  keyword_uuid TEXT NOT NULL,
+  PRIMARY KEY (version_uuid, keyword_uuid)
+);
+-- Notes of the folders and masters. owner_uuid is the object
+-- that has the note.
+CREATE TABLE notes (
+  uuid TEXT,
+  owner_uuid TEXT NOT NULL,
+  attached_to_uuid TEXT,
+  property_key TEXT,
+  note TEXT,
+  create_date TEXT
+);
+-- EXIF properties of the versions, by Aperture key. value is an
+-- integer, a real or a text.
+CREATE TABLE exif (
+  version_uuid TEXT NOT NULL,
+  key TEXT NOT NULL,
+  value,
+  PRIMARY KEY (version_uuid, key)
+);
+-- IPTC properties of the versions, by Aperture key.
+CREATE TABLE iptc (
+  version_uuid TEXT NOT NULL,
+  key TEXT NOT NULL,
+  value TEXT,
+  PRIMARY KEY (version_uuid, key)
+);
+";
+
+fn date_text(date: &Option<DateTime<Utc>>) -> Option<String> {
+    date.as_ref().map(DateTime::to_rfc3339)
+}
+
+/// The objects of the library in `uuids` that `filter` accepts,
+/// sorted by uuid.
+fn sorted_objects<'a, T, F>(
+    library: &'a Library,
+    uuids: &'a std::collections::HashSet<String>,
+    filter: F,
+) -> Vec<&'a T>
+where
+    F: Fn(&'a Wrapper) -> Option<&'a T>,
+{
+    let mut uuids: Vec<&String> = uuids.iter().collect();
+    uuids.sort();
+    uuids
+        .into_iter()
+        .filter_map(|uuid| library.get(uuid).and_then(&filter))
+        .collect()
+}
+
+fn insert_notes(
+    tx: &rusqlite::Transaction,
+    owner: &Option<String>,
+    notes: &Option<Vec<NotesProperties>>,
+) -> Result<()> {
+    let (Some(owner), Some(notes)) = (owner, notes) else {
+        return Ok(());
+    };
+    let mut stmt = tx.prepare_cached("INSERT INTO notes VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+    for note in notes {
+        stmt.execute(params![
+            note.uuid,
+            owner,
+            note.attached_to_uuid,
+            note.property_key,
+            note.note,
+            date_text(&note.create_date),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Write the objects loaded in `library` into `conn`, creating the
+/// tables of `SCHEMA`.
+pub(crate) fn export(library: &Library, conn: &mut rusqlite::Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+
+    let volumes = sorted_objects(library, library.volumes(), |object| match object {
+        Wrapper::Volume(volume) => Some(volume.as_ref()),
+        _ => None,
+    });
+    for volume in volumes {
+        tx.execute(
+            "INSERT INTO volumes VALUES (?1, ?2, ?3, ?4)",
+            params![
+                volume.uuid(),
+                volume.model_id(),
+                volume.volume_name,
+                volume.disk_uuid
+            ],
+        )?;
+    }
+
+    let folders = sorted_objects(library, library.folders(), |object| match object {
+        Wrapper::Folder(folder) => Some(folder.as_ref()),
+        _ => None,
+    });
+    for folder in folders {
+        tx.execute(
+            "INSERT INTO folders VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                folder.uuid(),
+                folder.model_id(),
+                folder.parent(),
+                folder.folder_type.map(|folder_type| folder_type as u32),
+                folder.name,
+                folder.path,
+                folder.implicit_album_uuid,
+                folder.poster_version_uuid,
+                folder.colour_label_index,
+                date_text(&folder.create_date),
+                folder.is_hidden,
+                folder.is_favourite,
+                folder.is_in_trash,
+            ],
+        )?;
+        insert_notes(&tx, folder.uuid(), &folder.notes)?;
+    }
+
+    let albums = sorted_objects(library, library.albums(), |object| match object {
+        Wrapper::Album(album) => Some(album.as_ref()),
+        _ => None,
+    });
+    for album in albums {
+        tx.execute(
+            "INSERT INTO albums VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                album.uuid(),
+                album.model_id(),
+                album.parent(),
+                album.subclass.map(|subclass| subclass as u32),
+                album.album_type,
+                album.name,
+                album.query_folder_uuid,
+                album.colour_label_index,
+                date_text(&album.create_date),
+                album.is_hidden,
+                album.is_favourite,
+                album.is_in_trash,
+            ],
+        )?;
+        if let (Some(uuid), Some(content)) = (album.uuid(), &album.content) {
+            for (position, version_uuid) in content.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO album_versions VALUES (?1, ?2, ?3)",
+                    params![uuid, version_uuid, position as i64],
+                )?;
+            }
+        }
+    }
+
+    let keywords = sorted_objects(library, library.keywords(), |object| match object {
+        Wrapper::Keyword(keyword) => Some(keyword.as_ref()),
+        _ => None,
+    });
+    for keyword in &keywords {
+        tx.execute(
+            "INSERT INTO keywords VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                keyword.uuid(),
+                keyword.model_id(),
+                keyword.parent(),
+                keyword.name,
+                keyword
+                    .uuid()
+                    .as_ref()
+                    .and_then(|uuid| library.keyword_path(uuid)),
+            ],
+        )?;
+    }
+    let paths = Keyword::paths(keywords);
+    let by_path: HashMap<&Vec<String>, &String> =
+        paths.iter().map(|(uuid, path)| (path, uuid)).collect();
+
+    let masters = sorted_objects(library, library.masters(), |object| match object {
+        Wrapper::Master(master) => Some(master.as_ref()),
+        _ => None,
+    });
+    for master in masters {
+        tx.execute(
+            "INSERT INTO masters VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, \
+             ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            params![
+                master.uuid(),
+                master.model_id(),
+                master.parent(),
+                master.file_volume_uuid,
+                master.alternate_master,
+                master.original_version_uuid,
+                master.name,
+                master.filename,
+                master.image_path,
+                master
+                    .uuid()
+                    .as_ref()
+                    .and_then(|uuid| library.resolve_master_file(uuid))
+                    .map(|resolved| resolved.path.to_string_lossy().into_owned()),
+                master.master_type,
+                master.subtype,
+                master.file_size,
+                master.is_reference,
+                master.is_missing,
+                master.is_in_trash,
+                date_text(&master.create_date),
+                date_text(&master.image_date),
+            ],
+        )?;
+        insert_notes(&tx, master.uuid(), &master.notes)?;
+    }
+
+    let versions = sorted_objects(library, library.versions(), |object| match object {
+        Wrapper::Version(version) => Some(version.as_ref()),
+        _ => None,
+    });
+    for version in versions {
+        tx.execute(
+            "INSERT INTO versions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, \
+             ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+            params![
+                version.uuid(),
+                version.model_id(),
+                version.master_uuid,
+                version.project_uuid,
+                version.raw_master_uuid,
+                version.nonraw_master_uuid,
+                version.version_number,
+                version.name,
+                version.file_name,
+                version.rating,
+                version.colour_label_index,
+                version.rotation,
+                version.timezone_name,
+                date_text(&version.create_date),
+                date_text(&version.image_date),
+                version.is_original,
+                version.is_flagged,
+                version.is_hidden,
+                version.is_in_trash,
+            ],
+        )?;
+        let uuid = match version.uuid() {
+            Some(uuid) => uuid,
+            None => continue,
+        };
+        for path in version.keyword_paths() {
+            if let Some(keyword_uuid) = by_path.get(&path) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO version_keywords VALUES (?1, ?2)",
+                    params![uuid, keyword_uuid],
+                )?;
+            }
+        }
+        if let Some(exif) = &version.exif {
+            for (key, value) in &exif.bag {
+                let value = match value {
+                    ExifValue::None => SqlValue::Null,
+                    ExifValue::Int(value) => SqlValue::Integer(*value),
+                    ExifValue::Real(value) => SqlValue::Real(*value),
+                    ExifValue::Str(value) => SqlValue::Text(value.clone()),
+                    ExifValue::Date(value) => SqlValue::Text(value.to_rfc3339()),
+                };
+                tx.execute(
+                    "INSERT INTO exif VALUES (?1, ?2, ?3)",
+                    params![uuid, key, value],
+                )?;
+            }
+        }
+        if let Some(iptc) = &version.iptc {
+            for (key, value) in &iptc.bag {
+                let value = match value {
+                    IptcValue::None => None,
+                    IptcValue::Str(value) => Some(value),
+                };
+                tx.execute(
+                    "INSERT INTO iptc VALUES (?1, ?2, ?3)",
+                    params![uuid, key, value],
+                )?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test_export_sqlite() {
+    use crate::testutils;
+    use crate::{Error, Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_export_sqlite");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.load_volumes(PROGRESS_NONE).is_ok());
+    assert!(library.load_folders(PROGRESS_NONE).is_ok());
+    assert!(library.load_albums(PROGRESS_NONE).is_ok());
+    assert!(library.load_keywords(PROGRESS_NONE).is_ok());
+    assert!(library.load_masters(PROGRESS_NONE).is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let db_path = path.join("export.sqlite");
+    assert!(library.export_sqlite(&db_path).is_ok());
+    // Never write into an existing file.
+    assert!(matches!(
+        library.export_sqlite(&db_path),
+        Err(Error::Io(_, _))
+    ));
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let count = |table: &str| -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    };
+    assert_eq!(count("volumes"), 1);
+    assert_eq!(count("folders"), 1);
+    assert_eq!(count("albums"), 2);
+    assert_eq!(count("album_versions"), 1);
+    assert_eq!(count("keywords"), 2);
+    assert_eq!(count("masters"), 1);
+    assert_eq!(count("versions"), 2);
+
+    let (path_value, rating): (String, i64) = conn
+        .query_row(
+            "SELECT k.path, v.rating FROM version_keywords vk \
+             JOIN keywords k ON k.uuid = vk.keyword_uuid \
+             JOIN versions v ON v.uuid = vk.version_uuid",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(path_value, "Places|Paris");
+    assert_eq!(rating, 4);
+    let resolved: String = conn
+        .query_row("SELECT resolved_path FROM masters", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(resolved, "/Volumes/Photos/2011/img_8826.cr2");
+}
@@ -0,0 +1,63 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+/// Offset in seconds between the UNIX epoch and the Core Data
+/// reference date (2001-01-01 00:00:00 UTC) used for the timestamps.
+const REFERENCE_DATE_OFFSET: i64 = 978_307_200;
+
+/// Open the sqlite database at `path` read only.
+/// Will fail if the file doesn't exist instead of creating it.
+pub fn open_database<P>(path: P) -> rusqlite::Result<rusqlite::Connection>
+where
+    P: AsRef<Path>,
+{
+    use rusqlite::OpenFlags;
+
+    rusqlite::Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// Return true if the database `conn` has the table `name`.
+pub fn has_table(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Convert a Core Data timestamp to a date.
+pub fn date_from_timestamp(timestamp: f64) -> Option<DateTime<Utc>> {
+    let secs = timestamp.floor();
+    let nsecs = ((timestamp - secs) * 1_000_000_000.0) as u32;
+    DateTime::from_timestamp(secs as i64 + REFERENCE_DATE_OFFSET, nsecs)
+}
+
+/// Get the date in column `column` from `row`.
+pub fn get_date_value(
+    row: &rusqlite::Row,
+    column: &str,
+) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    let timestamp: Option<f64> = row.get(column)?;
+    Ok(timestamp.and_then(date_from_timestamp))
+}
+
+#[cfg(test)]
+#[test]
+fn test_date_from_timestamp() {
+    let date = date_from_timestamp(0.0).unwrap();
+    assert_eq!(date.to_rfc3339(), "2001-01-01T00:00:00+00:00");
+
+    let date = date_from_timestamp(336_528_000.5).unwrap();
+    assert_eq!(date.to_rfc3339(), "2011-09-01T00:00:00.500+00:00");
+}
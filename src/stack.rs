@@ -0,0 +1,142 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Stacks: versions grouped together, like the shots of a bracket.
+
+use std::collections::BTreeMap;
+
+use crate::Result;
+
+/// A stack of versions.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stack {
+    /// uuid of the stack, shared by its versions in `RKStackedVersion`.
+    pub uuid: String,
+    /// uuid of the versions, in the stack order.
+    pub versions: Vec<String>,
+    /// uuid of the pick, the version representing the stack.
+    pub pick: Option<String>,
+}
+
+impl Stack {
+    /// Whether the version `uuid` is the pick.
+    pub fn is_pick(&self, uuid: &str) -> bool {
+        self.pick.as_deref() == Some(uuid)
+    }
+
+    /// Build the stacks from the `rows`: the stack uuid, the version
+    /// uuid, the index in the stack and whether it is the pick.
+    /// Without a pick, the first version is. The stacks are sorted
+    /// by uuid.
+    pub(crate) fn from_rows<I>(rows: I) -> Vec<Stack>
+    where
+        I: IntoIterator<Item = (String, String, i64, bool)>,
+    {
+        let mut members: BTreeMap<String, Vec<(i64, String, bool)>> = BTreeMap::new();
+        for (stack_uuid, version_uuid, index, is_pick) in rows {
+            members
+                .entry(stack_uuid)
+                .or_default()
+                .push((index, version_uuid, is_pick));
+        }
+        members
+            .into_iter()
+            .map(|(uuid, mut versions)| {
+                versions.sort();
+                let pick = versions
+                    .iter()
+                    .find(|(_, _, is_pick)| *is_pick)
+                    .or_else(|| versions.first())
+                    .map(|(_, version, _)| version.clone());
+                Stack {
+                    uuid,
+                    versions: versions
+                        .into_iter()
+                        .map(|(_, version, _)| version)
+                        .collect(),
+                    pick,
+                }
+            })
+            .collect()
+    }
+
+    /// Load the stacks from `RKStackedVersion`.
+    pub fn from_db(conn: &rusqlite::Connection) -> Result<Vec<Stack>> {
+        let mut stmt = conn.prepare(
+            "SELECT sv.stackUuid, v.uuid, sv.stackIndex, sv.isPick FROM RKStackedVersion sv \
+             JOIN RKVersion v ON sv.versionId = v.modelId",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                    row.get::<_, Option<bool>>(3)?.unwrap_or(false),
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<(String, String, i64, bool)>>>()?;
+
+        Ok(Stack::from_rows(rows))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_stack_from_rows() {
+    let stacks = Stack::from_rows(vec![
+        ("B".to_string(), "v3".to_string(), 1, false),
+        ("A".to_string(), "v2".to_string(), 1, true),
+        ("B".to_string(), "v4".to_string(), 0, false),
+        ("A".to_string(), "v1".to_string(), 0, false),
+    ]);
+    assert_eq!(stacks.len(), 2);
+    assert_eq!(stacks[0].uuid, "A");
+    assert_eq!(stacks[0].versions, vec!["v1", "v2"]);
+    assert!(stacks[0].is_pick("v2"));
+    assert!(!stacks[0].is_pick("v1"));
+    // Without a pick, the first.
+    assert_eq!(stacks[1].versions, vec!["v4", "v3"]);
+    assert_eq!(stacks[1].pick.as_deref(), Some("v4"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_load_stacks() {
+    use crate::testutils;
+    use crate::{store, Library, LoadPolicy, PROGRESS_NONE};
+
+    let path = testutils::create_test_db_library("test_load_stacks");
+    let mut library = Library::new(&path);
+    library.set_load_policy(LoadPolicy::DatabaseOnly);
+    assert!(library.stacks().is_empty());
+    assert!(library.load_stacks().is_ok());
+    assert!(library.load_versions(PROGRESS_NONE).is_ok());
+
+    let stacks = library.stacks();
+    assert_eq!(stacks.len(), 1);
+    assert_eq!(
+        stacks[0].versions,
+        vec!["BF6nuoBnTumzoXyexdmXlw", "VF%CkiTKQy+h53Oyr7KCOA"]
+    );
+    let stack = library.stack_of("VF%CkiTKQy+h53Oyr7KCOA").unwrap();
+    assert_eq!(stack.uuid, "sT4ckKd0QmGm3yJx9Xq1bA");
+    assert!(stack.is_pick("BF6nuoBnTumzoXyexdmXlw"));
+    assert!(library.stack_of("JpLq7STrRMmgm5YZTm6IzA").is_none());
+
+    // Linked to the versions loaded after.
+    match library.get("BF6nuoBnTumzoXyexdmXlw") {
+        Some(store::Wrapper::Version(version)) => {
+            assert_eq!(
+                version.stack_uuid.as_deref(),
+                Some("sT4ckKd0QmGm3yJx9Xq1bA")
+            );
+            assert_eq!(version.is_stack_pick, Some(true));
+        }
+        _ => unreachable!(),
+    }
+}
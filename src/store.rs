@@ -6,16 +6,21 @@
 
 use crate::album::Album;
 use crate::folder::Folder;
+use crate::keyword::Keyword;
 use crate::master::Master;
+use crate::place::Place;
 use crate::version::Version;
 use crate::volume::Volume;
-use crate::AplibObject;
+use crate::{AplibObject, AplibType};
 
 /// Wrap an AplibObject to key it into the store.
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
 pub enum Wrapper {
     Album(Box<Album>),
     Folder(Box<Folder>),
+    Keyword(Box<Keyword>),
     Master(Box<Master>),
+    Place(Box<Place>),
     Version(Box<Version>),
     Volume(Box<Volume>),
     None,
@@ -27,20 +32,38 @@ impl Wrapper {
         match *self {
             Wrapper::Album(ref o) => o.uuid().clone(),
             Wrapper::Folder(ref o) => o.uuid().clone(),
+            Wrapper::Keyword(ref o) => o.uuid().clone(),
             Wrapper::Version(ref o) => o.uuid().clone(),
             Wrapper::Master(ref o) => o.uuid().clone(),
+            Wrapper::Place(ref o) => o.uuid().clone(),
             Wrapper::Volume(ref o) => o.uuid().clone(),
             Wrapper::None => None,
         }
     }
 
+    /// The type of the wrapped object.
+    pub fn obj_type(&self) -> Option<AplibType> {
+        match *self {
+            Wrapper::Album(ref o) => Some(o.obj_type()),
+            Wrapper::Folder(ref o) => Some(o.obj_type()),
+            Wrapper::Keyword(ref o) => Some(o.obj_type()),
+            Wrapper::Version(ref o) => Some(o.obj_type()),
+            Wrapper::Master(ref o) => Some(o.obj_type()),
+            Wrapper::Place(ref o) => Some(o.obj_type()),
+            Wrapper::Volume(ref o) => Some(o.obj_type()),
+            Wrapper::None => None,
+        }
+    }
+
     /// Extract the parent from the wrapper
     pub fn parent_uuid(&self) -> Option<String> {
         match *self {
             Wrapper::Album(ref o) => o.parent().clone(),
             Wrapper::Folder(ref o) => o.parent().clone(),
+            Wrapper::Keyword(ref o) => o.parent().clone(),
             Wrapper::Version(ref o) => o.parent().clone(),
             Wrapper::Master(ref o) => o.parent().clone(),
+            Wrapper::Place(ref o) => o.parent().clone(),
             Wrapper::Volume(ref o) => o.parent().clone(),
             Wrapper::None => None,
         }
@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 
 /// Return the testfile path for filename
 /// Test files are in the testdata directory in the crate top level.
@@ -11,3 +12,42 @@ pub fn get_test_file_path(filename: &str) -> PathBuf {
     path.push(filename);
     path
 }
+
+/// A library bundle in the temporary directory, removed when
+/// dropped, even if the test fails.
+pub struct TempLibrary(PathBuf);
+
+impl Deref for TempLibrary {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempLibrary {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempLibrary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Create a library bundle `name` in the temporary directory,
+/// with only a Library.apdb database created from `Library.sql`.
+pub fn create_test_db_library(name: &str) -> TempLibrary {
+    let path = std::env::temp_dir().join(format!("{}-{}.aplibrary", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let apdb = path.join("Database/apdb");
+    std::fs::create_dir_all(&apdb).expect("Couldn't create library");
+
+    let sql = std::fs::read_to_string(get_test_file_path("Library.sql")).unwrap();
+    let conn = rusqlite::Connection::open(apdb.join("Library.apdb")).unwrap();
+    conn.execute_batch(&sql).expect("Couldn't create database");
+
+    TempLibrary(path)
+}
@@ -0,0 +1,128 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Dates and time zones.
+//!
+//! Aperture stores the dates in UTC with the name of the time zone
+//! of the picture. The time zone of the camera clock can be different
+//! if it wasn't set when travelling: the EXIF capture date is then in
+//! the camera time zone.
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Resolve the time zone `name`. It's an IANA name like
+/// `America/Vancouver`.
+pub fn resolve_time_zone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+/// Return `date` in the time zone `zone`.
+pub fn to_local(date: &DateTime<Utc>, zone: &str) -> Option<DateTime<FixedOffset>> {
+    resolve_time_zone(zone).map(|tz| date.with_timezone(&tz).fixed_offset())
+}
+
+/// Return the local date `date` of the time zone `zone` in UTC. If
+/// the local date is ambiguous, the earliest is picked.
+pub fn from_local(date: &NaiveDateTime, zone: &str) -> Option<DateTime<Utc>> {
+    resolve_time_zone(zone)?
+        .from_local_datetime(date)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Return the capture date in the picture time zone, from the
+/// `camera_date` as set on the camera clock in the `camera_zone`.
+pub fn camera_to_picture(
+    camera_date: &NaiveDateTime,
+    camera_zone: &str,
+    picture_zone: &str,
+) -> Option<DateTime<FixedOffset>> {
+    to_local(&from_local(camera_date, camera_zone)?, picture_zone)
+}
+
+/// Return the capture date in the time zone of the picture
+/// `picture_zone`: the `image_date`, or without it the `camera_date`
+/// of the camera clock in `camera_zone`, the picture zone if `None`.
+/// Without the picture zone, it is the `image_date` in UTC.
+pub fn capture_date(
+    image_date: Option<&DateTime<Utc>>,
+    camera_date: Option<&NaiveDateTime>,
+    picture_zone: Option<&str>,
+    camera_zone: Option<&str>,
+) -> Option<DateTime<FixedOffset>> {
+    let picture_zone = match picture_zone {
+        Some(zone) => zone,
+        None => return image_date.map(DateTime::fixed_offset),
+    };
+    if let Some(local) = image_date.and_then(|date| to_local(date, picture_zone)) {
+        return Some(local);
+    }
+    camera_to_picture(
+        camera_date?,
+        camera_zone.unwrap_or(picture_zone),
+        picture_zone,
+    )
+}
+
+/// Format `date` for XMP.
+pub fn xmp_date(date: &DateTime<FixedOffset>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
+#[cfg(test)]
+#[test]
+fn test_time_zones() {
+    use chrono::NaiveDate;
+
+    assert!(resolve_time_zone("Europe/Paris").is_some());
+    assert!(resolve_time_zone("Somewhere/Else").is_none());
+
+    let date = Utc.with_ymd_and_hms(2007, 9, 17, 0, 5, 31).unwrap();
+    assert_eq!(
+        to_local(&date, "America/Vancouver").unwrap().to_rfc3339(),
+        "2007-09-16T17:05:31-07:00"
+    );
+
+    // The camera was still on Paris time in Montréal.
+    let camera_date = NaiveDate::from_ymd_opt(2010, 7, 1)
+        .unwrap()
+        .and_hms_opt(18, 0, 0)
+        .unwrap();
+    assert_eq!(
+        camera_to_picture(&camera_date, "Europe/Paris", "America/Montreal")
+            .unwrap()
+            .to_rfc3339(),
+        "2010-07-01T12:00:00-04:00"
+    );
+
+    let date = Utc.with_ymd_and_hms(2007, 9, 17, 0, 5, 31).unwrap();
+    let capture = |image_date, zones: (Option<&str>, Option<&str>)| {
+        capture_date(image_date, Some(&camera_date), zones.0, zones.1).map(|date| xmp_date(&date))
+    };
+    assert_eq!(
+        capture(
+            Some(&date),
+            (Some("America/Vancouver"), Some("Europe/Paris"))
+        )
+        .as_deref(),
+        Some("2007-09-16T17:05:31-07:00")
+    );
+    assert_eq!(
+        capture(None, (Some("America/Montreal"), Some("Europe/Paris"))).as_deref(),
+        Some("2010-07-01T12:00:00-04:00")
+    );
+    // The camera clock is in the picture time zone.
+    assert_eq!(
+        capture(None, (Some("America/Montreal"), None)).as_deref(),
+        Some("2010-07-01T18:00:00-04:00")
+    );
+    assert_eq!(
+        capture(Some(&date), (None, None)).as_deref(),
+        Some("2007-09-17T00:05:31+00:00")
+    );
+    assert!(capture(None, (None, Some("Europe/Paris"))).is_none());
+}
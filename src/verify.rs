@@ -0,0 +1,399 @@
+/*
+ This Source Code Form is subject to the terms of the Mozilla Public
+ License, v. 2.0. If a copy of the MPL was not distributed with this
+ file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Verification of the plists against the database.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::album::Album;
+use crate::folder::Folder;
+use crate::keyword::Keyword;
+use crate::master::Master;
+use crate::version::Version;
+use crate::volume::Volume;
+use crate::{AplibObject, AplibType};
+
+/// A field whose value differ between the plist and the database.
+#[derive(Debug)]
+pub struct Mismatch {
+    /// Type of the object.
+    pub obj_type: AplibType,
+    /// uuid of the object.
+    pub uuid: String,
+    /// Name of the property.
+    pub field: &'static str,
+    /// Value in the plist.
+    pub plist: String,
+    /// Value in the database.
+    pub database: String,
+}
+
+/// An object in the database whose parent isn't in the database.
+#[derive(Debug)]
+pub struct Orphan {
+    /// Type of the object.
+    pub obj_type: AplibType,
+    /// uuid of the object.
+    pub uuid: String,
+    /// uuid of the missing parent.
+    pub parent: String,
+}
+
+/// The result of the verification.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Fields whose value differ.
+    pub mismatches: Vec<Mismatch>,
+    /// Objects only found in the plists.
+    pub missing_in_database: Vec<(AplibType, String)>,
+    /// Objects only found in the database.
+    pub missing_in_plists: Vec<(AplibType, String)>,
+    /// Objects in the database whose parent isn't.
+    pub orphans: Vec<Orphan>,
+    /// modelId of the `RKAlbumVersion` rows whose album or version
+    /// doesn't exist.
+    pub orphan_album_versions: Vec<i64>,
+}
+
+impl VerifyReport {
+    /// Return true if no divergence was found.
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+            && self.missing_in_database.is_empty()
+            && self.missing_in_plists.is_empty()
+            && self.orphans.is_empty()
+            && self.orphan_album_versions.is_empty()
+    }
+}
+
+/// Object that can be verified field by field.
+pub(crate) trait Verifiable: AplibObject {
+    /// Return the fields to compare with their property name.
+    /// Only the fields loaded from both the plist and the database
+    /// are returned.
+    fn fields(&self) -> Vec<(&'static str, Option<String>)>;
+}
+
+fn value<T: Debug>(v: &Option<T>) -> Option<String> {
+    v.as_ref().map(|v| format!("{:?}", v))
+}
+
+/// Dates are compared to the second.
+fn date(v: &Option<DateTime<Utc>>) -> Option<String> {
+    v.map(|d| d.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Compare the model id. 0 is unset.
+fn model_id<T: AplibObject>(obj: &T) -> Option<String> {
+    Some(obj.model_id())
+        .filter(|id| *id != 0)
+        .map(|id| id.to_string())
+}
+
+/// Verify objects and accumulate the report.
+#[derive(Default)]
+pub(crate) struct Verifier {
+    report: VerifyReport,
+    /// Type and parent of the objects in the database, by uuid.
+    database: HashMap<String, (AplibType, Option<String>)>,
+}
+
+impl Verifier {
+    /// Compare the objects from the plists with the objects from the
+    /// database.
+    pub fn compare<T: Verifiable>(&mut self, plists: Vec<T>, database: Vec<T>) {
+        let mut database: HashMap<String, T> = database
+            .into_iter()
+            .filter_map(|obj| obj.uuid().clone().map(|uuid| (uuid, obj)))
+            .collect();
+        for obj in &database {
+            self.database
+                .insert(obj.0.clone(), (obj.1.obj_type(), obj.1.parent().clone()));
+        }
+
+        let mut seen = HashSet::new();
+        for obj in plists {
+            let uuid = match obj.uuid() {
+                Some(uuid) => uuid.clone(),
+                None => continue,
+            };
+            if let Some(db_obj) = database.remove(&uuid) {
+                let db_fields = db_obj.fields();
+                for ((field, plist), (_, database)) in obj.fields().into_iter().zip(db_fields) {
+                    // A value not set on one side isn't a divergence.
+                    if let (Some(plist), Some(database)) = (plist, database) {
+                        if plist != database {
+                            self.report.mismatches.push(Mismatch {
+                                obj_type: obj.obj_type(),
+                                uuid: uuid.clone(),
+                                field,
+                                plist,
+                                database,
+                            });
+                        }
+                    }
+                }
+            } else if !seen.contains(&uuid) {
+                self.report
+                    .missing_in_database
+                    .push((obj.obj_type(), uuid.clone()));
+            }
+            seen.insert(uuid);
+        }
+        for (uuid, obj) in database {
+            self.report.missing_in_plists.push((obj.obj_type(), uuid));
+        }
+    }
+
+    /// Add the orphan `RKAlbumVersion` rows.
+    pub fn orphan_album_versions(&mut self, mut ids: Vec<i64>) {
+        self.report.orphan_album_versions.append(&mut ids);
+    }
+
+    /// Finish the verification by looking for orphans and return
+    /// the report.
+    pub fn finish(mut self) -> VerifyReport {
+        for (uuid, (obj_type, parent)) in &self.database {
+            if let Some(parent) = parent {
+                if !parent.is_empty() && !self.database.contains_key(parent) {
+                    self.report.orphans.push(Orphan {
+                        obj_type: *obj_type,
+                        uuid: uuid.clone(),
+                        parent: parent.clone(),
+                    });
+                }
+            }
+        }
+
+        let report = &mut self.report;
+        report
+            .mismatches
+            .sort_by(|a, b| (&a.uuid, a.field).cmp(&(&b.uuid, b.field)));
+        report.missing_in_database.sort_by(|a, b| a.1.cmp(&b.1));
+        report.missing_in_plists.sort_by(|a, b| a.1.cmp(&b.1));
+        report.orphans.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        report.orphan_album_versions.sort();
+
+        self.report
+    }
+}
+
+impl Verifiable for Volume {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("modelId", model_id(self)),
+            ("volumeName", value(&self.volume_name)),
+            ("diskUuid", value(&self.disk_uuid)),
+        ]
+    }
+}
+
+impl Verifiable for Folder {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("modelId", model_id(self)),
+            ("parentFolderUuid", value(self.parent())),
+            ("folderType", value(&self.folder_type)),
+            ("folderPath", value(&self.path)),
+            ("name", value(&self.name)),
+            ("implicitAlbumUuid", value(&self.implicit_album_uuid)),
+            ("colorLabelIndex", value(&self.colour_label_index)),
+            ("createDate", date(&self.create_date)),
+            ("sortKeyPath", value(&self.sort_key_path)),
+            ("sortAscending", value(&self.sort_ascending)),
+            ("isHidden", value(&self.is_hidden)),
+            ("isMagic", value(&self.is_magic)),
+            ("isFavorite", value(&self.is_favourite)),
+            ("isInTrash", value(&self.is_in_trash)),
+            ("isExpanded", value(&self.is_expanded)),
+            ("isHiddenWhenEmpty", value(&self.is_hidden_when_empty)),
+            ("posterVersionUuid", value(&self.poster_version_uuid)),
+        ]
+    }
+}
+
+impl Verifiable for Album {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        let content = self.content.as_ref().map(|content| {
+            let mut content = content.clone();
+            content.sort();
+            content.join(",")
+        });
+        vec![
+            ("modelId", model_id(self)),
+            ("folderUuid", value(self.parent())),
+            ("albumSubclass", value(&self.subclass)),
+            ("albumType", value(&self.album_type)),
+            ("queryFolderUuid", value(&self.query_folder_uuid)),
+            ("sortAscending", value(&self.sort_asc)),
+            ("sortKeyPath", value(&self.sort_key)),
+            ("name", value(&self.name)),
+            ("customSortAvailable", value(&self.custom_sort_available)),
+            ("colorLabelIndex", value(&self.colour_label_index)),
+            ("createDate", date(&self.create_date)),
+            ("isHidden", value(&self.is_hidden)),
+            ("isMagic", value(&self.is_magic)),
+            ("isFavorite", value(&self.is_favourite)),
+            ("isInTrash", value(&self.is_in_trash)),
+            (
+                "selectedTrackPathUuid",
+                value(&self.selected_track_path_uuid),
+            ),
+            ("versionUuids", content),
+        ]
+    }
+}
+
+impl Verifiable for Keyword {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("modelId", model_id(self)),
+            ("parentUuid", value(self.parent())),
+            ("name", value(&self.name)),
+        ]
+    }
+}
+
+impl Verifiable for Master {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("modelId", model_id(self)),
+            ("projectUuid", value(self.parent())),
+            ("alternateMasterUuid", value(&self.alternate_master)),
+            ("originalVersionUuid", value(&self.original_version_uuid)),
+            ("importGroupUuid", value(&self.import_group_uuid)),
+            ("fileName", value(&self.filename)),
+            ("name", value(&self.name)),
+            ("originalVersionName", value(&self.original_version_name)),
+            ("originalFileName", value(&self.original_file_name)),
+            ("fileVolumeUuid", value(&self.file_volume_uuid)),
+            ("type", value(&self.master_type)),
+            ("subtype", value(&self.subtype)),
+            ("imagePath", value(&self.image_path)),
+            ("fileSize", value(&self.file_size)),
+            ("fileIsReference", value(&self.is_reference)),
+            ("isExternallyEditable", value(&self.is_externaly_editable)),
+            ("isInTrash", value(&self.is_in_trash)),
+            ("isMissing", value(&self.is_missing)),
+            ("isTrulyRaw", value(&self.is_truly_raw)),
+            ("colorSpaceName", value(&self.color_space_name)),
+            ("createDate", date(&self.create_date)),
+            ("imageDate", date(&self.image_date)),
+            ("fileCreationDate", date(&self.file_creation_date)),
+            ("fileModificationDate", date(&self.file_modification_date)),
+            ("hasFocusPoints", value(&self.has_focus_points)),
+            ("imageFormat", value(&self.image_format)),
+            ("pixelFormat", value(&self.pixel_format)),
+            ("faceDetectionState", value(&self.face_detection_state)),
+        ]
+    }
+}
+
+impl Verifiable for Version {
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("modelId", model_id(self)),
+            ("masterUuid", value(&self.master_uuid)),
+            ("projectUuid", value(&self.project_uuid)),
+            ("rawMasterUuid", value(&self.raw_master_uuid)),
+            ("nonRawMasterUuid", value(&self.nonraw_master_uuid)),
+            ("imageTimeZoneName", value(&self.timezone_name)),
+            ("createDate", date(&self.create_date)),
+            ("imageDate", date(&self.image_date)),
+            (
+                "exportImageChangeDate",
+                date(&self.export_image_change_date),
+            ),
+            (
+                "exportMetadataChangeDate",
+                date(&self.export_metadata_change_date),
+            ),
+            ("versionNumber", value(&self.version_number)),
+            ("isFlagged", value(&self.is_flagged)),
+            ("isOriginal", value(&self.is_original)),
+            ("isEditable", value(&self.is_editable)),
+            ("isHidden", value(&self.is_hidden)),
+            ("isInTrash", value(&self.is_in_trash)),
+            ("fileName", value(&self.file_name)),
+            ("name", value(&self.name)),
+            ("mainRating", value(&self.rating)),
+            ("rotation", value(&self.rotation)),
+            ("colorLabelIndex", value(&self.colour_label_index)),
+        ]
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_verify_database() {
+    use crate::testutils;
+    use crate::AplibType;
+    use crate::{Error, Library};
+    use std::fs;
+
+    let path = testutils::create_test_db_library("test_verify_database");
+    let database = path.join("Database");
+    fs::create_dir_all(database.join("Folders")).unwrap();
+    fs::copy(
+        testutils::get_test_file_path("a%TX9lmjQVWvuK9u6RNhGQ.apfolder"),
+        database
+            .join("Folders")
+            .join("a%TX9lmjQVWvuK9u6RNhGQ.apfolder"),
+    )
+    .unwrap();
+    // Same uuid as the master in the database.
+    let master_dir = database.join("Versions/2011/09/01/20110901-000000/img_8826.cr2");
+    fs::create_dir_all(&master_dir).unwrap();
+    fs::copy(
+        testutils::get_test_file_path("Master.apmaster"),
+        master_dir.join("Master.apmaster"),
+    )
+    .unwrap();
+
+    let mut library = Library::new(&path);
+    let report = library.verify_database().expect("Verification failed");
+    assert!(!report.is_consistent());
+
+    let image_path = report
+        .mismatches
+        .iter()
+        .find(|m| m.field == "imagePath")
+        .expect("imagePath mismatch not found");
+    assert_eq!(image_path.obj_type, AplibType::Master);
+    assert_eq!(image_path.uuid, "JpLq7STrRMmgm5YZTm6IzA");
+    assert_eq!(image_path.plist, "\"Vault/2007/20070602/img_8826.cr2\"");
+    assert_eq!(image_path.database, "\"2011/img_8826.cr2\"");
+
+    assert_eq!(
+        report.missing_in_database,
+        vec![(AplibType::Folder, "a%TX9lmjQVWvuK9u6RNhGQ".to_string())]
+    );
+    assert!(report
+        .missing_in_plists
+        .contains(&(AplibType::Folder, "evHgvM2oQ3GR0j6gEMnNTQ".to_string())));
+    assert!(report
+        .missing_in_plists
+        .contains(&(AplibType::Keyword, "hxLCb2k1T7mbDLm4PHdtHg".to_string())));
+    assert!(!report
+        .missing_in_plists
+        .iter()
+        .any(|(_, uuid)| uuid == "JpLq7STrRMmgm5YZTm6IzA"));
+
+    // The built-in folders aren't in the test database.
+    assert_eq!(report.orphans.len(), 2);
+    assert_eq!(report.orphans[0].parent, "AllProjectsItem");
+    assert_eq!(report.orphans[1].parent, "TopLevelAlbums");
+    assert_eq!(report.orphan_album_versions, vec![2]);
+
+    let mut library = Library::new("/nonexistent/Aperture Library.aplibrary");
+    assert!(matches!(
+        library.verify_database(),
+        Err(Error::MissingDatabase(_))
+    ));
+}
@@ -4,27 +4,38 @@
  file, You can obtain one at http://mozilla.org/MPL/2.0/.
 */
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use exempi2::Xmp;
 use std::path::Path;
 
+use crate::adjustments::Adjustments;
 use crate::audit::{
     audit_get_array_value, audit_get_bool_value, audit_get_date_value, audit_get_dict_value,
     audit_get_int_value, audit_get_str_value, Report, SkipReason,
 };
 use crate::custominfo::CustomInfoProperties;
 use crate::exif::ExifProperties;
+use crate::faces::FaceRegion;
 use crate::iptc::IptcProperties;
+use crate::place::location_shown_to_xmp;
 use crate::plutils::Value;
+use crate::sqlutils::get_date_value;
 use crate::store;
-use crate::xmp::ToXmp;
+use crate::time;
+use crate::xmp::ns::{NS_APLIB, NS_XMP, NS_XMP_DM};
+use crate::xmp::{
+    append_subject, face_regions_to_xmp, keyword_paths_to_xmp, FlagTarget, StackTarget, ToXmp,
+    XmpOptions, XmpReport,
+};
 use crate::AplibObject;
 use crate::AplibType;
 use crate::PlistLoadable;
+use crate::{Error, Result, SqliteLoadable};
 
 #[derive(Debug)]
 /// A rendered image. There is one for the orignal, and one per
 /// actual version. `Version` are associated to a `Master`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     uuid: Option<String>,
     model_id: Option<i64>,
@@ -56,28 +67,49 @@ pub struct Version {
     pub rating: Option<i64>,
     pub rotation: Option<i64>,
     pub colour_label_index: Option<i64>,
+    /// Width of the master in pixels.
+    pub master_width: Option<i64>,
+    /// Height of the master in pixels.
+    pub master_height: Option<i64>,
 
     pub iptc: Option<IptcProperties>,
     pub exif: Option<ExifProperties>,
     pub custom_info: Option<CustomInfoProperties>,
+    /// The keywords from the plist: the names separated by tabs,
+    /// leaf first.
     pub keywords: Option<Vec<Value>>,
+    /// The keyword paths, root first, when loaded from the database.
+    /// Linked by the library.
+    pub assigned_keywords: Option<Vec<Vec<String>>>,
+    /// The image adjustments.
+    pub adjustments: Option<Adjustments>,
+    /// The places the version is in, the names from the root. Linked
+    /// by the library.
+    pub places: Option<Vec<Vec<String>>>,
+    /// The faces in the master, with the names of the persons. Linked
+    /// by the library.
+    pub faces: Option<Vec<FaceRegion>>,
+    /// uuid of the stack the version is in. Linked by the library.
+    pub stack_uuid: Option<String>,
+    /// Whether the version is the pick of its stack.
+    pub is_stack_pick: Option<bool>,
 }
 
 impl PlistLoadable for Version {
     /// Load the version object from the plist at plist_path.
-    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Option<Version>
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Version>
     where
         P: AsRef<Path>,
     {
         use crate::plutils::*;
 
-        let plist = parse_plist(plist_path);
+        let plist = parse_plist(&plist_path)?;
         match plist {
             Value::Dictionary(ref dict) => {
                 let iptc = audit_get_dict_value(dict, "iptcProperties", &mut auditor);
                 let exif = audit_get_dict_value(dict, "exifProperties", &mut auditor);
                 let custom_info = audit_get_dict_value(dict, "customInfo", &mut auditor);
-                let result = Some(Version {
+                let result = Ok(Version {
                     uuid: audit_get_str_value(dict, "uuid", &mut auditor),
                     master_uuid: audit_get_str_value(dict, "masterUuid", &mut auditor),
                     project_uuid: audit_get_str_value(dict, "projectUuid", &mut auditor),
@@ -110,10 +142,18 @@ impl PlistLoadable for Version {
                     rating: audit_get_int_value(dict, "mainRating", &mut auditor),
                     rotation: audit_get_int_value(dict, "rotation", &mut auditor),
                     colour_label_index: audit_get_int_value(dict, "colorLabelIndex", &mut auditor),
+                    master_width: audit_get_int_value(dict, "masterWidth", &mut auditor),
+                    master_height: audit_get_int_value(dict, "masterHeight", &mut auditor),
                     iptc: IptcProperties::from(&iptc, &mut auditor),
                     exif: ExifProperties::from(&exif, &mut auditor),
                     custom_info: CustomInfoProperties::from(&custom_info, &mut auditor),
                     keywords: audit_get_array_value(dict, "keywords", &mut auditor),
+                    assigned_keywords: None,
+                    adjustments: Adjustments::from(dict, &mut auditor),
+                    places: None,
+                    faces: None,
+                    stack_uuid: None,
+                    is_stack_pick: None,
                 });
                 if let Some(auditor) = &mut auditor {
                     auditor.skip("statistics", SkipReason::Ignore);
@@ -121,15 +161,9 @@ impl PlistLoadable for Version {
                     auditor.skip("faceDetectionIsFromPreview", SkipReason::Ignore);
                     auditor.skip("processedHeight", SkipReason::Ignore);
                     auditor.skip("processedWidth", SkipReason::Ignore);
-                    auditor.skip("masterHeight", SkipReason::Ignore);
-                    auditor.skip("masterWidth", SkipReason::Ignore);
                     auditor.skip("supportedStatus", SkipReason::Ignore);
                     auditor.skip("showInLibrary", SkipReason::Ignore);
 
-                    auditor.skip("adjustmentProperties", SkipReason::Ignore); // don't know what to do yet
-                    auditor.skip("RKImageAdjustments", SkipReason::Ignore);
-                    auditor.skip("hasAdjustments", SkipReason::Ignore);
-                    auditor.skip("hasEnabledAdjustments", SkipReason::Ignore);
                     auditor.skip("renderVersion", SkipReason::Ignore);
 
                     auditor.skip("imageProxyState", SkipReason::Ignore);
@@ -138,8 +172,221 @@ impl PlistLoadable for Version {
                 }
                 result
             }
-            _ => None,
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
+        }
+    }
+}
+
+impl SqliteLoadable for Version {
+    fn tables() -> &'static str {
+        "RKVersion"
+    }
+
+    fn columns() -> &'static str {
+        "modelId, uuid, masterUuid, projectUuid, rawMasterUuid, nonRawMasterUuid, \
+         imageTimeZoneName, createDate, imageDate, exportImageChangeDate, \
+         exportMetadataChangeDate, versionNumber, isFlagged, isOriginal, isEditable, \
+         isHidden, isInTrash, fileName, name, mainRating, rotation, colorLabelIndex"
+    }
+
+    /// Load the version from the row. The EXIF and IPTC properties
+    /// are in a different database.
+    fn from_row(row: &rusqlite::Row) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Version {
+            uuid: row.get("uuid")?,
+            model_id: row.get("modelId")?,
+            master_uuid: row.get("masterUuid")?,
+            project_uuid: row.get("projectUuid")?,
+            raw_master_uuid: row.get("rawMasterUuid")?,
+            nonraw_master_uuid: row.get("nonRawMasterUuid")?,
+            timezone_name: row.get("imageTimeZoneName")?,
+            create_date: get_date_value(row, "createDate")?,
+            image_date: get_date_value(row, "imageDate")?,
+            export_image_change_date: get_date_value(row, "exportImageChangeDate")?,
+            export_metadata_change_date: get_date_value(row, "exportMetadataChangeDate")?,
+            version_number: row.get("versionNumber")?,
+            // Only in the plist.
+            db_version: None,
+            db_minor_version: None,
+            is_flagged: row.get("isFlagged")?,
+            is_original: row.get("isOriginal")?,
+            is_editable: row.get("isEditable")?,
+            is_hidden: row.get("isHidden")?,
+            is_in_trash: row.get("isInTrash")?,
+            file_name: row.get("fileName")?,
+            name: row.get("name")?,
+            rating: row.get("mainRating")?,
+            rotation: row.get("rotation")?,
+            colour_label_index: row.get("colorLabelIndex")?,
+            // Only in the plist.
+            master_width: None,
+            master_height: None,
+            iptc: None,
+            exif: None,
+            custom_info: None,
+            // From RKKeywordForVersion, filled by the library.
+            keywords: None,
+            assigned_keywords: None,
+            // Only in the plist.
+            adjustments: None,
+            places: None,
+            faces: None,
+            stack_uuid: None,
+            is_stack_pick: None,
+        })
+    }
+}
+
+impl Version {
+    /// Return the time zones of the picture and of the camera clock.
+    fn time_zones(&self) -> (Option<&str>, Option<&str>) {
+        let custom_info = self.custom_info.as_ref();
+        let picture_zone = custom_info
+            .and_then(|info| info.picture_time_zone_name.as_deref())
+            .or(self.timezone_name.as_deref());
+        let camera_zone = custom_info.and_then(|info| info.camera_time_zone_name.as_deref());
+        (picture_zone, camera_zone)
+    }
+
+    /// Return the local capture date, in the time zone of the picture.
+    /// See `time::capture_date()`.
+    pub fn capture_datetime_local(&self) -> Option<DateTime<FixedOffset>> {
+        let (picture_zone, camera_zone) = self.time_zones();
+        let capture_date = self.exif.as_ref().and_then(ExifProperties::capture_date);
+        time::capture_date(
+            self.image_date.as_ref(),
+            capture_date.as_ref(),
+            picture_zone,
+            camera_zone,
+        )
+    }
+
+    /// Return the rating for `xmp:Rating`: -1 for rejected, or
+    /// 0 to 5 stars.
+    pub fn xmp_rating(&self) -> Option<i64> {
+        self.rating
+            .map(|rating| if rating < 0 { -1 } else { rating.min(5) })
+    }
+
+    /// Push the rating, colour label and flag to `xmp`.
+    fn basic_to_xmp(&self, xmp: &mut Xmp, options: &XmpOptions) {
+        if let Some(rating) = self.xmp_rating() {
+            let _ = xmp.set_property_i64(NS_XMP, "Rating", rating, exempi2::PropFlags::NONE);
+        }
+        if let Some(label) = self
+            .colour_label_index
+            .and_then(|index| options.label_name(index))
+        {
+            let _ = xmp.set_property(NS_XMP, "Label", label, exempi2::PropFlags::NONE);
+        }
+        if self.is_flagged == Some(true) {
+            match options.flag {
+                FlagTarget::None => {}
+                FlagTarget::Good => {
+                    let _ =
+                        xmp.set_property_bool(NS_XMP_DM, "good", true, exempi2::PropFlags::NONE);
+                }
+                FlagTarget::Keyword(ref keyword) => {
+                    append_subject(keyword, xmp);
+                }
+            }
+        }
+    }
+
+    /// Push the stack to `xmp`, where `options` says.
+    fn stack_to_xmp(&self, xmp: &mut Xmp, options: &XmpOptions) {
+        let stack_uuid = match self.stack_uuid {
+            Some(ref uuid) => uuid,
+            None => return,
+        };
+        match options.stack {
+            StackTarget::None => {}
+            StackTarget::Keyword(ref prefix) => {
+                append_subject(&format!("{prefix}{stack_uuid}"), xmp);
+            }
+            StackTarget::Custom => {
+                let _ = exempi2::register_namespace(NS_APLIB, "aplib");
+                let _ =
+                    xmp.set_property(NS_APLIB, "StackUuid", stack_uuid, exempi2::PropFlags::NONE);
+                let _ = xmp.set_property_bool(
+                    NS_APLIB,
+                    "StackPick",
+                    self.is_stack_pick.unwrap_or(false),
+                    exempi2::PropFlags::NONE,
+                );
+            }
+        }
+    }
+
+    /// Return the size of the master, if known.
+    pub fn master_size(&self) -> Option<(i64, i64)> {
+        Some((self.master_width?, self.master_height?))
+    }
+
+    /// Return the size of the version: the crop of the master, rotated.
+    pub fn size(&self) -> Option<(i64, i64)> {
+        let (width, height) = self.master_size()?;
+        let (width, height) = match self
+            .adjustments
+            .as_ref()
+            .and_then(|adjustments| adjustments.crop_rect(Some((width, height))))
+        {
+            Some(crop) => (
+                (crop.width * width as f64).round() as i64,
+                (crop.height * height as f64).round() as i64,
+            ),
+            None => (width, height),
+        };
+        if self.rotation.unwrap_or(0).rem_euclid(180) == 90 {
+            Some((height, width))
+        } else {
+            Some((width, height))
+        }
+    }
+
+    /// Return the face regions normalized to the version: the part
+    /// in the crop, rotated. Without the master size the crop can't
+    /// be applied.
+    pub fn face_regions(&self) -> Vec<FaceRegion> {
+        let crop = self
+            .adjustments
+            .as_ref()
+            .and_then(|adjustments| adjustments.crop_rect(self.master_size()));
+        let rotation = self.rotation.unwrap_or(0);
+        self.faces
+            .iter()
+            .flatten()
+            .filter_map(|region| {
+                let rect = match crop {
+                    Some(ref crop) => region.rect.crop(crop)?,
+                    None => region.rect,
+                };
+                Some(FaceRegion {
+                    name: region.name.clone(),
+                    rect: rect.rotate(rotation),
+                })
+            })
+            .collect()
+    }
+
+    /// Return the hierarchical paths of the keywords, root first.
+    pub fn keyword_paths(&self) -> Vec<Vec<String>> {
+        if let Some(ref paths) = self.assigned_keywords {
+            return paths.clone();
         }
+        self.keywords
+            .as_ref()
+            .map(|keywords| {
+                keywords
+                    .iter()
+                    .filter_map(Value::as_string)
+                    .map(|keyword| keyword.split('\t').rev().map(str::to_owned).collect())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 }
 
@@ -166,14 +413,41 @@ impl AplibObject for Version {
 
 impl ToXmp for Version {
     fn to_xmp(&self, xmp: &mut Xmp) -> bool {
+        self.to_xmp_with_options(xmp, &XmpOptions::default(), &mut XmpReport::default())
+    }
+
+    fn to_xmp_with_options(
+        &self,
+        xmp: &mut Xmp,
+        options: &XmpOptions,
+        report: &mut XmpReport,
+    ) -> bool {
         // Here we make sure the Exif data are
         // processed before Iptc.
         if let Some(ref exif) = self.exif {
-            exif.to_xmp(xmp);
+            let (picture_zone, camera_zone) = self.time_zones();
+            exif.to_xmp_in_zones(xmp, picture_zone, camera_zone);
         }
         if let Some(ref iptc) = self.iptc {
             iptc.to_xmp(xmp);
         }
+        // After IPTC, to override the Keywords and StarRating.
+        keyword_paths_to_xmp(&self.keyword_paths(), xmp, options);
+        if let Some(ref places) = self.places {
+            location_shown_to_xmp(places, xmp);
+        }
+        face_regions_to_xmp(&self.face_regions(), self.size(), xmp, options);
+        self.basic_to_xmp(xmp, options);
+        self.stack_to_xmp(xmp, options);
+        if options.camera_raw {
+            if let Some(ref adjustments) = self.adjustments {
+                let settings = adjustments.camera_raw_settings(self.master_size());
+                settings.to_xmp(xmp);
+                report
+                    .untranslated_adjustments
+                    .extend(settings.untranslated.iter().cloned());
+            }
+        }
         true
     }
 }
@@ -189,7 +463,7 @@ fn test_version_parse() {
         testutils::get_test_file_path("Version-0.apversion").as_path(),
         None,
     );
-    assert!(version.is_some());
+    assert!(version.is_ok());
     let version = version.unwrap();
 
     assert_eq!(version.uuid.as_ref().unwrap(), "MHMIbw5CQaiMgQ3n7g2w2A");
@@ -199,6 +473,11 @@ fn test_version_parse() {
         "WZMCPPRHR%C3nffgeeS4IQ"
     );
     assert_eq!(version.name.as_ref().unwrap(), "img_3136");
+    assert_eq!(version.xmp_rating(), Some(0));
+    assert_eq!(
+        version.keyword_paths()[0],
+        vec!["+locations", "canada", "ontario", "toronto"]
+    );
     assert!(version.iptc.is_some());
     let iptc = version.iptc.as_ref().unwrap();
     assert!(iptc.bag.contains_key("Byline"));
@@ -223,4 +502,37 @@ fn test_version_parse() {
     let value = xmp.get_property(xmp::ns::NS_EXIF, "ApertureValue", &mut options);
     assert!(value.is_ok());
     assert_eq!(value.unwrap().to_str(), Ok("4"));
+
+    options = exempi2::PropFlags::NONE;
+    let value = xmp.get_property(xmp::ns::NS_XMP, "Rating", &mut options);
+    assert_eq!(value.unwrap().to_str(), Ok("0"));
+    // No colour label.
+    assert!(!xmp.has_property(xmp::ns::NS_XMP, "Label"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_capture_datetime_local() {
+    use crate::testutils;
+
+    let mut version = Version::from_path(
+        testutils::get_test_file_path("Version-0.apversion").as_path(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        version.capture_datetime_local().unwrap().to_rfc3339(),
+        "2007-09-16T17:05:31-07:00"
+    );
+
+    // The camera clock was on Toronto time.
+    version.image_date = None;
+    version.custom_info = Some(CustomInfoProperties {
+        camera_time_zone_name: Some("America/Toronto".to_string()),
+        picture_time_zone_name: Some("America/Vancouver".to_string()),
+    });
+    assert_eq!(
+        version.capture_datetime_local().unwrap().to_rfc3339(),
+        "2007-09-16T14:05:31-07:00"
+    );
 }
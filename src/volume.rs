@@ -8,8 +8,9 @@ use std::path::Path;
 
 use crate::audit::{audit_get_int_value, audit_get_str_value, Report};
 use crate::store;
-use crate::{AplibObject, AplibType, PlistLoadable, Result, SqliteLoadable};
+use crate::{AplibObject, AplibType, Error, PlistLoadable, Result, SqliteLoadable};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Volume {
     uuid: Option<String>,
     model_id: Option<i64>,
@@ -46,21 +47,21 @@ impl SqliteLoadable for Volume {
 
 impl PlistLoadable for Volume {
     /// Load the version object from the plist at plist_path.
-    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Option<Volume>
+    fn from_path<P>(plist_path: P, mut auditor: Option<&mut Report>) -> Result<Volume>
     where
         P: AsRef<Path>,
     {
         use crate::plutils::*;
 
-        let plist = parse_plist(plist_path);
+        let plist = parse_plist(&plist_path)?;
         match plist {
-            Value::Dictionary(ref dict) => Some(Volume {
+            Value::Dictionary(ref dict) => Ok(Volume {
                 uuid: audit_get_str_value(dict, "uuid", &mut auditor),
                 model_id: audit_get_int_value(dict, "modelId", &mut auditor),
                 disk_uuid: audit_get_str_value(dict, "diskUuid", &mut auditor),
                 volume_name: audit_get_str_value(dict, "volumeName", &mut auditor),
             }),
-            _ => None,
+            _ => Err(Error::InvalidData(plist_path.as_ref().to_path_buf())),
         }
     }
 }
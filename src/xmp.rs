@@ -6,16 +6,32 @@
 
 use exempi2::Xmp;
 
+use crate::faces::FaceRegion;
+
 /// Define namespace constants until we can get them out of Exempi.
 pub mod ns {
     pub const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
     pub const NS_IPTC4XMP: &str = "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/";
+    pub const NS_IPTC4XMP_EXT: &str = "http://iptc.org/std/Iptc4xmpExt/2008-02-29/";
     pub const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
     pub const NS_XMP_RIGHTS: &str = "http://ns.adobe.com/xap/1.0/rights/";
     pub const NS_PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
     pub const NS_EXIF: &str = "http://ns.adobe.com/exif/1.0/";
     pub const NS_EXIF_AUX: &str = "http://ns.adobe.com/exif/1.0/aux/";
     pub const NS_TIFF: &str = "http://ns.adobe.com/tiff/1.0/";
+    pub const NS_XMP_DM: &str = "http://ns.adobe.com/xmp/1.0/DynamicMedia/";
+    pub const NS_LIGHTROOM: &str = "http://ns.adobe.com/lightroom/1.0/";
+    pub const NS_DIGIKAM: &str = "http://www.digikam.org/ns/1.0/";
+    pub const NS_CAMERA_RAW: &str = "http://ns.adobe.com/camera-raw-settings/1.0/";
+    pub const NS_MWG_RS: &str = "http://www.metadataworkinggroup.com/schemas/regions/";
+    pub const NS_ST_AREA: &str = "http://ns.adobe.com/xmp/sType/Area#";
+    pub const NS_ST_DIM: &str = "http://ns.adobe.com/xap/1.0/sType/Dimensions#";
+    pub const NS_MP: &str = "http://ns.microsoft.com/photo/1.2/";
+    pub const NS_MP_RI: &str = "http://ns.microsoft.com/photo/1.2/t/RegionInfo#";
+    pub const NS_MP_REG: &str = "http://ns.microsoft.com/photo/1.2/t/Region#";
+    /// Our own, for what has no standard property. The properties
+    /// are in docs/format.md.
+    pub const NS_APLIB: &str = "http://ns.figuiere.net/aplib/1.0/";
 }
 
 #[derive(Clone, Debug)]
@@ -92,10 +108,306 @@ pub(crate) enum XmpTranslator {
     None,
 }
 
+/// The names of the Aperture colour labels, by index.
+pub const APERTURE_LABELS: [&str; 7] =
+    ["Red", "Orange", "Yellow", "Green", "Blue", "Purple", "Gray"];
+
+/// Where to put the flag of the versions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FlagTarget {
+    /// Don't output the flag.
+    None,
+    /// `xmpDM:good` set to true.
+    #[default]
+    Good,
+    /// A keyword in `dc:subject`.
+    Keyword(String),
+}
+
+/// Where to put the stack of the versions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum StackTarget {
+    /// Don't output the stack.
+    #[default]
+    None,
+    /// A keyword in `dc:subject`: the prefix followed by the stack
+    /// uuid.
+    Keyword(String),
+    /// `aplib:StackUuid` and `aplib:StackPick`.
+    Custom,
+}
+
+/// Options for the conversion to XMP.
+#[derive(Clone, Debug)]
+pub struct XmpOptions {
+    /// Output the adjustments as Camera Raw Settings (`crs:`).
+    pub camera_raw: bool,
+    /// The `xmp:Label` names, by colour label index. An empty
+    /// name means no label. Default to `APERTURE_LABELS`.
+    pub label_names: Vec<String>,
+    /// Where to put the flag.
+    pub flag: FlagTarget,
+    /// Also output the keyword paths to `digiKam:TagsList`.
+    pub digikam_tags: bool,
+    /// Also output the face regions as Microsoft Photo
+    /// `MP:RegionInfo`.
+    pub mp_regions: bool,
+    /// Where to put the stack.
+    pub stack: StackTarget,
+}
+
+impl Default for XmpOptions {
+    fn default() -> XmpOptions {
+        XmpOptions {
+            camera_raw: false,
+            label_names: APERTURE_LABELS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            flag: FlagTarget::default(),
+            digikam_tags: false,
+            mp_regions: false,
+            stack: StackTarget::default(),
+        }
+    }
+}
+
+impl XmpOptions {
+    /// Return the label name for the colour label `index`.
+    pub fn label_name(&self, index: i64) -> Option<&str> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.label_names.get(index))
+            .map(String::as_str)
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// What couldn't be converted to XMP.
+#[derive(Clone, Debug, Default)]
+pub struct XmpReport {
+    /// The enabled adjustments without a `crs:` equivalent. The
+    /// operation identifier, or `identifier.parameter` if only a
+    /// parameter is lost.
+    pub untranslated_adjustments: Vec<String>,
+}
+
+/// Replace `dc:subject` with the bag of `keywords`.
+pub(crate) fn keywords_to_subject<S: AsRef<str>>(keywords: &[S], xmp: &mut Xmp) {
+    let _ = xmp.delete_property(ns::NS_DC, "subject");
+    for keyword in keywords {
+        let _ = xmp.append_array_item(
+            ns::NS_DC,
+            "subject",
+            exempi2::PropFlags::VALUE_IS_ARRAY,
+            keyword.as_ref(),
+            exempi2::PropFlags::NONE,
+        );
+    }
+}
+
+/// Append `keyword` to `dc:subject` unless it is already there.
+pub(crate) fn append_subject(keyword: &str, xmp: &mut Xmp) {
+    let mut index = 1;
+    while let Ok(item) = xmp.get_array_item(
+        ns::NS_DC,
+        "subject",
+        index,
+        &mut exempi2::PropFlags::empty(),
+    ) {
+        if item.to_str() == Ok(keyword) {
+            return;
+        }
+        index += 1;
+    }
+    let _ = xmp.append_array_item(
+        ns::NS_DC,
+        "subject",
+        exempi2::PropFlags::VALUE_IS_ARRAY,
+        keyword,
+        exempi2::PropFlags::NONE,
+    );
+}
+
+/// Return the keywords for `dc:subject` from the keyword `paths`:
+/// every keyword and its ancestors, once.
+pub(crate) fn subject_keywords(paths: &[Vec<String>]) -> Vec<&str> {
+    let mut keywords: Vec<&str> = vec![];
+    for keyword in paths.iter().flatten() {
+        if !keywords.contains(&keyword.as_str()) {
+            keywords.push(keyword);
+        }
+    }
+    keywords
+}
+
+/// Push the hierarchical keyword `paths`, root first, to `xmp`:
+/// `dc:subject` and `lr:hierarchicalSubject`, and optionally
+/// `digiKam:TagsList`.
+pub(crate) fn keyword_paths_to_xmp(paths: &[Vec<String>], xmp: &mut Xmp, options: &XmpOptions) {
+    if paths.is_empty() {
+        return;
+    }
+    keywords_to_subject(&subject_keywords(paths), xmp);
+
+    let mut hierarchies = vec![(ns::NS_LIGHTROOM, "lr", "hierarchicalSubject", "|")];
+    if options.digikam_tags {
+        hierarchies.push((ns::NS_DIGIKAM, "digiKam", "TagsList", "/"));
+    }
+    for (namespace, prefix, property, separator) in hierarchies {
+        let _ = exempi2::register_namespace(namespace, prefix);
+        let _ = xmp.delete_property(namespace, property);
+        let array_flags = if property == "TagsList" {
+            exempi2::PropFlags::ARRAY_IS_ORDERED
+        } else {
+            exempi2::PropFlags::VALUE_IS_ARRAY
+        };
+        for path in paths {
+            let _ = xmp.append_array_item(
+                namespace,
+                property,
+                array_flags,
+                &path.join(separator),
+                exempi2::PropFlags::NONE,
+            );
+        }
+    }
+}
+
+/// Register `namespace` and return the prefix to compose the paths,
+/// with the colon.
+fn path_prefix(namespace: &str, suggested: &str) -> String {
+    exempi2::register_namespace(namespace, suggested)
+        .map(|prefix| prefix.to_string())
+        .unwrap_or_else(|_| format!("{suggested}:"))
+}
+
+/// Format the rectangle of `region` as the `MPReg:Rectangle` value:
+/// the top left corner, the width and the height.
+fn mp_rectangle(region: &FaceRegion) -> String {
+    let rect = &region.rect;
+    format!(
+        "{:.6}, {:.6}, {:.6}, {:.6}",
+        rect.x, rect.y, rect.width, rect.height
+    )
+}
+
+/// Replace the face regions in `xmp` with `regions`, normalized to
+/// the image of `dimensions` pixels if known. `mwg-rs:Regions` and,
+/// if set in `options`, `MP:RegionInfo`.
+pub(crate) fn face_regions_to_xmp(
+    regions: &[FaceRegion],
+    dimensions: Option<(i64, i64)>,
+    xmp: &mut Xmp,
+    options: &XmpOptions,
+) {
+    if regions.is_empty() {
+        return;
+    }
+    let mwg = path_prefix(ns::NS_MWG_RS, "mwg-rs");
+    let area = path_prefix(ns::NS_ST_AREA, "stArea");
+    let dim = path_prefix(ns::NS_ST_DIM, "stDim");
+    let _ = xmp.delete_property(ns::NS_MWG_RS, "Regions");
+    let _ = xmp.set_property(
+        ns::NS_MWG_RS,
+        "Regions",
+        "",
+        exempi2::PropFlags::VALUE_IS_STRUCT,
+    );
+    if let Some((width, height)) = dimensions {
+        let applied = format!("Regions/{mwg}AppliedToDimensions");
+        for (field, value) in [
+            ("w", width.to_string()),
+            ("h", height.to_string()),
+            ("unit", "pixel".to_string()),
+        ] {
+            let _ = xmp.set_property(
+                ns::NS_MWG_RS,
+                &format!("{applied}/{dim}{field}"),
+                &value,
+                exempi2::PropFlags::NONE,
+            );
+        }
+    }
+    let list = format!("Regions/{mwg}RegionList");
+    let _ = xmp.set_property(ns::NS_MWG_RS, &list, "", exempi2::PropFlags::VALUE_IS_ARRAY);
+    for (index, region) in regions.iter().enumerate() {
+        let item = format!("{list}[{}]", index + 1);
+        // The MWG area is the centre, the width and the height.
+        let (x, y) = region.rect.centre();
+        let mut fields = vec![
+            (format!("{mwg}Area/{area}x"), format!("{x:.6}")),
+            (format!("{mwg}Area/{area}y"), format!("{y:.6}")),
+            (
+                format!("{mwg}Area/{area}w"),
+                format!("{:.6}", region.rect.width),
+            ),
+            (
+                format!("{mwg}Area/{area}h"),
+                format!("{:.6}", region.rect.height),
+            ),
+            (format!("{mwg}Area/{area}unit"), "normalized".to_string()),
+            (format!("{mwg}Type"), "Face".to_string()),
+        ];
+        if let Some(ref name) = region.name {
+            fields.push((format!("{mwg}Name"), name.clone()));
+        }
+        for (field, value) in fields {
+            let _ = xmp.set_property(
+                ns::NS_MWG_RS,
+                &format!("{item}/{field}"),
+                &value,
+                exempi2::PropFlags::NONE,
+            );
+        }
+    }
+
+    if options.mp_regions {
+        path_prefix(ns::NS_MP, "MP");
+        let ri = path_prefix(ns::NS_MP_RI, "MPRI");
+        let reg = path_prefix(ns::NS_MP_REG, "MPReg");
+        let _ = xmp.delete_property(ns::NS_MP, "RegionInfo");
+        let _ = xmp.set_property(
+            ns::NS_MP,
+            "RegionInfo",
+            "",
+            exempi2::PropFlags::VALUE_IS_STRUCT,
+        );
+        let list = format!("RegionInfo/{ri}Regions");
+        let _ = xmp.set_property(ns::NS_MP, &list, "", exempi2::PropFlags::VALUE_IS_ARRAY);
+        for (index, region) in regions.iter().enumerate() {
+            let item = format!("{list}[{}]", index + 1);
+            let mut fields = vec![(format!("{reg}Rectangle"), mp_rectangle(region))];
+            if let Some(ref name) = region.name {
+                fields.push((format!("{reg}PersonDisplayName"), name.clone()));
+            }
+            for (field, value) in fields {
+                let _ = xmp.set_property(
+                    ns::NS_MP,
+                    &format!("{item}/{field}"),
+                    &value,
+                    exempi2::PropFlags::NONE,
+                );
+            }
+        }
+    }
+}
+
 /// Trait for conversion to XMP.
 pub trait ToXmp {
     /// Push the object properties to the `xmp` XMP meta.
     fn to_xmp(&self, xmp: &mut Xmp) -> bool;
+
+    /// Push the object properties to the `xmp` XMP meta according to
+    /// `options`. What is lost is added to `report`.
+    fn to_xmp_with_options(
+        &self,
+        xmp: &mut Xmp,
+        _options: &XmpOptions,
+        _report: &mut XmpReport,
+    ) -> bool {
+        self.to_xmp(xmp)
+    }
 }
 
 #[cfg(test)]
@@ -119,3 +431,74 @@ fn test_xmp() {
     assert!(value.is_ok());
     assert_eq!(value.unwrap().to_str(), Ok("Batman"));
 }
+
+#[cfg(test)]
+#[test]
+fn test_append_subject() {
+    let mut xmp = Xmp::new();
+    keywords_to_subject(&["cat", "flagged"], &mut xmp);
+    append_subject("flagged", &mut xmp);
+    append_subject("picked", &mut xmp);
+
+    let mut subject = vec![];
+    while let Ok(item) = xmp.get_array_item(
+        ns::NS_DC,
+        "subject",
+        subject.len() as i32 + 1,
+        &mut exempi2::PropFlags::empty(),
+    ) {
+        subject.push(item.to_string());
+    }
+    assert_eq!(subject, vec!["cat", "flagged", "picked"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_subject_keywords() {
+    let paths = vec![
+        vec!["+locations".to_string(), "canada".to_string()],
+        vec![
+            "+locations".to_string(),
+            "canada".to_string(),
+            "ontario".to_string(),
+        ],
+        vec!["cat".to_string()],
+    ];
+    assert_eq!(
+        subject_keywords(&paths),
+        vec!["+locations", "canada", "ontario", "cat"]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_label_name() {
+    let mut options = XmpOptions::default();
+    assert_eq!(options.label_name(0), Some("Red"));
+    assert_eq!(options.label_name(6), Some("Gray"));
+    assert_eq!(options.label_name(-1), None);
+    assert_eq!(options.label_name(7), None);
+
+    options.label_names[1] = String::new();
+    assert_eq!(options.label_name(1), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_mp_rectangle() {
+    use crate::rect::Rect;
+
+    let region = FaceRegion {
+        name: Some("Hubert".to_string()),
+        rect: Rect {
+            x: 0.25,
+            y: 0.5,
+            width: 0.125,
+            height: 0.2,
+        },
+    };
+    assert_eq!(
+        mp_rectangle(&region),
+        "0.250000, 0.500000, 0.125000, 0.200000"
+    );
+}
@@ -0,0 +1,13 @@
+-- Minimal subset of the Faces.db schema, for the tests.
+CREATE TABLE RKFaceName (modelId INTEGER PRIMARY KEY, uuid VARCHAR, faceKey INTEGER,
+  keyVersionUuid VARCHAR, name VARCHAR, fullName VARCHAR, email VARCHAR);
+CREATE TABLE RKDetectedFace (modelId INTEGER PRIMARY KEY, uuid VARCHAR, masterUuid VARCHAR,
+  faceKey INTEGER, topLeftX REAL, topLeftY REAL, bottomRightX REAL, bottomRightY REAL,
+  rejected INTEGER, ignore INTEGER);
+
+INSERT INTO RKFaceName VALUES (1, 'Ugl3Y1b8RMqXk6R5ZKkH2w', 7, 'BF6nuoBnTumzoXyexdmXlw',
+  'Hubert', 'Hubert Figuière', NULL);
+INSERT INTO RKDetectedFace VALUES (1, 'c2NGWvY3Q8yQ9nQzvE1Wbg', 'JpLq7STrRMmgm5YZTm6IzA',
+  7, 0.4, 0.8, 0.6, 0.5, 0, 0);
+INSERT INTO RKDetectedFace VALUES (2, 'x0I3T7o9QbK3B2cMpaJm8Q', 'JpLq7STrRMmgm5YZTm6IzA',
+  7, 0.1, 0.9, 0.2, 0.8, 1, 0);
@@ -0,0 +1,83 @@
+-- Minimal subset of the Library.apdb schema, for the tests.
+CREATE TABLE RKVolume (modelId INTEGER PRIMARY KEY, uuid VARCHAR, name VARCHAR, diskUuid VARCHAR);
+CREATE TABLE RKFolder (modelId INTEGER PRIMARY KEY, uuid VARCHAR, parentFolderUuid VARCHAR,
+  folderType INTEGER, folderPath VARCHAR, name VARCHAR, implicitAlbumUuid VARCHAR,
+  colorLabelIndex INTEGER, createDate TIMESTAMP, sortKeyPath VARCHAR, sortAscending INTEGER,
+  isHidden INTEGER, isMagic INTEGER, isFavorite INTEGER, isInTrash INTEGER,
+  isExpanded INTEGER, isHiddenWhenEmpty INTEGER, posterVersionUuid VARCHAR);
+CREATE TABLE RKAlbum (modelId INTEGER PRIMARY KEY, uuid VARCHAR, folderUuid VARCHAR,
+  albumSubclass INTEGER, albumType INTEGER, queryFolderUuid VARCHAR, sortAscending INTEGER,
+  sortKeyPath VARCHAR, name VARCHAR, createDate TIMESTAMP, colorLabelIndex INTEGER,
+  customSortAvailable INTEGER, isHidden INTEGER, isMagic INTEGER, isFavorite INTEGER,
+  isInTrash INTEGER, selectedTrackPathUuid VARCHAR);
+CREATE TABLE RKAlbumVersion (modelId INTEGER PRIMARY KEY, versionId INTEGER, albumId INTEGER);
+CREATE TABLE RKKeyword (modelId INTEGER PRIMARY KEY, uuid VARCHAR, name VARCHAR,
+  parentId INTEGER);
+CREATE TABLE RKKeywordForVersion (modelId INTEGER PRIMARY KEY, versionId INTEGER,
+  keywordId INTEGER);
+CREATE TABLE RKMaster (modelId INTEGER PRIMARY KEY, uuid VARCHAR, projectUuid VARCHAR,
+  alternateMasterUuid VARCHAR, originalVersionUuid VARCHAR, importGroupUuid VARCHAR,
+  fileName VARCHAR, name VARCHAR, originalVersionName VARCHAR, originalFileName VARCHAR,
+  fileVolumeUuid VARCHAR, type VARCHAR, subtype VARCHAR, imagePath VARCHAR,
+  fileSize INTEGER, fileIsReference INTEGER, isExternallyEditable INTEGER,
+  isInTrash INTEGER, isMissing INTEGER, isTrulyRaw INTEGER, colorSpaceName VARCHAR,
+  createDate TIMESTAMP, imageDate TIMESTAMP, fileCreationDate TIMESTAMP,
+  fileModificationDate TIMESTAMP, hasFocusPoints INTEGER, imageFormat INTEGER,
+  pixelFormat INTEGER, colorSpaceDefinition BLOB, faceDetectionState INTEGER);
+CREATE TABLE RKVersion (modelId INTEGER PRIMARY KEY, uuid VARCHAR, masterUuid VARCHAR,
+  projectUuid VARCHAR, rawMasterUuid VARCHAR, nonRawMasterUuid VARCHAR,
+  imageTimeZoneName VARCHAR, createDate TIMESTAMP, imageDate TIMESTAMP,
+  exportImageChangeDate TIMESTAMP, exportMetadataChangeDate TIMESTAMP,
+  versionNumber INTEGER, isFlagged INTEGER, isOriginal INTEGER, isEditable INTEGER,
+  isHidden INTEGER, isInTrash INTEGER, fileName VARCHAR, name VARCHAR,
+  mainRating INTEGER, rotation INTEGER, colorLabelIndex INTEGER);
+
+INSERT INTO RKVolume VALUES (1, 'vEyyIbUTQ5eKfCqU9oZ8uQ', 'Photos', '3D1B0F8C-5C3A-4A9B-9A7E-1F2E3D4C5B6A');
+INSERT INTO RKFolder (modelId, uuid, parentFolderUuid, folderType, folderPath, name,
+  implicitAlbumUuid, isInTrash)
+  VALUES (333, 'evHgvM2oQ3GR0j6gEMnNTQ', 'AllProjectsItem', 2, '1/3/333/', 'Paris',
+  'J0+f3AmESPer4GHGv4BgAQ', 0);
+INSERT INTO RKAlbum (modelId, uuid, folderUuid, albumSubclass, albumType, name)
+  VALUES (601, 'J0+f3AmESPer4GHGv4BgAQ', 'evHgvM2oQ3GR0j6gEMnNTQ', 1, 1, NULL);
+INSERT INTO RKAlbum (modelId, uuid, folderUuid, albumSubclass, albumType, name)
+  VALUES (181, 'x6yNun58SB2sImfCarTJHA', 'TopLevelAlbums', 3, 1, 'Flickr');
+INSERT INTO RKAlbumVersion VALUES (1, 2, 181);
+-- Version 99 doesn't exist.
+INSERT INTO RKAlbumVersion VALUES (2, 99, 181);
+INSERT INTO RKKeyword VALUES (1, 'ZqA2XTm9RNa%2nK2UyBqSQ', 'Places', NULL);
+INSERT INTO RKKeyword VALUES (2, 'hxLCb2k1T7mbDLm4PHdtHg', 'Paris', 1);
+INSERT INTO RKKeywordForVersion VALUES (1, 2, 2);
+INSERT INTO RKMaster (modelId, uuid, projectUuid, originalVersionUuid, fileName, name,
+  fileVolumeUuid, type, subtype, imagePath, fileIsReference, isInTrash, imageDate)
+  VALUES (1, 'JpLq7STrRMmgm5YZTm6IzA', 'evHgvM2oQ3GR0j6gEMnNTQ', 'VF%CkiTKQy+h53Oyr7KCOA',
+  'img_8826.cr2', 'img_8826', 'vEyyIbUTQ5eKfCqU9oZ8uQ', 'IMGT', 'RAWST',
+  '2011/img_8826.cr2', 1, 0, 336528000.0);
+INSERT INTO RKVersion (modelId, uuid, masterUuid, projectUuid, rawMasterUuid,
+  imageTimeZoneName, imageDate, versionNumber, isFlagged, isOriginal, isInTrash,
+  fileName, name, mainRating, colorLabelIndex)
+  VALUES (1, 'VF%CkiTKQy+h53Oyr7KCOA', 'JpLq7STrRMmgm5YZTm6IzA', 'evHgvM2oQ3GR0j6gEMnNTQ',
+  'JpLq7STrRMmgm5YZTm6IzA', 'Europe/Paris', 336528000.0, 0, 0, 1, 0,
+  'img_8826.cr2', 'img_8826', 0, NULL);
+INSERT INTO RKVersion (modelId, uuid, masterUuid, projectUuid, rawMasterUuid,
+  imageTimeZoneName, imageDate, versionNumber, isFlagged, isOriginal, isInTrash,
+  fileName, name, mainRating, colorLabelIndex)
+  VALUES (2, 'BF6nuoBnTumzoXyexdmXlw', 'JpLq7STrRMmgm5YZTm6IzA', 'evHgvM2oQ3GR0j6gEMnNTQ',
+  'JpLq7STrRMmgm5YZTm6IzA', 'Europe/Paris', 336528000.0, 1, 1, 0, 0,
+  'img_8826.cr2', 'img_8826 - Version 2', 4, 2);
+CREATE TABLE RKPlace (modelId INTEGER PRIMARY KEY, uuid VARCHAR, parentUuid VARCHAR,
+  defaultName VARCHAR, type INTEGER, userPlace INTEGER, centroid VARCHAR,
+  minLatitude REAL, minLongitude REAL, maxLatitude REAL, maxLongitude REAL);
+CREATE TABLE RKPlaceForVersion (modelId INTEGER PRIMARY KEY, versionId INTEGER,
+  placeId INTEGER);
+INSERT INTO RKPlace VALUES (1, 'Hk3Qx1tSRfO7nOQ2B4bD+g', NULL, 'France', 1, 0,
+  '46.2276,2.2137', 41.3, -5.2, 51.1, 9.6);
+INSERT INTO RKPlace VALUES (2, 'tS0Q7c3ZRJq6bFz0m9P4Ag', 'Hk3Qx1tSRfO7nOQ2B4bD+g',
+  'Île-de-France', 2, 0, '48.8499,2.6370', 48.12, 1.44, 49.24, 3.56);
+INSERT INTO RKPlace VALUES (3, 'WnU3MDx8Q0uAkWhd8y8vKw', 'tS0Q7c3ZRJq6bFz0m9P4Ag',
+  'Paris', 3, 0, '48.8566,2.3522', 48.81, 2.22, 48.91, 2.47);
+INSERT INTO RKPlaceForVersion VALUES (1, 2, 3);
+INSERT INTO RKPlaceForVersion VALUES (2, 2, 1);
+CREATE TABLE RKStackedVersion (modelId INTEGER PRIMARY KEY, stackUuid VARCHAR,
+  versionId INTEGER, stackIndex INTEGER, isPick INTEGER);
+INSERT INTO RKStackedVersion VALUES (1, 'sT4ckKd0QmGm3yJx9Xq1bA', 1, 1, 0);
+INSERT INTO RKStackedVersion VALUES (2, 'sT4ckKd0QmGm3yJx9Xq1bA', 2, 0, 1);